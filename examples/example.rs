@@ -1,8 +1,5 @@
-#![feature(plugin, custom_derive)]
-#![plugin(rocket_codegen)]
-
+#[macro_use]
 extern crate rocket;
-extern crate rocket_request_id;
 
 use rocket_request_id::{RequestID, RequestIDFairing};
 
@@ -12,9 +9,9 @@ fn get(req_id: RequestID) -> String {
     format!("My id is {}", id)
 }
 
-fn main() {
-    rocket::ignite()
+#[launch]
+fn rocket() -> _ {
+    rocket::build()
         .attach(RequestIDFairing)
         .mount("/", routes![get])
-        .launch();
 }
@@ -0,0 +1,26 @@
+#[macro_use]
+extern crate rocket;
+
+use opentelemetry::global;
+use opentelemetry_sdk::trace::TracerProvider;
+use rocket_request_id::{RequestID, RequestIDFairing};
+
+#[get("/")]
+fn get(req_id: RequestID) -> String {
+    format!("My id is {}", req_id)
+}
+
+#[launch]
+fn rocket() -> _ {
+    // Print each request's span as it ends; a real service would export them to a collector.
+    let provider = TracerProvider::builder()
+        .with_simple_exporter(opentelemetry_stdout::SpanExporter::default())
+        .build();
+    global::set_tracer_provider(provider);
+
+    rocket::build()
+        // With the trace context tracked, spans continue the traces in requests' traceparent
+        // headers, and responses' traceparent headers name the requests' spans.
+        .attach(RequestIDFairing::default().with_trace_context())
+        .mount("/", routes![get])
+}
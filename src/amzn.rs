@@ -0,0 +1,238 @@
+use rand::{thread_rng, Rng};
+use rocket::http::Header;
+use rocket::request::{FromRequest, Outcome as ReqOutcome, Request};
+use rocket::{Ignite, Rocket, Sentinel};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{fairing_missing, tracked_guard, RequestIDError};
+
+/// The header AWS load balancers and X-Ray propagate a request's trace in.
+pub const AMZN_TRACE_ID_HEADER: &str = "X-Amzn-Trace-Id";
+
+///
+/// The AWS `X-Amzn-Trace-Id` of a rocket request.
+///
+/// This is only available if the fairing was configured with
+/// `RequestIDFairing::with_amzn_trace_id`. In that case, a request which arrives with a valid
+/// `Root` in its `X-Amzn-Trace-Id` header keeps it (along with the header's other fields), and any
+/// other request gets a new root. The root is what shows up in load balancer access logs, so it's
+/// what correlates a request with them.
+///
+/// Either way, the request gets a new `Self` field of its own, following the X-Ray propagation
+/// rules: it replaces any `Self` field the upstream added. Malformed fields are dropped, rather
+/// than the whole header.
+///
+/// Its `Display` implementation renders the header to send with any requests made on this
+/// request's behalf, which is also the header the fairing sets on the response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AmznTraceId {
+    root: String,
+    parent: Option<String>,
+    upstream_self: Option<String>,
+    own_self: String,
+    // Any other fields (e.g. Sampled), as they arrived.
+    fields: Vec<String>,
+}
+
+impl AmznTraceId {
+    /// Returns the trace's root, e.g. `1-5759e988-bd862e3fe1be46a994272793`.
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
+    /// Returns the `Parent` field the upstream sent, if any.
+    pub fn parent(&self) -> Option<&str> {
+        self.parent.as_ref().map(|parent| &parent[..])
+    }
+
+    /// Returns the `Self` field the upstream sent, if any.
+    pub fn upstream_self(&self) -> Option<&str> {
+        self.upstream_self.as_ref().map(|id| &id[..])
+    }
+
+    /// Returns this request's own `Self` field.
+    pub fn self_id(&self) -> &str {
+        &self.own_self
+    }
+
+    /// Returns the header to send with requests made on this request's behalf to propagate its
+    /// trace.
+    pub fn header(&self) -> Header<'static> {
+        Header::new(AMZN_TRACE_ID_HEADER, self.to_string())
+    }
+
+    /// Returns a new trace ID for a request with the given `X-Amzn-Trace-Id` header, or none. If
+    /// the header has no valid `Root`, a new one is generated, and the header's other fields are
+    /// discarded.
+    pub fn for_request(header: Option<&str>) -> AmznTraceId {
+        let mut trace = AmznTraceId {
+            root: String::new(),
+            parent: None,
+            upstream_self: None,
+            own_self: generate_id(),
+            fields: Vec::new(),
+        };
+        let mut keys: Vec<&str> = Vec::new();
+        for field in header.into_iter().flat_map(|header| header.split(';')) {
+            let field = field.trim();
+            if field.is_empty() {
+                continue;
+            }
+            let mut kv = field.splitn(2, '=');
+            let (key, value) = (kv.next().unwrap(), kv.next().unwrap_or(""));
+            let valid = match key {
+                _ if keys.contains(&key) => false,
+                "Root" | "Self" => is_trace_id(value),
+                "Parent" => value.len() == 16 && value.bytes().all(|b| b.is_ascii_hexdigit()),
+                _ => is_valid_field(key, value),
+            };
+            if !valid {
+                debug!("dropping X-Amzn-Trace-Id field {:?}", field);
+                continue;
+            }
+            keys.push(key);
+            match key {
+                "Root" => trace.root = value.to_string(),
+                "Parent" => trace.parent = Some(value.to_string()),
+                "Self" => trace.upstream_self = Some(value.to_string()),
+                _ => trace.fields.push(field.to_string()),
+            }
+        }
+
+        if trace.root.is_empty() {
+            if let Some(header) = header {
+                debug!("ignoring X-Amzn-Trace-Id without a valid root {:?}", header);
+            }
+            trace.root = generate_id();
+            trace.parent = None;
+            trace.upstream_self = None;
+            trace.fields.clear();
+        }
+        trace
+    }
+}
+
+impl fmt::Display for AmznTraceId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Root={}", self.root)?;
+        if let Some(ref parent) = self.parent {
+            write!(f, ";Parent={}", parent)?;
+        }
+        for field in &self.fields {
+            write!(f, ";{}", field)?;
+        }
+        write!(f, ";Self={}", self.own_self)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AmznTraceId {
+    type Error = RequestIDError;
+
+    async fn from_request(request: &'r Request<'_>) -> ReqOutcome<Self, Self::Error> {
+        tracked_guard(request, "X-Amzn-Trace-Id", |tracked| tracked.amzn.clone())
+    }
+}
+
+impl Sentinel for AmznTraceId {
+    fn abort(rocket: &Rocket<Ignite>) -> bool {
+        fairing_missing(rocket, "AmznTraceId")
+    }
+}
+
+// is_trace_id checks that id is an X-Ray trace ID: a version (1), the epoch time in seconds as 8
+// hex digits, and 96 bits of hex.
+fn is_trace_id(id: &str) -> bool {
+    let parts: Vec<&str> = id.split('-').collect();
+    let hex = |part: &str, len| part.len() == len && part.bytes().all(|b| b.is_ascii_hexdigit());
+    parts.len() == 3 && parts[0] == "1" && hex(parts[1], 8) && hex(parts[2], 24)
+}
+
+// is_valid_field checks that a field we don't know about can be passed on safely.
+fn is_valid_field(key: &str, value: &str) -> bool {
+    let key_char = |c: u8| c.is_ascii_alphanumeric() || c == b'-' || c == b'_';
+    let value_char = |c: u8| c > 0x20 && c < 0x7f && c != b';' && c != b',' && c != b'=';
+    !key.is_empty() && key.bytes().all(key_char) && !value.is_empty()
+        && value.bytes().all(value_char)
+}
+
+// generate_id generates a new X-Ray trace ID.
+fn generate_id() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0);
+    let random = (thread_rng().gen::<u32>() as u128) << 64 | thread_rng().gen::<u64>() as u128;
+    format!("1-{:08x}-{:024x}", now as u32, random)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_alb_root() {
+        let header = "Root=1-5759e988-bd862e3fe1be46a994272793;Sampled=1";
+        let trace = AmznTraceId::for_request(Some(header));
+
+        assert_eq!(trace.root(), "1-5759e988-bd862e3fe1be46a994272793");
+        assert_eq!(trace.parent(), None);
+        assert_eq!(trace.upstream_self(), None);
+        assert!(is_trace_id(trace.self_id()));
+        assert_eq!(trace.to_string(), format!("{};Self={}", header, trace.self_id()));
+    }
+
+    #[test]
+    fn replaces_upstream_self() {
+        let header = "Self=1-67891234-123456789abcdef012345678;\
+                      Root=1-67891233-abcdef012345678912345678;\
+                      Parent=53995c3f42cd8ad8;Sampled=0;CalledFrom=app";
+        let trace = AmznTraceId::for_request(Some(header));
+
+        assert_eq!(trace.root(), "1-67891233-abcdef012345678912345678");
+        assert_eq!(trace.parent(), Some("53995c3f42cd8ad8"));
+        assert_eq!(trace.upstream_self(), Some("1-67891234-123456789abcdef012345678"));
+        assert_ne!(trace.self_id(), "1-67891234-123456789abcdef012345678");
+        assert_eq!(
+            trace.to_string(),
+            format!(
+                "Root=1-67891233-abcdef012345678912345678;Parent=53995c3f42cd8ad8;Sampled=0;\
+                 CalledFrom=app;Self={}",
+                trace.self_id()
+            )
+        );
+    }
+
+    #[test]
+    fn skips_malformed_fields() {
+        let header = "Root=1-5759e988-bd862e3fe1be46a994272793; Parent=xyz;Self=2-0-0;\
+                      Sampled;Root=1-00000000-000000000000000000000000;bad key=1;Lineage=a:1|b:2";
+        let trace = AmznTraceId::for_request(Some(header));
+
+        assert_eq!(trace.root(), "1-5759e988-bd862e3fe1be46a994272793");
+        assert_eq!(trace.parent(), None);
+        assert_eq!(trace.upstream_self(), None);
+        assert_eq!(
+            trace.to_string(),
+            format!(
+                "Root=1-5759e988-bd862e3fe1be46a994272793;Lineage=a:1|b:2;Self={}",
+                trace.self_id()
+            )
+        );
+    }
+
+    #[test]
+    fn generates_root() {
+        for header in [None, Some(""), Some("Root=1-xyz;Sampled=1"), Some("garbage")].iter() {
+            let trace = AmznTraceId::for_request(*header);
+
+            assert!(is_trace_id(trace.root()));
+            assert_ne!(trace.root(), trace.self_id());
+            assert_eq!(
+                trace.to_string(),
+                format!("Root={};Self={}", trace.root(), trace.self_id())
+            );
+        }
+    }
+}
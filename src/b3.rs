@@ -0,0 +1,507 @@
+use rand::{thread_rng, Rng};
+use rocket::http::{Header, HeaderMap};
+use rocket::request::{FromRequest, Outcome as ReqOutcome, Request};
+use rocket::{Ignite, Rocket, Sentinel};
+use std::fmt;
+
+use super::{fairing_missing, hex, random_nonzero_u64, tracked_guard, RequestIDError};
+
+/// The header Zipkin's B3 single-header format propagates a request's trace in.
+pub const B3_HEADER: &str = "b3";
+
+// The headers Zipkin's older B3 multi-header format propagates a request's trace in.
+
+/// The trace's ID.
+pub const X_B3_TRACE_ID_HEADER: &str = "X-B3-TraceId";
+/// The sender's span ID.
+pub const X_B3_SPAN_ID_HEADER: &str = "X-B3-SpanId";
+/// The sender's parent's span ID.
+pub const X_B3_PARENT_SPAN_ID_HEADER: &str = "X-B3-ParentSpanId";
+/// The sampling decision, as `1` or `0`.
+pub const X_B3_SAMPLED_HEADER: &str = "X-B3-Sampled";
+/// `1` if the trace is being debugged, which implies it's sampled.
+pub const X_B3_FLAGS_HEADER: &str = "X-B3-Flags";
+
+/// A B3 sampling decision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sampling {
+    /// The trace isn't recorded (`0`).
+    Deny,
+    /// The trace is recorded (`1`).
+    Accept,
+    /// The trace is recorded, and should be even if it would otherwise be rate limited (`d`).
+    Debug,
+}
+
+impl Sampling {
+    fn parse(field: &str) -> Option<Sampling> {
+        match field {
+            "0" => Some(Sampling::Deny),
+            "1" => Some(Sampling::Accept),
+            "d" => Some(Sampling::Debug),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Sampling {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Sampling::Deny => "0",
+            Sampling::Accept => "1",
+            Sampling::Debug => "d",
+        })
+    }
+}
+
+///
+/// The [Zipkin B3](https://github.com/openzipkin/b3-propagation) trace context of a rocket request.
+///
+/// This is only available if the fairing was configured with `RequestIDFairing::with_b3` or
+/// `with_b3_multi`. In that case, a request which arrives with a valid `b3` header (or
+/// `X-B3-TraceId` header) continues that trace, and any other request starts a new one; either
+/// way, the request gets a newly generated span ID of its own. The trace ID is the same for every
+/// service the trace passes through, so it's what correlates their logs.
+///
+/// Upstreams which don't record a trace may send just their sampling decision (e.g. `b3: 0`, or
+/// only `X-B3-Sampled: 0`). The request then starts a new trace, but keeps the decision.
+///
+/// Its `Display` implementation renders the `b3` header to send with any requests made on this
+/// request's behalf; `multi_headers` returns the equivalent multi-header set. The fairing sets
+/// whichever it's configured to read on the response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct B3 {
+    trace_id: u128,
+    // Whether the trace ID is 128 bits wide, rather than 64, so that it's propagated as it arrived.
+    wide: bool,
+    span_id: u64,
+    parent_id: Option<u64>,
+    sampling: Option<Sampling>,
+}
+
+impl B3 {
+    /// Returns the ID of the trace this request is part of. 64-bit trace IDs are zero-extended.
+    pub fn trace_id(&self) -> u128 {
+        self.trace_id
+    }
+
+    /// Returns the span ID the upstream sent, or `None` if this request started a new trace (or the
+    /// upstream sent an `X-B3-TraceId` without an `X-B3-SpanId`).
+    pub fn parent_id(&self) -> Option<u64> {
+        self.parent_id
+    }
+
+    /// Returns this request's own span ID.
+    pub fn span_id(&self) -> u64 {
+        self.span_id
+    }
+
+    /// Returns the upstream's sampling decision, if it made one.
+    pub fn sampling(&self) -> Option<Sampling> {
+        self.sampling
+    }
+
+    /// Returns the header to send with requests made on this request's behalf to propagate its
+    /// trace.
+    pub fn header(&self) -> Header<'static> {
+        Header::new(B3_HEADER, self.to_string())
+    }
+
+    /// Returns the headers to send with requests made on this request's behalf to propagate its
+    /// trace to services which only understand B3's multi-header format.
+    pub fn multi_headers(&self) -> Vec<Header<'static>> {
+        let trace_id = if self.wide {
+            format!("{:032x}", self.trace_id)
+        } else {
+            format!("{:016x}", self.trace_id)
+        };
+        let mut headers = vec![
+            Header::new(X_B3_TRACE_ID_HEADER, trace_id),
+            Header::new(X_B3_SPAN_ID_HEADER, format!("{:016x}", self.span_id)),
+        ];
+        if let Some(parent_id) = self.parent_id {
+            headers.push(Header::new(X_B3_PARENT_SPAN_ID_HEADER, format!("{:016x}", parent_id)));
+        }
+        // Debug implies that the trace is sampled, so X-B3-Sampled isn't sent with it.
+        match self.sampling {
+            Some(Sampling::Debug) => headers.push(Header::new(X_B3_FLAGS_HEADER, "1")),
+            Some(sampling) => headers.push(Header::new(X_B3_SAMPLED_HEADER, sampling.to_string())),
+            None => {}
+        }
+        headers
+    }
+
+    /// Returns a new trace context for a request with the given `b3` header, or none. If the
+    /// header is malformed, a new trace is started.
+    pub fn for_request(header: Option<&str>) -> B3 {
+        match header.map(|header| (header, parse(header))) {
+            Some((_, Some(Parsed::Trace(parent)))) => B3 {
+                trace_id: parent.trace_id,
+                wide: parent.wide,
+                span_id: random_nonzero_u64(),
+                parent_id: Some(parent.span_id),
+                sampling: parent.sampling,
+            },
+            Some((_, Some(Parsed::Sampling(sampling)))) => B3 {
+                sampling: Some(sampling),
+                ..B3::start()
+            },
+            Some((header, None)) => {
+                debug!("ignoring malformed b3 header {:?}", header);
+                B3::start()
+            }
+            None => B3::start(),
+        }
+    }
+
+    /// Returns a new trace context for a request with the given B3 multi-header format headers. A
+    /// missing or malformed `X-B3-TraceId` starts a new trace, but the upstream's sampling decision
+    /// is kept either way.
+    pub fn for_multi_headers(headers: &HeaderMap) -> B3 {
+        let sampling = multi_sampling(headers);
+        let trace = match headers.get_one(X_B3_TRACE_ID_HEADER) {
+            Some(header) => {
+                let trace = trace_id(header);
+                if trace.is_none() {
+                    debug!("ignoring malformed X-B3-TraceId {:?}", header);
+                }
+                trace
+            }
+            None => None,
+        };
+        let (trace_id, wide) = match trace {
+            Some(trace) => trace,
+            None => {
+                return B3 {
+                    sampling,
+                    ..B3::start()
+                }
+            }
+        };
+
+        let parent_id = match headers.get_one(X_B3_SPAN_ID_HEADER) {
+            Some(header) => {
+                let span_id = id64(header);
+                if span_id.is_none() {
+                    debug!("ignoring malformed X-B3-SpanId {:?}", header);
+                }
+                span_id
+            }
+            None => None,
+        };
+        B3 {
+            trace_id,
+            wide,
+            span_id: random_nonzero_u64(),
+            parent_id,
+            sampling,
+        }
+    }
+
+    fn start() -> B3 {
+        B3 {
+            trace_id: (random_nonzero_u64() as u128) << 64 | thread_rng().gen::<u64>() as u128,
+            wide: true,
+            span_id: random_nonzero_u64(),
+            parent_id: None,
+            sampling: None,
+        }
+    }
+}
+
+impl fmt::Display for B3 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.wide {
+            write!(f, "{:032x}", self.trace_id)?;
+        } else {
+            write!(f, "{:016x}", self.trace_id)?;
+        }
+        write!(f, "-{:016x}", self.span_id)?;
+        // The parent span ID can only follow a sampling decision.
+        if let Some(sampling) = self.sampling {
+            write!(f, "-{}", sampling)?;
+            if let Some(parent_id) = self.parent_id {
+                write!(f, "-{:016x}", parent_id)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for B3 {
+    type Error = RequestIDError;
+
+    async fn from_request(request: &'r Request<'_>) -> ReqOutcome<Self, Self::Error> {
+        tracked_guard(request, "b3 trace context", |tracked| tracked.b3.clone())
+    }
+}
+
+impl Sentinel for B3 {
+    fn abort(rocket: &Rocket<Ignite>) -> bool {
+        fairing_missing(rocket, "B3")
+    }
+}
+
+// Parsed is what a valid b3 header says: either the upstream's span, or only its sampling decision.
+#[derive(Debug, PartialEq, Eq)]
+enum Parsed {
+    Trace(Parent),
+    Sampling(Sampling),
+}
+
+// Parent is the upstream's span, as described by a b3 header.
+#[derive(Debug, PartialEq, Eq)]
+struct Parent {
+    trace_id: u128,
+    wide: bool,
+    span_id: u64,
+    sampling: Option<Sampling>,
+}
+
+// parse parses a b3 header: {TraceId}-{SpanId}[-{SamplingState}[-{ParentSpanId}]], or a bare
+// {SamplingState}. The upstream's own parent span ID is checked, but isn't needed.
+fn parse(header: &str) -> Option<Parsed> {
+    let mut fields = header.split('-');
+    let first = fields.next().unwrap();
+    let span = match fields.next() {
+        Some(span) => span,
+        None => return Sampling::parse(first).map(Parsed::Sampling),
+    };
+
+    let (trace_id, wide) = trace_id(first)?;
+    let span_id = id64(span)?;
+
+    let sampling = match fields.next() {
+        Some(field) => Some(Sampling::parse(field)?),
+        None => None,
+    };
+    if let Some(parent) = fields.next() {
+        id64(parent)?;
+    }
+    if fields.next().is_some() {
+        return None;
+    }
+
+    Some(Parsed::Trace(Parent {
+        trace_id,
+        wide,
+        span_id,
+        sampling,
+    }))
+}
+
+// multi_sampling reads the upstream's sampling decision from B3 multi-header format headers. Old
+// implementations send X-B3-Sampled as true or false.
+fn multi_sampling(headers: &HeaderMap) -> Option<Sampling> {
+    if headers.get_one(X_B3_FLAGS_HEADER) == Some("1") {
+        return Some(Sampling::Debug);
+    }
+    match headers.get_one(X_B3_SAMPLED_HEADER) {
+        Some("1") | Some("true") => Some(Sampling::Accept),
+        Some("0") | Some("false") => Some(Sampling::Deny),
+        Some(header) => {
+            debug!("ignoring malformed X-B3-Sampled {:?}", header);
+            None
+        }
+        None => None,
+    }
+}
+
+// trace_id parses a nonzero 64 or 128-bit trace ID, and returns whether it was 128 bits.
+pub fn trace_id(field: &str) -> Option<(u128, bool)> {
+    if field.len() != 16 && field.len() != 32 {
+        return None;
+    }
+    match hex(field)? {
+        0 => None,
+        id => Some((id, field.len() == 32)),
+    }
+}
+
+// id64 parses a nonzero 64-bit span ID.
+fn id64(field: &str) -> Option<u64> {
+    if field.len() != 16 {
+        return None;
+    }
+    match hex(field)? {
+        0 => None,
+        id => Some(id as u64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_headers() {
+        assert_eq!(
+            parse("80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1-1-05e3ac9a4f6e3b90"),
+            Some(Parsed::Trace(Parent {
+                trace_id: 0x80f198ee56343ba864fe8b2a57d3eff7,
+                wide: true,
+                span_id: 0xe457b5a2e4d86bd1,
+                sampling: Some(Sampling::Accept),
+            }))
+        );
+        assert_eq!(
+            parse("a3ce929d0e0e4736-00f067aa0ba902b7"),
+            Some(Parsed::Trace(Parent {
+                trace_id: 0xa3ce929d0e0e4736,
+                wide: false,
+                span_id: 0x00f067aa0ba902b7,
+                sampling: None,
+            }))
+        );
+        assert_eq!(parse("0"), Some(Parsed::Sampling(Sampling::Deny)));
+        assert_eq!(parse("d"), Some(Parsed::Sampling(Sampling::Debug)));
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        let malformed = [
+            "",
+            "true",
+            "80f198ee56343ba864fe8b2a57d3eff7",
+            "80f198ee56343ba864fe8b2a57d3eff7-",
+            "80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1-2",
+            "80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1-1-05e3ac9a",
+            "80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1-1-05e3ac9a4f6e3b90-x",
+            "80F198EE56343BA864FE8B2A57D3EFF7-E457B5A2E4D86BD1",
+            "80f198ee56343ba864fe8b2a57d3ef-e457b5a2e4d86bd1",
+            "00000000000000000000000000000000-e457b5a2e4d86bd1",
+            "80f198ee56343ba864fe8b2a57d3eff7-0000000000000000",
+        ];
+        for header in malformed.iter() {
+            assert_eq!(parse(header), None, "{:?} should be rejected", header);
+        }
+    }
+
+    #[test]
+    fn continues_traces() {
+        let headers = [
+            "80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1-1-05e3ac9a4f6e3b90",
+            "80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1-d",
+            "a3ce929d0e0e4736-00f067aa0ba902b7-0",
+            "a3ce929d0e0e4736-00f067aa0ba902b7",
+        ];
+        for header in headers.iter() {
+            let b3 = B3::for_request(Some(header));
+            let (trace_id, rest) = header.split_at(header.find('-').unwrap());
+
+            assert_eq!(b3.trace_id(), u128::from_str_radix(trace_id, 16).unwrap());
+            assert_eq!(b3.parent_id(), Some(u64::from_str_radix(&rest[1..17], 16).unwrap()));
+            assert_ne!(b3.span_id(), b3.parent_id().unwrap());
+
+            // The outbound header keeps the trace ID and sampling decision, with a new span whose
+            // parent is the upstream's.
+            let outbound = b3.to_string();
+            assert!(outbound.starts_with(&format!("{}-{:016x}", trace_id, b3.span_id())));
+            match b3.sampling() {
+                Some(sampling) => {
+                    assert!(outbound.ends_with(&format!("-{}-{}", sampling, &rest[1..17])))
+                }
+                None => assert_eq!(outbound.len(), trace_id.len() + 17),
+            }
+            assert_eq!(B3::for_request(Some(&outbound)).trace_id(), b3.trace_id());
+        }
+    }
+
+    #[test]
+    fn starts_new_traces() {
+        for header in [None, Some("0"), Some("not-a-trace")].iter() {
+            let b3 = B3::for_request(*header);
+
+            assert_ne!(b3.trace_id() >> 64, 0);
+            assert_eq!(b3.parent_id(), None);
+            assert_eq!(b3.to_string().len(), 49 + b3.sampling().map_or(0, |_| 2));
+            match parse(&b3.to_string()) {
+                Some(Parsed::Trace(parent)) => assert_eq!(parent.span_id, b3.span_id()),
+                parsed => panic!("{:?} should parse as a trace", parsed),
+            }
+        }
+        assert_eq!(B3::for_request(Some("0")).sampling(), Some(Sampling::Deny));
+        assert!(B3::for_request(Some("0")).to_string().ends_with("-0"));
+    }
+
+    fn header_map(headers: &[(&'static str, &'static str)]) -> HeaderMap<'static> {
+        let mut map = HeaderMap::new();
+        for &(name, value) in headers {
+            map.add_raw(name, value);
+        }
+        map
+    }
+
+    #[test]
+    fn continues_multi_header_traces() {
+        let headers = header_map(&[
+            ("x-b3-traceid", "80f198ee56343ba864fe8b2a57d3eff7"),
+            ("X-B3-SPANID", "e457b5a2e4d86bd1"),
+            ("X-B3-ParentSpanId", "05e3ac9a4f6e3b90"),
+            ("X-B3-Sampled", "1"),
+        ]);
+        let b3 = B3::for_multi_headers(&headers);
+
+        assert_eq!(b3.trace_id(), 0x80f198ee56343ba864fe8b2a57d3eff7);
+        assert_eq!(b3.parent_id(), Some(0xe457b5a2e4d86bd1));
+        assert_ne!(b3.span_id(), 0xe457b5a2e4d86bd1);
+        assert_eq!(b3.sampling(), Some(Sampling::Accept));
+
+        let mut outbound = HeaderMap::new();
+        for header in b3.multi_headers() {
+            outbound.add(header);
+        }
+        assert_eq!(
+            outbound.get_one(X_B3_TRACE_ID_HEADER),
+            Some("80f198ee56343ba864fe8b2a57d3eff7")
+        );
+        assert_eq!(
+            outbound.get_one(X_B3_SPAN_ID_HEADER),
+            Some(&format!("{:016x}", b3.span_id())[..])
+        );
+        assert_eq!(outbound.get_one(X_B3_PARENT_SPAN_ID_HEADER), Some("e457b5a2e4d86bd1"));
+        assert_eq!(outbound.get_one(X_B3_SAMPLED_HEADER), Some("1"));
+        assert_eq!(B3::for_multi_headers(&outbound).trace_id(), b3.trace_id());
+    }
+
+    #[test]
+    fn partial_multi_headers() {
+        // A trace ID without a span ID still continues the trace.
+        let b3 = B3::for_multi_headers(&header_map(&[("X-B3-TraceId", "a3ce929d0e0e4736")]));
+        assert_eq!(b3.trace_id(), 0xa3ce929d0e0e4736);
+        assert_eq!(b3.parent_id(), None);
+        assert_eq!(b3.sampling(), None);
+        assert_eq!(b3.multi_headers().len(), 2);
+        assert_eq!(b3.multi_headers()[0].value(), "a3ce929d0e0e4736");
+
+        let b3 = B3::for_multi_headers(&header_map(&[
+            ("X-B3-TraceId", "a3ce929d0e0e4736"),
+            ("X-B3-SpanId", "not-a-span"),
+            ("X-B3-Sampled", "false"),
+        ]));
+        assert_eq!(b3.trace_id(), 0xa3ce929d0e0e4736);
+        assert_eq!(b3.parent_id(), None);
+        assert_eq!(b3.sampling(), Some(Sampling::Deny));
+
+        // Only a sampling decision starts a new trace with that decision.
+        let b3 = B3::for_multi_headers(&header_map(&[("X-B3-Sampled", "0")]));
+        assert_ne!(b3.trace_id() >> 64, 0);
+        assert_eq!(b3.parent_id(), None);
+        assert_eq!(b3.sampling(), Some(Sampling::Deny));
+
+        let b3 = B3::for_multi_headers(&header_map(&[
+            ("X-B3-SpanId", "e457b5a2e4d86bd1"),
+            ("X-B3-Flags", "1"),
+        ]));
+        assert_eq!(b3.parent_id(), None);
+        assert_eq!(b3.sampling(), Some(Sampling::Debug));
+        let headers = b3.multi_headers();
+        assert!(headers.iter().any(|h| h.name() == X_B3_FLAGS_HEADER && h.value() == "1"));
+        assert!(!headers.iter().any(|h| h.name() == X_B3_SAMPLED_HEADER));
+
+        let b3 = B3::for_multi_headers(&header_map(&[("X-B3-TraceId", "0000000000000000")]));
+        assert_ne!(b3.trace_id(), 0);
+        assert_eq!(b3.to_string().len(), 49);
+    }
+}
@@ -0,0 +1,96 @@
+/// The base62 alphabet: digits, then uppercase, then lowercase letters, so that encoded IDs of the
+/// same length sort the same way as the values they encode. KSUIDs use it too.
+pub const ALPHABET: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// Encodes an ID in base62, most significant digit first, without leading zeros: 0 is `0`.
+pub fn encode(mut id: u128) -> String {
+    let mut encoded = Vec::new();
+    loop {
+        encoded.push(ALPHABET[(id % 62) as usize]);
+        id /= 62;
+        if id == 0 {
+            break;
+        }
+    }
+    encoded.iter().rev().map(|&c| c as char).collect()
+}
+
+/// Decodes an ID encoded by `encode`. Only its canonical encoding is accepted: not the empty
+/// string, and not with leading zeros, so each ID has exactly one encoding.
+pub fn decode(s: &str) -> Option<u128> {
+    if s.is_empty() || (s.len() > 1 && s.starts_with('0')) {
+        return None;
+    }
+    s.bytes().try_fold(0u128, |id, b| {
+        let digit = ALPHABET.iter().position(|&c| c == b)?;
+        id.checked_mul(62)?.checked_add(digit as u128)
+    })
+}
+
+/// Returns the check character for an encoded ID, by the Luhn mod 62 algorithm: every other digit,
+/// from the last, is doubled (and what's doubled past 62 folded back into a digit) before they're
+/// all summed. This catches any one mistyped character, and most swaps of neighbouring ones. It
+/// returns `None` if `encoded` isn't all base62 digits.
+pub fn check_char(encoded: &str) -> Option<char> {
+    let sum = encoded.bytes().rev().enumerate().try_fold(0, |sum, (i, b)| {
+        let digit = ALPHABET.iter().position(|&c| c == b)?;
+        let addend = if i % 2 == 0 { digit * 2 } else { digit };
+        Some(sum + addend / 62 + addend % 62)
+    })?;
+    Some(ALPHABET[(62 - sum % 62) % 62] as char)
+}
+
+/// Encodes an ID in base62 followed by its check character.
+pub fn encode_checked(id: u128) -> String {
+    let mut encoded = encode(id);
+    let check = check_char(&encoded).expect("base62 ids are all base62 digits");
+    encoded.push(check);
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{thread_rng, Rng};
+
+    #[test]
+    fn round_trips() {
+        assert_eq!(encode(0), "0");
+        assert_eq!(encode(61), "z");
+        assert_eq!(encode(62), "10");
+        assert_eq!(encode(u64::MAX as u128), "LygHa16AHYF");
+        assert_eq!(encode(u128::MAX), "7n42DGM5Tflk9n8mt7Fhc7");
+        for &id in [0, 1, 61, 62, 3843, 3844, u64::MAX as u128, u128::MAX].iter() {
+            assert_eq!(decode(&encode(id)), Some(id));
+        }
+    }
+
+    #[test]
+    fn round_trips_random_ids() {
+        let mut rng = thread_rng();
+        for _ in 0..10_000 {
+            // Random bit widths, so that short encodings are tested as much as long ones.
+            let id = rng.gen::<u128>() >> rng.gen_range(0..128u32);
+            let encoded = encode(id);
+            assert!(encoded.len() <= 22);
+            assert!(encoded == "0" || !encoded.starts_with('0'), "{}", encoded);
+            assert_eq!(decode(&encoded), Some(id), "{}", encoded);
+        }
+    }
+
+    #[test]
+    fn check_chars() {
+        assert_eq!(check_char("0"), Some('0'));
+        assert_eq!(check_char("1"), Some('y'));
+        assert_eq!(check_char("10"), Some('z'));
+        assert_eq!(check_char("a-b"), None);
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        // The last is u128::MAX + 1.
+        for malformed in ["", "00", "01", "a-b", "a b", "é", "7n42DGM5Tflk9n8mt7Fhc8"].iter() {
+            assert_eq!(decode(malformed), None, "{}", malformed);
+        }
+    }
+}
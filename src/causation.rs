@@ -0,0 +1,159 @@
+use rocket::http::Header;
+use rocket::request::{FromRequest, Outcome as ReqOutcome, Request};
+use rocket::{Ignite, Rocket, Sentinel};
+
+use super::{fairing_missing, tracked_guard, RequestIDError};
+
+/// The header a request's causation chain is propagated in: the IDs of the requests which led to
+/// it, oldest first, separated by commas.
+pub const CAUSATION_ID_HEADER: &str = "X-Causation-ID";
+
+///
+/// The chain of requests which caused a rocket request, e.g. a call from one service to another
+/// made while handling a request from a third.
+///
+/// This is only available if the fairing was configured with
+/// `RequestIDFairing::with_causation_chain`. In that case, the chain is made up of the IDs in the
+/// request's `X-Causation-ID` header, followed by its upstream's ID for the request (the one the
+/// `ParentRequestID` guard returns), unless the request adopted that as its own ID. IDs which
+/// aren't valid inbound IDs are dropped, and only the newest ones are kept if there are more than
+/// the chain's maximum depth.
+///
+/// `header` returns the header to send with requests made on this request's behalf, which
+/// continues the chain with this request's own ID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CausationChain {
+    causes: Vec<String>,
+    own_id: String,
+    max_depth: usize,
+}
+
+impl CausationChain {
+    /// Returns the IDs of the requests which caused this one, oldest first.
+    pub fn causes(&self) -> &[String] {
+        &self.causes
+    }
+
+    /// Returns the ID of the request which directly caused this one, if any.
+    pub fn cause(&self) -> Option<&str> {
+        self.causes.last().map(|id| &id[..])
+    }
+
+    /// Returns the header to send with requests made on this request's behalf: the chain, with
+    /// this request's ID (as rendered in its response header) as the newest cause.
+    pub fn header(&self) -> Header<'static> {
+        let skip = (self.causes.len() + 1).saturating_sub(self.max_depth);
+        let ids: Vec<&str> = self
+            .causes
+            .iter()
+            .map(|id| &id[..])
+            .chain(Some(&self.own_id[..]))
+            .skip(skip)
+            .collect();
+        Header::new(CAUSATION_ID_HEADER, ids.join(","))
+    }
+}
+
+// for_request returns the causation chain for a request with the given X-Causation-ID header and
+// parent ID (if it didn't adopt that as its own), keeping only the IDs valid accepts. Empty
+// entries, e.g. from a trailing comma, are skipped without counting towards the depth.
+pub fn for_request<F: Fn(&str) -> bool>(
+    header: Option<&str>,
+    parent: Option<&str>,
+    own_id: String,
+    max_depth: usize,
+    valid: F,
+) -> CausationChain {
+    // Only the newest IDs can survive, so don't bother looking at any others.
+    let mut causes: Vec<String> = header
+        .into_iter()
+        .flat_map(|header| header.rsplit(','))
+        .map(|id| id.trim())
+        .filter(|id| !id.is_empty())
+        .take(max_depth)
+        .filter(|id| {
+            let ok = valid(id);
+            if !ok {
+                debug!("dropping invalid X-Causation-ID entry");
+            }
+            ok
+        })
+        .map(|id| id.to_string())
+        .collect();
+    causes.reverse();
+    if let Some(parent) = parent.filter(|parent| !parent.is_empty()) {
+        if causes.last().map(|id| &id[..]) != Some(parent) {
+            causes.push(parent.to_string());
+        }
+    }
+    if causes.len() > max_depth {
+        debug!("truncating causation chain to its newest {} ids", max_depth);
+        let excess = causes.len() - max_depth;
+        causes.drain(..excess);
+    }
+    CausationChain {
+        causes,
+        own_id,
+        max_depth,
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for CausationChain {
+    type Error = RequestIDError;
+
+    async fn from_request(request: &'r Request<'_>) -> ReqOutcome<Self, Self::Error> {
+        tracked_guard(request, "causation chain", |tracked| tracked.causation.clone())
+    }
+}
+
+impl Sentinel for CausationChain {
+    fn abort(rocket: &Rocket<Ignite>) -> bool {
+        fairing_missing(rocket, "CausationChain")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(header: Option<&str>, parent: Option<&str>, max_depth: usize) -> CausationChain {
+        for_request(header, parent, "own".to_string(), max_depth, |id| {
+            id.bytes().all(|b| b.is_ascii_alphanumeric())
+        })
+    }
+
+    #[test]
+    fn appends_parent() {
+        let chain = parse(Some("a, b"), Some("c"), 16);
+        assert_eq!(chain.causes(), ["a", "b", "c"]);
+        assert_eq!(chain.cause(), Some("c"));
+        assert_eq!(chain.header().value(), "a,b,c,own");
+    }
+
+    #[test]
+    fn doesnt_repeat_parent() {
+        let chain = parse(Some("a,b"), Some("b"), 16);
+        assert_eq!(chain.causes(), ["a", "b"]);
+    }
+
+    #[test]
+    fn drops_invalid_ids() {
+        let chain = parse(Some("a,,b c,d"), None, 16);
+        assert_eq!(chain.causes(), ["a", "d"]);
+        let chain = parse(Some(",a, ,b,"), Some(""), 2);
+        assert_eq!(chain.causes(), ["a", "b"]);
+    }
+
+    #[test]
+    fn limits_depth() {
+        let chain = parse(Some("a,b,c,d,e"), Some("f"), 3);
+        assert_eq!(chain.causes(), ["d", "e", "f"]);
+        assert_eq!(chain.header().value(), "e,f,own");
+
+        let chain = parse(None, None, 3);
+        assert!(chain.causes().is_empty());
+        assert_eq!(chain.cause(), None);
+        assert_eq!(chain.header().value(), "own");
+    }
+}
@@ -0,0 +1,115 @@
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// A range of IP addresses written in CIDR notation, such as `10.0.0.0/8`.
+///
+/// A bare address, such as `192.168.1.1`, is treated as a range containing only itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl Cidr {
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        // A server listening on `::` sees IPv4 clients as `::ffff:a.b.c.d`.
+        match (self.network, addr.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(addr)) => {
+                masked(u32::from(net) as u128, 32, self.prefix_len)
+                    == masked(u32::from(addr) as u128, 32, self.prefix_len)
+            }
+            (IpAddr::V6(net), IpAddr::V6(addr)) => {
+                masked(u128::from(net), 128, self.prefix_len)
+                    == masked(u128::from(addr), 128, self.prefix_len)
+            }
+            _ => false,
+        }
+    }
+}
+
+// masked returns the top prefix_len bits of an address which is width bits wide.
+fn masked(bits: u128, width: u8, prefix_len: u8) -> u128 {
+    if prefix_len == 0 {
+        0
+    } else {
+        bits >> (width - prefix_len)
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Cidr, String> {
+        let mut parts = s.splitn(2, '/');
+        let network: IpAddr = parts
+            .next()
+            .unwrap()
+            .parse()
+            .map_err(|_| format!("invalid address in {:?}", s))?;
+        let width = if network.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match parts.next() {
+            Some(len) => len
+                .parse()
+                .map_err(|_| format!("invalid prefix length in {:?}", s))?,
+            None => width,
+        };
+        if prefix_len > width {
+            return Err(format!("prefix length too long in {:?}", s));
+        }
+
+        Ok(Cidr {
+            network,
+            prefix_len,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn contains_v4() {
+        let cidr: Cidr = "10.0.0.0/8".parse().unwrap();
+        assert!(cidr.contains(ip("10.0.0.1")));
+        assert!(cidr.contains(ip("10.255.255.255")));
+        assert!(!cidr.contains(ip("11.0.0.1")));
+        assert!(!cidr.contains(ip("::1")));
+    }
+
+    #[test]
+    fn contains_v6() {
+        let cidr: Cidr = "fd00::/8".parse().unwrap();
+        assert!(cidr.contains(ip("fd12:3456::1")));
+        assert!(!cidr.contains(ip("fe80::1")));
+        assert!(!cidr.contains(ip("10.0.0.1")));
+    }
+
+    #[test]
+    fn contains_mapped_v4() {
+        let cidr: Cidr = "10.0.0.0/8".parse().unwrap();
+        assert!(cidr.contains(ip("::ffff:10.1.2.3")));
+        assert!(!cidr.contains(ip("::ffff:11.1.2.3")));
+    }
+
+    #[test]
+    fn bare_address_and_zero_prefix() {
+        let single: Cidr = "192.168.1.1".parse().unwrap();
+        assert!(single.contains(ip("192.168.1.1")));
+        assert!(!single.contains(ip("192.168.1.2")));
+
+        let everything: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(everything.contains(ip("203.0.113.7")));
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("10.0.0/8".parse::<Cidr>().is_err());
+        assert!("10.0.0.0/eight".parse::<Cidr>().is_err());
+    }
+}
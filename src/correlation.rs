@@ -0,0 +1,70 @@
+use rocket::http::Header;
+use rocket::request::{FromRequest, Outcome as ReqOutcome, Request};
+use rocket::{Ignite, Rocket, Sentinel};
+use std::fmt;
+
+use super::{fairing_missing, tracked_guard, RequestIDError};
+
+/// The header a correlation ID is propagated in.
+pub const CORRELATION_ID_HEADER: &str = "X-Correlation-ID";
+
+///
+/// The ID which correlates every request made as part of the same interaction, across services.
+///
+/// This is only available if the fairing was configured with
+/// `RequestIDFairing::with_correlation_id`. In that case, a request which arrives with a valid
+/// `X-Correlation-ID` header keeps it, and any other request's own ID becomes its correlation ID;
+/// either way, the fairing echoes it in the response's `X-Correlation-ID` header.
+///
+/// Unlike its `RequestID`, which identifies one hop, this is the same for every request in the
+/// interaction, so it should be passed on (with `header`) to any requests made on this request's
+/// behalf.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorrelationID {
+    id: String,
+}
+
+// correlation_id returns a CorrelationID for the interaction identified by id.
+pub fn correlation_id(id: String) -> CorrelationID {
+    CorrelationID { id }
+}
+
+impl CorrelationID {
+    /// Returns the correlation ID.
+    pub fn as_str(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns the header to send with requests made on this request's behalf to keep them in the
+    /// same interaction, which is also the header the fairing sets on the response.
+    pub fn header(&self) -> Header<'static> {
+        Header::new(CORRELATION_ID_HEADER, self.id.clone())
+    }
+}
+
+impl From<CorrelationID> for String {
+    fn from(c: CorrelationID) -> String {
+        c.id
+    }
+}
+
+impl fmt::Display for CorrelationID {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.id)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for CorrelationID {
+    type Error = RequestIDError;
+
+    async fn from_request(request: &'r Request<'_>) -> ReqOutcome<Self, Self::Error> {
+        tracked_guard(request, "correlation id", |tracked| tracked.correlation.clone())
+    }
+}
+
+impl Sentinel for CorrelationID {
+    fn abort(rocket: &Rocket<Ignite>) -> bool {
+        fairing_missing(rocket, "CorrelationID")
+    }
+}
@@ -0,0 +1,74 @@
+/// [Crockford's base32](https://www.crockford.com/base32.html) alphabet, which leaves out I, L, O
+/// and U, so that IDs can't be misread as other IDs, or as words. ULIDs use it too.
+pub const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Encodes an ID in Crockford's base32, most significant digit first, without leading zeros: 0 is
+/// `0`.
+pub fn encode(mut id: u64) -> String {
+    let mut encoded = Vec::new();
+    loop {
+        encoded.push(ALPHABET[(id % 32) as usize]);
+        id /= 32;
+        if id == 0 {
+            break;
+        }
+    }
+    encoded.iter().rev().map(|&c| c as char).collect()
+}
+
+/// Decodes an ID encoded by `encode`, as leniently as the spec allows: in either case, with `O`
+/// read as `0`, `I` and `L` as `1`, and hyphens, which people add to keep their place reading an
+/// ID out, ignored.
+pub fn decode(s: &str) -> Option<u64> {
+    let mut digits = s.bytes().filter(|&b| b != b'-').peekable();
+    digits.peek()?;
+    digits.try_fold(0u64, |id, b| {
+        let b = match b.to_ascii_uppercase() {
+            b'O' => b'0',
+            b'I' | b'L' => b'1',
+            b => b,
+        };
+        let digit = ALPHABET.iter().position(|&c| c == b)?;
+        id.checked_mul(32)?.checked_add(digit as u64)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_vectors() {
+        // The examples of the spec's reference implementations.
+        let vectors = [
+            (0, "0"),
+            (1, "1"),
+            (31, "Z"),
+            (32, "10"),
+            (1234, "16J"),
+            (5111, "4ZQ"),
+            (u64::MAX, "FZZZZZZZZZZZZ"),
+        ];
+        for &(id, encoded) in vectors.iter() {
+            assert_eq!(encode(id), encoded);
+            assert_eq!(decode(encoded), Some(id));
+        }
+    }
+
+    #[test]
+    fn decodes_leniently() {
+        for lenient in ["16j", "16-J", "-1-6-j-", "016J"].iter() {
+            assert_eq!(decode(lenient), Some(1234), "{}", lenient);
+        }
+        assert_eq!(decode("1O"), Some(32));
+        assert_eq!(decode("IL"), Some(33));
+        assert_eq!(decode("l0"), Some(32));
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        for malformed in ["", "-", "U", "16J*", "16 J", "G00000000000000"].iter() {
+            assert_eq!(decode(malformed), None, "{}", malformed);
+        }
+    }
+}
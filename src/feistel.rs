@@ -0,0 +1,63 @@
+// The number of rounds of the Feistel network. IDs only need to look random, not to resist
+// cryptanalysis, and 4 rounds are enough for every bit of the output to depend on every bit of the
+// input.
+const ROUNDS: u64 = 4;
+
+// round is the network's round function: it mixes half of the block with a key for the round.
+fn round(half: u32, key: u64, i: u64) -> u32 {
+    let mixed = (half as u64 ^ key.rotate_left(16 * i as u32) ^ i)
+        .wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    (mixed >> 32) as u32 ^ mixed as u32
+}
+
+// obfuscate permutes n with a Feistel network keyed with key, over its two 32-bit halves.
+pub fn obfuscate(n: u64, key: u64) -> u64 {
+    let (mut left, mut right) = ((n >> 32) as u32, n as u32);
+    for i in 0..ROUNDS {
+        let next = left ^ round(right, key, i);
+        left = right;
+        right = next;
+    }
+    (left as u64) << 32 | right as u64
+}
+
+/// Returns the sequence number an ID with `RequestIDFairing::with_obfuscated_counter(key)`
+/// stands for, e.g. to see how many requests came between two IDs in a bug report.
+pub fn deobfuscate(id: u64, key: u64) -> u64 {
+    let (mut left, mut right) = ((id >> 32) as u32, id as u32);
+    for i in (0..ROUNDS).rev() {
+        let prev = right ^ round(left, key, i);
+        right = left;
+        left = prev;
+    }
+    (left as u64) << 32 | right as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: u64 = 0x0123_4567_89ab_cdef;
+
+    #[test]
+    fn round_trips() {
+        for n in (0..10_000).chain(u64::MAX - 10_000..=u64::MAX) {
+            assert_eq!(deobfuscate(obfuscate(n, KEY), KEY), n);
+        }
+        assert_ne!(deobfuscate(obfuscate(1234, KEY), KEY + 1), 1234);
+    }
+
+    #[test]
+    fn looks_random() {
+        let mut flipped = 0;
+        for n in 1..1000 {
+            let (a, b) = (obfuscate(n, KEY), obfuscate(n + 1, KEY));
+            assert!((a ^ b).count_ones() >= 8, "{:016x} {:016x}", a, b);
+            flipped += (a ^ b).count_ones();
+        }
+        // Random neighbours would differ in 32 bits on average.
+        let average = flipped / 999;
+        assert!((28..=36).contains(&average), "{}", average);
+        assert_ne!(obfuscate(1, KEY), obfuscate(1, KEY + 1));
+    }
+}
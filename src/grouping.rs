@@ -0,0 +1,121 @@
+use super::hex;
+
+/// How a fairing with `RequestIDFairing::with_grouping` breaks up hex IDs: digits hex digits, in
+/// groups of size from the left, joined by separator, after a version digit if there is one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Grouping {
+    pub digits: u8,
+    pub size: u8,
+    pub separator: u8,
+    pub version: Option<u8>,
+}
+
+impl Grouping {
+    /// Renders id, e.g. `1-0000-0000-029a-eebe` for `0x029aeebe` with a version of 1 and groups
+    /// of 4.
+    pub fn encode(&self, id: u128) -> String {
+        let hex = hex::encode(id, self.digits as usize);
+        let mut encoded = String::with_capacity(hex.len() * 2);
+        if let Some(version) = self.version {
+            encoded.push_str(&format!("{:x}", version));
+            encoded.push(self.separator as char);
+        }
+        for (i, group) in hex.as_bytes().chunks(self.size as usize).enumerate() {
+            if i > 0 {
+                encoded.push(self.separator as char);
+            }
+            encoded.extend(group.iter().map(|&b| b as char));
+        }
+        encoded
+    }
+
+    /// Parses an ID rendered by `encode`, or its digits without any separators, in either case.
+    /// The version digit is optional either way, but must match if it's there.
+    pub fn decode(&self, s: &str) -> Option<u128> {
+        if let Some(version) = self.version {
+            let prefix = format!("{:x}{}", version, self.separator as char);
+            match s.get(..prefix.len()) {
+                Some(start) if start.eq_ignore_ascii_case(&prefix) => {
+                    // With groups of one digit, the version may really be the ID's first digit.
+                    if let Some(id) = self.decode_digits(&s[prefix.len()..]) {
+                        return Some(id);
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.decode_digits(s)
+    }
+
+    // decode_digits is decode, for an ID without its version digit.
+    fn decode_digits(&self, s: &str) -> Option<u128> {
+        let hex: String = s.chars().filter(|&c| c != self.separator as char).collect();
+        let id = hex::decode(&hex, self.digits as usize)?;
+        // Separators must be where encode puts them, if there are any.
+        let grouped = Grouping {
+            version: None,
+            ..*self
+        };
+        if hex.len() != s.len() && !grouped.encode(id).eq_ignore_ascii_case(s) {
+            return None;
+        }
+        Some(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GROUPING: Grouping = Grouping {
+        digits: 16,
+        size: 4,
+        separator: b'-',
+        version: Some(1),
+    };
+
+    #[test]
+    fn round_trips() {
+        assert_eq!(GROUPING.encode(0x029aeebe), "1-0000-0000-029a-eebe");
+        let ungrouped = Grouping {
+            version: None,
+            ..GROUPING
+        };
+        assert_eq!(ungrouped.encode(0x029aeebe), "0000-0000-029a-eebe");
+        let uneven = Grouping {
+            digits: 32,
+            size: 12,
+            separator: b'.',
+            version: None,
+        };
+        assert_eq!(uneven.encode(u128::MAX), "ffffffffffff.ffffffffffff.ffffffff");
+
+        let forms = [
+            "1-0000-0000-029a-eebe",
+            "1-0000-0000-029A-EEBE",
+            "0000-0000-029a-eebe",
+            "00000000029aeebe",
+            "1-00000000029aeebe",
+        ];
+        for s in forms.iter() {
+            assert_eq!(GROUPING.decode(s), Some(0x029aeebe), "{}", s);
+        }
+        assert_eq!(uneven.decode("ffffffffffff.ffffffffffff.ffffffff"), Some(u128::MAX));
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        let malformed = [
+            "",
+            "2-0000-0000-029a-eebe",
+            "00-00-0000-029a-eebe",
+            "0000-0000-029a-eeb",
+            "0000-0000-029a-eebeb",
+            "0000_0000_029a_eebe",
+            "0000-0000-029a-eebg",
+        ];
+        for malformed in malformed.iter() {
+            assert_eq!(GROUPING.decode(malformed), None, "{}", malformed);
+        }
+    }
+}
@@ -0,0 +1,42 @@
+/// Encodes an ID as digits lowercase hex digits, with leading zeros.
+pub fn encode(id: u128, digits: usize) -> String {
+    format!("{:0width$x}", id, width = digits)
+}
+
+/// Encodes an ID as `encode` does, in uppercase.
+pub fn encode_upper(id: u128, digits: usize) -> String {
+    format!("{:0width$X}", id, width = digits)
+}
+
+/// Decodes an ID encoded by `encode` or `encode_upper`. It must be exactly digits long.
+pub fn decode(s: &str, digits: usize) -> Option<u128> {
+    if s.len() != digits || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    u128::from_str_radix(s, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        assert_eq!(encode(0, 8), "00000000");
+        assert_eq!(encode(0x29aeebe, 16), "00000000029aeebe");
+        assert_eq!(encode(u128::MAX, 32), "ffffffffffffffffffffffffffffffff");
+        let ids = [(0, 8), (0x29aeebe, 16), (u64::MAX as u128, 16), (u128::MAX, 32)];
+        for &(id, digits) in ids.iter() {
+            assert_eq!(decode(&encode(id, digits), digits), Some(id));
+        }
+        assert_eq!(encode_upper(0x29aeebe, 8), "029AEEBE");
+        assert_eq!(decode("029AEEBE", 8), Some(0x29aeebe));
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        for malformed in ["", "29aeebe", "0029aeebe", "029aeebg", "+29aeebe", "029aee-be"].iter() {
+            assert_eq!(decode(malformed, 8), None, "{}", malformed);
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The header clients send idempotency keys in, with `RequestIDFairing::with_idempotency_keys`.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// The IDs a fairing gave requests with each idempotency key it has seen within its TTL.
+pub struct IdempotencyKeys {
+    ttl: Duration,
+    keys: Mutex<Keys>,
+}
+
+#[derive(Default)]
+struct Keys {
+    ids: HashMap<String, (u32, u128)>,
+    // The keys in the order they were first seen, and when, so that those which have expired are
+    // at the front.
+    seen: VecDeque<(Instant, String)>,
+}
+
+impl IdempotencyKeys {
+    pub fn new(ttl: Duration) -> IdempotencyKeys {
+        IdempotencyKeys {
+            ttl,
+            keys: Mutex::default(),
+        }
+    }
+
+    /// Returns the ID for requests with key: the one the first request with it in the last TTL
+    /// got, or a new one from generate, if there wasn't one.
+    pub fn id_for<F: FnOnce() -> (u32, u128)>(&self, key: &str, generate: F) -> (u32, u128) {
+        let mut keys = self.keys.lock().unwrap();
+        let now = Instant::now();
+        while let Some(&(seen, _)) = keys.seen.front() {
+            if now.duration_since(seen) < self.ttl {
+                break;
+            }
+            let (_, expired) = keys.seen.pop_front().unwrap();
+            keys.ids.remove(&expired);
+        }
+        if let Some(&id) = keys.ids.get(key) {
+            return id;
+        }
+        let id = generate();
+        keys.ids.insert(key.to_string(), id);
+        keys.seen.push_back((now, key.to_string()));
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn maps_keys_to_ids() {
+        let keys = IdempotencyKeys::new(Duration::from_secs(60));
+        assert_eq!(keys.id_for("a", || (0, 1)), (0, 1));
+        assert_eq!(keys.id_for("a", || (0, 2)), (0, 1));
+        assert_eq!(keys.id_for("b", || (0, 3)), (0, 3));
+        assert_eq!(keys.id_for("a", || (0, 4)), (0, 1));
+    }
+
+    #[test]
+    fn expires_keys() {
+        let keys = IdempotencyKeys::new(Duration::from_millis(20));
+        assert_eq!(keys.id_for("a", || (0, 1)), (0, 1));
+        thread::sleep(Duration::from_millis(30));
+        assert_eq!(keys.id_for("b", || (0, 2)), (0, 2));
+        assert_eq!(keys.id_for("a", || (0, 3)), (0, 3));
+        let keys = keys.keys.lock().unwrap();
+        assert_eq!(keys.ids.len(), 2);
+        assert_eq!(keys.seen.len(), 2);
+    }
+}
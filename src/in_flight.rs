@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// The IDs a fairing has generated for requests which are still in flight, for
+/// `RequestIDFairing::with_collision_checks`. Each is counted, since a duplicate may be let
+/// through.
+#[derive(Default)]
+pub struct InFlight(Mutex<HashMap<(u32, u128), usize>>);
+
+/// An ID's place in an `InFlight`, which it gives up when dropped, along with the request it was
+/// generated for.
+pub struct Claim {
+    in_flight: Arc<InFlight>,
+    id: (u32, u128),
+}
+
+impl InFlight {
+    /// Claims `id` for a request, unless another request already has.
+    pub fn try_claim(in_flight: &Arc<InFlight>, id: (u32, u128)) -> Option<Claim> {
+        let mut ids = in_flight.0.lock().unwrap();
+        if ids.contains_key(&id) {
+            return None;
+        }
+        ids.insert(id, 1);
+        Some(Claim {
+            in_flight: in_flight.clone(),
+            id,
+        })
+    }
+
+    /// Claims `id` for a request, even if another request already has.
+    pub fn claim(in_flight: &Arc<InFlight>, id: (u32, u128)) -> Claim {
+        *in_flight.0.lock().unwrap().entry(id).or_insert(0) += 1;
+        Claim {
+            in_flight: in_flight.clone(),
+            id,
+        }
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        let mut ids = match self.in_flight.0.lock() {
+            Ok(ids) => ids,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if let Some(count) = ids.get_mut(&self.id) {
+            *count -= 1;
+            if *count == 0 {
+                ids.remove(&self.id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn claims() {
+        let in_flight = Arc::new(InFlight::default());
+        let first = InFlight::try_claim(&in_flight, (0, 7)).unwrap();
+        assert!(InFlight::try_claim(&in_flight, (0, 7)).is_none());
+        let other = InFlight::try_claim(&in_flight, (1, 7)).unwrap();
+
+        let duplicate = InFlight::claim(&in_flight, (0, 7));
+        drop(first);
+        assert!(InFlight::try_claim(&in_flight, (0, 7)).is_none());
+        drop(duplicate);
+        drop(other);
+        assert!(in_flight.0.lock().unwrap().is_empty());
+        assert!(InFlight::try_claim(&in_flight, (0, 7)).is_some());
+    }
+}
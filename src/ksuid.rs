@@ -0,0 +1,119 @@
+use rand::Rng;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::base62::ALPHABET;
+
+// KSUID timestamps count seconds from this Unix time, which extends their range by 44 years.
+const EPOCH: u64 = 1_400_000_000;
+
+// The number of characters in an encoded KSUID.
+const LEN: usize = 27;
+
+/// Returns a new [KSUID](https://github.com/segmentio/ksuid): a 32-bit timestamp in seconds since
+/// the KSUID epoch, which is returned separately, and a 128-bit random payload.
+pub fn generate<R: Rng>(rng: &mut R) -> (u32, u128) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock is before 1970");
+    let timestamp = now.as_secs().saturating_sub(EPOCH) as u32;
+    (timestamp, rng.gen())
+}
+
+/// Returns the time a KSUID with the given timestamp was generated at.
+pub fn timestamp(timestamp: u32) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(EPOCH + timestamp as u64)
+}
+
+/// Encodes a KSUID as 27 characters of base62.
+pub fn encode(timestamp: u32, payload: u128) -> String {
+    // The KSUID as a big-endian number in base 2^32, divided by 62 once per character.
+    let mut limbs = [
+        timestamp,
+        (payload >> 96) as u32,
+        (payload >> 64) as u32,
+        (payload >> 32) as u32,
+        payload as u32,
+    ];
+    let mut encoded = [b'0'; LEN];
+    for c in encoded.iter_mut().rev() {
+        let mut rem = 0u64;
+        for limb in limbs.iter_mut() {
+            let acc = rem << 32 | *limb as u64;
+            *limb = (acc / 62) as u32;
+            rem = acc % 62;
+        }
+        *c = ALPHABET[rem as usize];
+    }
+    encoded.iter().map(|&c| c as char).collect()
+}
+
+/// Decodes a KSUID encoded by `encode` into its timestamp and payload.
+pub fn decode(s: &str) -> Option<(u32, u128)> {
+    if s.len() != LEN {
+        return None;
+    }
+    let mut limbs = [0u32; 5];
+    for b in s.bytes() {
+        let mut carry = ALPHABET.iter().position(|&c| c == b)? as u64;
+        for limb in limbs.iter_mut().rev() {
+            let acc = *limb as u64 * 62 + carry;
+            *limb = acc as u32;
+            carry = acc >> 32;
+        }
+        // 27 characters can hold more than the 160 bits a KSUID has.
+        if carry != 0 {
+            return None;
+        }
+    }
+    let payload = limbs[1..]
+        .iter()
+        .fold(0u128, |payload, &limb| payload << 32 | limb as u128);
+    Some((limbs[0], payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+
+    #[test]
+    fn known_vectors() {
+        // From the README of the reference implementation.
+        let (timestamp, payload) = decode("0ujtsYcgvSTl8PAuAdqWYSMnLOv").unwrap();
+        assert_eq!(timestamp, 107_608_047);
+        assert_eq!(payload, 0xb5a1_cd34_b5f9_9d11_54fb_6853_345c_9735);
+        assert_eq!(encode(timestamp, payload), "0ujtsYcgvSTl8PAuAdqWYSMnLOv");
+        assert_eq!(
+            super::timestamp(timestamp),
+            UNIX_EPOCH + Duration::from_secs(1_507_608_047)
+        );
+
+        assert_eq!(encode(0, 0), "000000000000000000000000000");
+        assert_eq!(encode(u32::MAX, u128::MAX), "aWgEPTl1tmebfsQzFP4bxwgy80V");
+        assert_eq!(decode("aWgEPTl1tmebfsQzFP4bxwgy80V"), Some((u32::MAX, u128::MAX)));
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        for malformed in [
+            "",
+            "0ujtsYcgvSTl8PAuAdqWYSMnLO",
+            "0ujtsYcgvSTl8PAuAdqWYSMnLOvv",
+            "0ujtsYcgvSTl8PAuAdqWYSMnLO-",
+            "aWgEPTl1tmebfsQzFP4bxwgy80W",
+            "zzzzzzzzzzzzzzzzzzzzzzzzzzz",
+        ]
+        .iter()
+        {
+            assert_eq!(decode(malformed), None, "{}", malformed);
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        for _ in 0..100 {
+            let (timestamp, payload) = generate(&mut thread_rng());
+            assert_eq!(decode(&encode(timestamp, payload)), Some((timestamp, payload)));
+        }
+    }
+}
@@ -1,186 +1,6961 @@
-#![cfg_attr(test, feature(plugin, custom_derive))]
-#![cfg_attr(test, plugin(rocket_codegen))]
-
 #[macro_use]
 extern crate lazy_static;
 #[macro_use]
 extern crate log;
-extern crate rand;
-extern crate rocket;
+
+mod amzn;
+mod b3;
+mod base62;
+mod causation;
+mod cidr;
+mod correlation;
+mod crockford;
+mod feistel;
+mod grouping;
+mod hex;
+mod idempotency;
+mod in_flight;
+mod ksuid;
+mod nanoid;
+#[cfg(feature = "opentelemetry")]
+mod otel;
+mod pool;
+mod rng_pool;
+mod session;
+#[cfg(feature = "signing")]
+mod signing;
+mod snowflake;
+mod timestamped;
+mod trace_context;
+mod ulid;
+mod xid;
+#[cfg(feature = "uuid")]
+mod uuid_v7;
 
 use rocket::request::Request;
-use rocket::http::Status;
+use rocket::http::{ContentType, Header, Status};
 use rocket::response::Response;
 use rocket::request::FromRequest;
 use rocket::request::Outcome as ReqOutcome;
-use rocket::Outcome;
-use rand::{thread_rng, Rng};
-use std::collections::hash_map;
-use std::sync::Mutex;
+use rocket::outcome::Outcome;
+use rocket::{Build, Data, Ignite, Rocket, Sentinel};
+use rand::rngs::{OsRng, StdRng};
+use rand::{thread_rng, Rng, RngCore, SeedableRng};
+use amzn::AMZN_TRACE_ID_HEADER;
+pub use amzn::AmznTraceId;
+use b3::{B3_HEADER, X_B3_TRACE_ID_HEADER};
+pub use b3::{Sampling, B3};
+use causation::CAUSATION_ID_HEADER;
+pub use causation::CausationChain;
+use cidr::Cidr;
+use in_flight::{Claim, InFlight};
+use rng_pool::RngPool;
+use correlation::CORRELATION_ID_HEADER;
+use grouping::Grouping;
+use idempotency::{IdempotencyKeys, IDEMPOTENCY_KEY_HEADER};
+use pool::IdPool;
+pub use correlation::CorrelationID;
+pub use feistel::deobfuscate;
+use trace_context::{TRACEPARENT_HEADER, TRACESTATE_HEADER};
+pub use trace_context::TraceParent;
+pub use session::{SessionCookie, SessionCorrelationID};
+#[cfg(feature = "signing")]
+pub use signing::{SignatureError, VerifiedId};
+pub use nanoid::{DEFAULT_NANOID_ALPHABET, DEFAULT_NANOID_LEN};
+pub use snowflake::MAX_SNOWFLAKE_WORKER_ID;
+pub use xid::MAX_XID_MACHINE_ID;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io::Cursor;
+use std::marker::PhantomData;
+use std::net::IpAddr;
+use std::slice;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+#[cfg(feature = "uuid")]
+use uuid::Uuid;
 
-// yes, this is global state. Let's go over how we got here and other possible options:
-//
-// First of all, rocket provides no good interface for associating arbitrary data with a request.
-// This is what state gets associated:
-// https://github.com/SergioBenitez/Rocket/blob/v0.3.6/lib/src/request/request.rs#L20-L29
-//
-// Ideally, this would be managed state, but as far as I can tell, there's no good way to add state
-// in a fairing or request guard (which makes sense because state is meant to be used via `.manage`
-// on the main rocket instance, and then the same copy is passed out repeatedly).
-//
-// In addition, there's no good way to identify a request (or else we'd be done already, huh?), but
-// `FromRequest` can be called an arbitrary number of times for the same request.. and we need it
-// to return the same ids each time.
-//
-// That leaves us with the possible solutions which work:
-// 1. Add a cookie or url hash or something to indicate the ID, make a request guard which reads
-//    from that, or if it doesn't exist initializes it
-//
-//    This mutates the request the application's rocket handler would see in surprising ways, and
-//    was thus deemed bad.
-//
-// 2. Use low level hackery to locate a request id either before or after the request in memory,
-//    otherwise behave as above
-//
-//    This would be really cool, but unfortunately I don't know of a way to do that and also have
-//    that hidden data get freed when a request is freed... so I'd still need a fairing to find it
-//    and free it, so it's no betterthan 3.
-//
-// 3. Keep a static map of currently know requests as identified by their memory address, add and
-//    remove ids as requests come in and leave via a fairing.
-//
-//    This is the approach I've gone with. It's really what 2 would be, but less hacky.
+/// The header the fairing reads an upstream-assigned ID from, and surfaces a request's ID on its
+/// response with, unless configured otherwise.
+const REQUEST_ID_HEADER: &str = "X-Request-ID";
+
+/// The header the fairing reports a request's latency on, with `with_server_timing`.
+const SERVER_TIMING_HEADER: &str = "Server-Timing";
+
+/// The longest inbound ID the fairing will consider by default, in bytes.
+const DEFAULT_MAX_INBOUND_LEN: usize = 128;
+
+/// The `u64` IDs the fairing never gives requests by default, which are often used to mean there's
+/// no ID.
+const DEFAULT_RESERVED_IDS: [u64; 2] = [0, u64::MAX];
+
+// The most times the fairing generates an ID before giving up on generating one that isn't
+// reserved.
+const MAX_RESERVED_RETRIES: usize = 100;
+
+/// The most IDs a causation chain keeps by default.
+const DEFAULT_MAX_CAUSATION_DEPTH: usize = 16;
+
+// Rocket 0.3 provided no good interface for associating arbitrary data with a request, so this
+// crate used to keep a global map of in-flight requests, identified by their memory addresses,
+// which the fairing added requests to and removed them from. Rocket 0.4 added
+// `Request::local_cache`, so now what's tracked about each request is kept in its local cache
+// instead. That goes away along with the request, so nothing can leak (even if on_response is
+// skipped, e.g. because a fairing panicked), and requests never contend for a lock.
 //
-// 4. Ask upstream to add a request id, or a way to associate arbitrary context with a request
-//    (like go's context).
+// The local cache only hands out shared references, so the entry is behind a lock, which is only
+// ever taken by the request's own fairing callbacks and guards.
 //
-//    ... This is probably the best idea, but hasn't been done yet.
-lazy_static!{
-    static ref REQUEST_IDS: Mutex<hash_map::HashMap<usize, u64, hash_map::RandomState>> =
-        Mutex::new(hash_map::HashMap::new());
+// Each namespace has its own entry, since the local cache keeps one value per type.
+struct Entry<N>(Mutex<Slot>, PhantomData<fn() -> N>);
+
+impl<N> Default for Entry<N> {
+    fn default() -> Entry<N> {
+        Entry(Mutex::default(), PhantomData)
+    }
+}
+
+// Slot is what's kept in a request's Entry.
+#[derive(Default)]
+struct Slot {
+    // Whether the fairing has seen the request.
+    attached: bool,
+    // What's tracked about the request, until its response is ready. The fairing leaves this
+    // empty if it doesn't give the request an ID.
+    tracked: Option<Tracked>,
+}
+
+impl<N> Drop for Entry<N> {
+    fn drop(&mut self) {
+        let slot = match self.0.get_mut() {
+            Ok(slot) => slot,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if slot.attached && slot.tracked.is_some() {
+            ACTIVE_REQUESTS.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+// The number of requests the fairing is tracking.
+static ACTIVE_REQUESTS: AtomicUsize = AtomicUsize::new(0);
+
+// Tracked is what the fairing records about each in-flight request.
+struct Tracked {
+    id: RequestID,
+    // Where the ID came from.
+    source: RequestIDSource,
+    // When the fairing saw the request, or the guard gave it an ID.
+    started: Instant,
+    // The upstream's ID for the request, verbatim.
+    parent: Option<String>,
+    // The request's W3C trace context, if the fairing is configured to track it.
+    trace: Option<TraceParent>,
+    // The request's B3 trace context, if the fairing is configured to track it.
+    b3: Option<B3>,
+    // The request's X-Amzn-Trace-Id, if the fairing is configured to track it.
+    amzn: Option<AmznTraceId>,
+    // The request's session correlation ID, if the fairing is configured to keep one.
+    session: Option<SessionCorrelationID>,
+    // The request's causation chain, if the fairing is configured to track it.
+    causation: Option<CausationChain>,
+    // The request's correlation ID, if the fairing is configured to track it.
+    correlation: Option<CorrelationID>,
+    // Whether the handler asked for the response not to get the fairing's headers.
+    suppress_headers: bool,
+    // The header with the invalid inbound ID the request is being rejected for, if it is.
+    rejected: Option<Cow<'static, str>>,
+    // The request's generated ID's place among those in flight, if the fairing checks for
+    // collisions. It's only held to be dropped: dropping the claim releases the ID.
+    #[allow(dead_code)]
+    in_flight: Option<Claim>,
+    // The request's span, for the RequestSpan guard.
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    // The request's OpenTelemetry span, which the fairing ends with its response.
+    #[cfg(feature = "opentelemetry")]
+    otel_span: Option<opentelemetry::global::BoxedSpan>,
+}
+
+impl Tracked {
+    // generated returns the record for a request which the fairing didn't see, which a guard gave
+    // id.
+    fn generated(id: RequestID) -> Tracked {
+        Tracked {
+            id,
+            source: RequestIDSource::Generated,
+            started: Instant::now(),
+            parent: None,
+            trace: None,
+            b3: None,
+            amzn: None,
+            session: None,
+            causation: None,
+            correlation: None,
+            suppress_headers: false,
+            rejected: None,
+            in_flight: None,
+            #[cfg(feature = "tracing")]
+            span: tracing::info_span!("request", request_id = %id),
+            #[cfg(feature = "opentelemetry")]
+            otel_span: None,
+        }
+    }
+}
+
+// slot_entry returns request's slot for namespace N, creating it if need be.
+fn slot_entry<'r, N: Namespace>(request: &'r Request<'_>) -> &'r Mutex<Slot> {
+    &request.local_cache(Entry::<N>::default).0
+}
+
+// with_slot calls f with request's slot in the default namespace.
+fn with_slot<T, F: FnOnce(&mut Slot) -> T>(request: &Request, f: F) -> T {
+    with_slot_in::<DefaultNamespace, T, F>(request, f)
+}
+
+// with_slot_in is with_slot, for namespace N.
+fn with_slot_in<N: Namespace, T, F: FnOnce(&mut Slot) -> T>(request: &Request, f: F) -> T {
+    let mut slot = slot_entry::<N>(request).lock().unwrap();
+    f(&mut slot)
+}
+
+// with_tracked calls f with what the fairing recorded about request, if anything.
+fn with_tracked<T, F: FnOnce(&Tracked) -> T>(request: &Request, f: F) -> Option<T> {
+    with_slot(request, |slot| slot.tracked.as_ref().map(f))
+}
+
+// with_tracked_mut is with_tracked, but lets f update the record.
+fn with_tracked_mut<T, F: FnOnce(&mut Tracked) -> T>(request: &Request, f: F) -> Option<T> {
+    with_slot(request, |slot| slot.tracked.as_mut().map(f))
+}
+
+// tracked_guard is the outcome for a guard which returns what get finds in the fairing's record
+// of request; what names it in logs. It tells a missing fairing apart from one which is attached,
+// but didn't give the request an ID (e.g. under InboundPolicy::Require) or isn't tracking it.
+fn tracked_guard<T, F>(request: &Request, what: &str, get: F) -> ReqOutcome<T, RequestIDError>
+where
+    F: FnOnce(&Tracked) -> Option<T>,
+{
+    let (attached, found) =
+        with_slot(request, |slot| (slot.attached, slot.tracked.as_ref().map(get)));
+    match found {
+        Some(Some(value)) => Outcome::Success(value),
+        _ if !attached => {
+            error!("unable to get {}: did you forget to attach the fairing?", what);
+            Outcome::Error((Status::InternalServerError, RequestIDError::FairingNotAttached))
+        }
+        Some(None) => {
+            error!("unable to get {}: the fairing isn't tracking it", what);
+            Outcome::Error((Status::InternalServerError, RequestIDError::NotTracked))
+        }
+        None => {
+            debug!("unable to get {}: the fairing didn't assign the request an id", what);
+            Outcome::Error((Status::InternalServerError, RequestIDError::NoRequestID))
+        }
+    }
+}
+
+// Attached is managed by rockets with a fairing in namespace N attached.
+struct Attached<N>(PhantomData<fn() -> N>);
+
+// mark_attached manages an Attached<N> for rocket, unless another fairing already has.
+fn mark_attached<N: Namespace>(rocket: Rocket<Build>) -> Rocket<Build> {
+    if rocket.state::<Attached<N>>().is_some() {
+        return rocket;
+    }
+    rocket.manage(Attached::<N>(PhantomData))
+}
+
+// fairing_missing is the sentinel for guards which need the fairing: it aborts launch if none is
+// attached in the default namespace, which is the only one those guards read.
+fn fairing_missing(rocket: &Rocket<Ignite>, guard: &str) -> bool {
+    if rocket.state::<Attached<DefaultNamespace>>().is_some() {
+        return false;
+    }
+    error!(
+        "a route takes a {} guard, which needs a RequestIDFairing: attach one, or take an \
+         Optional<{}> to do without",
+        guard, guard
+    );
+    true
+}
+
+thread_local! {
+    // The ID of the request being handled on this thread, if the fairing sets it.
+    static CURRENT_REQUEST_ID: Cell<Option<RequestID>> = const { Cell::new(None) };
+}
+
+/// Returns the ID of the request being handled on this thread, if there is one, and the fairing was
+/// configured with `RequestIDFairing::with_thread_local_id`; see it for the caveats.
+pub fn current_request_id() -> Option<RequestID> {
+    CURRENT_REQUEST_ID.with(|current| current.get())
+}
+
+/// Returns the instance ID in the low `bits` bits of an ID generated by a fairing with
+/// `RequestIDFairing::with_instance_id` (or `with_hostname_instance_id`) with the same `bits`.
+pub fn instance_id(id: u64, bits: u32) -> u64 {
+    match bits {
+        64 => id,
+        _ => id & ((1 << bits) - 1),
+    }
+}
+
+/// Returns the number of requests the fairing is currently tracking: those which have arrived, but
+/// haven't been responded to yet.
+///
+/// Every fairing in the process shares the count. If the fairing isn't attached, it's always 0.
+///
+/// This is only a count: what the fairing records about a request is kept in the request itself,
+/// and dropped along with it, so there's no map of requests which could grow without bound.
+pub fn active_request_count() -> usize {
+    ACTIVE_REQUESTS.load(Ordering::Relaxed)
+}
+
+// hex parses a field of lowercase hex digits, which is the only case W3C Trace Context and B3
+// allow.
+fn hex(field: &str) -> Option<u128> {
+    if !field.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b)) {
+        return None;
+    }
+    u128::from_str_radix(field, 16).ok()
+}
+
+// random_nonzero_u64 generates a span ID; tracing formats reserve 0 to mean no span.
+fn random_nonzero_u64() -> u64 {
+    loop {
+        let n = thread_rng().gen();
+        if n != 0 {
+            return n;
+        }
+    }
+}
+
+// Inbound is what a request's upstream told us about its ID.
+#[derive(Default)]
+struct Inbound {
+    // The first valid inbound ID, verbatim.
+    raw: Option<String>,
+    // The first valid inbound ID which parses as our kind of ID, and the header it was in.
+    id: Option<((u32, u128), Cow<'static, str>)>,
+    // The first header with an invalid or unparseable inbound ID, before id was found.
+    invalid: Option<Cow<'static, str>>,
 }
 
+// InboundValidator checks inbound IDs; see RequestIDFairing::with_inbound_validator.
+type InboundValidator = dyn Fn(&str) -> bool + Send + Sync;
+
+// RequestFilter picks the requests a fairing gives IDs; see RequestIDFairing::with_filter.
+type RequestFilter = dyn Fn(&Request) -> bool + Send + Sync;
+
+///
+/// A `Fairing` which assigns IDs to requests as they arrive, and surfaces them on responses.
+///
+/// The `RequestID` guard works without it, but only gives requests random IDs; the fairing is
+/// what honors inbound IDs, applies its configuration, and backs the other guards. It also sets
+/// an `X-Request-ID` header containing the ID on every response, unless the handler has already
+/// set one itself. This includes responses from handlers which never use the `RequestID` guard
+/// and error responses produced by catchers.
+///
+/// If a request arrives with an `X-Request-ID` header, for example one set by a proxy in front of
+/// rocket, its value is used as the request's ID instead of generating a new one. Only the first
+/// such header is considered (see `with_inbound_policy` to change this), and it is ignored unless
+/// it parses as the kind of ID the fairing
+/// generates (a `u64`, unless configured otherwise). Before that, it must be at most 128 bytes long
+/// and consist only of ASCII alphanumerics, `-`, `_` and `.`; this keeps garbage, such as
+/// newlines meant to forge log lines, out of the logs. By default the header
+/// is honored from any client; see `with_trusted_proxies` to restrict that.
 ///
-/// A `Fairing` that must be attached to a rocket instance before a `RequestID` request guard may
-/// be used.
+/// With the `tracing` feature, the fairing also opens a `request` span with a `request_id` field
+/// for each request it gives an ID. Rocket may poll a request's handler on any thread, and other
+/// requests' on the same one, so the fairing can't enter the span for the handler; instead, the
+/// `RequestSpan` guard returns it, for the handler to enter or instrument its futures with.
+///
+/// With the `opentelemetry` feature, the fairing also starts an OpenTelemetry server span with a
+/// `request_id` attribute for each request it gives an ID, from the global tracer provider, and
+/// ends it with the response. Combine this with `with_trace_context` for the span to continue the
+/// request's trace.
 ///
 /// It should be attached like so:
 /// ```
-/// use rocket_request_id;
+/// let rocket = rocket::build().attach(rocket_request_id::RequestIDFairing);
+/// ```
 ///
-/// rocket::ignite()
-///     .attach(rocket_request_id::RequestIDFairing)
-///     .launch();
+/// Or, to use a header other than `X-Request-ID`:
 /// ```
+/// use rocket_request_id::RequestIDFairing;
 ///
-pub struct RequestIDFairing;
-
-impl<'r> rocket::fairing::Fairing for RequestIDFairing {
-    fn info(&self) -> rocket::fairing::Info {
-        rocket::fairing::Info {
-            kind: rocket::fairing::Kind::Request | rocket::fairing::Kind::Response,
-            name: "request id",
-        }
-    }
-
-    fn on_request(&self, request: &mut Request, _: &rocket::Data) {
-        REQUEST_IDS
-            .lock()
-            .unwrap()
-            .insert(request as *const Request as usize, thread_rng().gen());
-    }
-    fn on_response(&self, request: &Request, _: &mut Response) {
-        REQUEST_IDS
-            .lock()
-            .unwrap()
-            .remove(&(request as *const Request as usize));
-    }
+/// let rocket = rocket::build()
+///     .attach(RequestIDFairing::default().with_header_name("X-Correlation-ID"));
+/// ```
+///
+pub struct RequestIDFairing {
+    header_name: Cow<'static, str>,
+    inbound_headers: Option<Vec<Cow<'static, str>>>,
+    query_param: Option<Cow<'static, str>>,
+    inbound_policy: InboundPolicy,
+    kind: IdKind,
+    prefix: &'static str,
+    generator: Option<Box<dyn IdGenerator>>,
+    // The number of low bits generated u64 IDs are masked to.
+    bits: u32,
+    // The u64 IDs which requests are never given.
+    reserved: Cow<'static, [u64]>,
+    // The instance ID generated u64 IDs end with, and its width in bits.
+    instance: Option<(u64, u32)>,
+    trusted_proxies: Option<Vec<Cidr>>,
+    max_inbound_len: usize,
+    oversized_inbound_policy: OversizedInboundPolicy,
+    inbound_validator: Option<Box<InboundValidator>>,
+    // Whether the fairing gives a request an ID at all; by default, it gives every request one.
+    filter: Option<Box<RequestFilter>>,
+    // The fraction of requests the fairing gives an ID.
+    sampling: f64,
+    // The IDs in flight, how many times to regenerate an ID which is, and what to do if it
+    // still is, if the fairing checks for collisions.
+    collisions: Option<(Arc<InFlight>, u32, OnDuplicateId)>,
+    // The IDs given to requests with each recent idempotency key, if the fairing maps keys to IDs.
+    idempotency_keys: Option<IdempotencyKeys>,
+    // The key to sign the IDs in response headers with, and verify inbound IDs with, if any.
+    #[cfg(feature = "signing")]
+    signing_key: Option<Vec<u8>>,
+    on_invalid: InvalidIdAction,
+    trace_context: bool,
+    b3_single: bool,
+    b3_multi: bool,
+    b3_request_ids: bool,
+    amzn_trace_id: bool,
+    session_cookie: Option<SessionCookie>,
+    causation_chain: bool,
+    max_causation_depth: usize,
+    correlation_id: bool,
+    correlate_by_trace: bool,
+    header_paths: Option<Vec<PathRule>>,
+    logging: bool,
+    server_timing: bool,
+    thread_local_id: bool,
+    // Finds a request's slot in the fairing's namespace.
+    namespace: for<'r> fn(&'r Request<'_>) -> &'r Mutex<Slot>,
+    // Records that a rocket has a fairing in the fairing's namespace, for the guards' sentinels.
+    mark_attached: fn(Rocket<Build>) -> Rocket<Build>,
+    // The generators random IDs come from, seeded when the fairing is attached.
+    rngs: OnceLock<RngPool>,
 }
 
+/// Allocates IDs for requests which a `RequestIDFairing` doesn't inherit an ID for.
 ///
-/// A unique ID for a given rocket request.
-/// This ID should be retrieved via its `FromRequest` implementation; that is to say, add an
-/// argument of the type `RequestID` to a rocket handler. That argument can then be dereferenced to
-/// access the ID.
+/// By default, the fairing generates random IDs; see `RequestIDFairing::with_generator` to use an
+/// `IdGenerator` instead.
 ///
-/// If multiple parameters of this type are requested, each will have the same ID.
-/// This property holds even if they are instantiated by other request guards.
+/// Generated IDs are `u64`s, so that whatever the generator, handlers get the same `RequestID`
+/// guard, and inbound IDs can be parsed back into one. A scheme which needs its own rendering can
+/// still pack its ID into 64 bits, and use `RequestIDFairing::with_format` or `with_prefix`.
 ///
-/// For example, the following is a typical usage:
 /// ```
-/// use rocket_request_id;
+/// use rocket::Request;
+/// use rocket_request_id::IdGenerator;
 ///
-/// #[get("/")]
-/// fn test_req_id(id: rocket_request_id::RequestID) -> String {
-///     format!("Hello, your request had ID {}", *id)
+/// // Give requests for the same path the same ID: a bad idea, but a short example.
+/// struct PathLength;
+///
+/// impl IdGenerator for PathLength {
+///     fn generate(&self, request: &Request) -> u64 {
+///         request.uri().path().as_str().len() as u64
+///     }
 /// }
 /// ```
-#[derive(Debug, PartialEq, Eq)]
-pub struct RequestID {
-    id: u64,
+pub trait IdGenerator: Send + Sync {
+    /// Returns the ID for `request`.
+    fn generate(&self, request: &Request) -> u64;
 }
 
-impl From<RequestID> for u64 {
-    fn from(r: RequestID) -> u64 {
-        r.id
+// PathRule is a rule about which responses get the ID header: those for requests under prefix do,
+// or don't.
+struct PathRule {
+    prefix: Cow<'static, str>,
+    emit: bool,
+}
+
+impl PathRule {
+    // matches reports whether path is prefix or a path below it. This goes by segments, so /api
+    // doesn't match /apiv2.
+    fn matches(&self, path: &str) -> bool {
+        let prefix = self.prefix.trim_end_matches('/');
+        path.starts_with(prefix)
+            && (path.len() == prefix.len() || path.as_bytes()[prefix.len()] == b'/')
     }
 }
 
-impl<'a, 'r> FromRequest<'a, 'r> for RequestID {
-    type Error = ();
+// The next ID to be allocated by a Counter.
+static NEXT_COUNTER_ID: AtomicU64 = AtomicU64::new(1);
 
-    fn from_request(request: &'a Request<'r>) -> ReqOutcome<Self, Self::Error> {
-        match REQUEST_IDS
-            .lock()
-            .unwrap()
-            .get(&(request as *const Request as usize))
-        {
-            Some(id) => Outcome::Success(RequestID { id: *id }),
-            None => {
-                error!("unable to get request id: did you forget to attach the fairing?");
-                Outcome::Failure((Status::InternalServerError, ()))
+// Counter allocates sequential IDs, starting from 1. The sequence is shared by every fairing in the
+// process.
+struct Counter;
+
+impl IdGenerator for Counter {
+    fn generate(&self, _: &Request) -> u64 {
+        NEXT_COUNTER_ID.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+// OwnedCounter allocates sequential IDs of its own, in the low bits of IDs whose high bits are
+// high.
+struct OwnedCounter {
+    next: AtomicU64,
+    high: u64,
+    // The number of bits for the sequence.
+    bits: u32,
+}
+
+impl IdGenerator for OwnedCounter {
+    fn generate(&self, _: &Request) -> u64 {
+        let next = self.next.fetch_add(1, Ordering::Relaxed);
+        match self.bits {
+            64 => next,
+            bits => self.high | next & ((1 << bits) - 1),
+        }
+    }
+}
+
+// ObfuscatedCounter allocates sequential IDs of its own, starting from 1, and permutes them with a
+// key so they look random.
+struct ObfuscatedCounter {
+    next: AtomicU64,
+    key: u64,
+}
+
+impl IdGenerator for ObfuscatedCounter {
+    fn generate(&self, _: &Request) -> u64 {
+        feistel::obfuscate(self.next.fetch_add(1, Ordering::Relaxed), self.key)
+    }
+}
+
+// FnGenerator allocates IDs with a closure.
+struct FnGenerator<F>(F);
+
+impl<F: Fn(&Request) -> u64 + Send + Sync> IdGenerator for FnGenerator<F> {
+    fn generate(&self, request: &Request) -> u64 {
+        (self.0)(request)
+    }
+}
+
+// OsRandom allocates random IDs from the operating system's random number generator.
+struct OsRandom;
+
+impl OsRandom {
+    // try_generate returns a random u64 from the operating system, or why it couldn't get one.
+    fn try_generate() -> Result<u64, rand::Error> {
+        let mut id = [0; 8];
+        OsRng.try_fill_bytes(&mut id)?;
+        Ok(u64::from_ne_bytes(id))
+    }
+}
+
+impl IdGenerator for OsRandom {
+    fn generate(&self, _: &Request) -> u64 {
+        // Better to fail the request than to hand out an ID which isn't what was asked for.
+        match OsRandom::try_generate() {
+            Ok(id) => id,
+            Err(err) => {
+                panic!("unable to read the operating system's random number generator: {}", err)
             }
         }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use rocket;
-    use super::*;
-    use rocket::local::Client;
+// Seeded allocates random IDs from a seeded generator, which requests take turns with.
+struct Seeded<R>(Mutex<R>);
 
-    #[get("/")]
-    fn req_id(id: RequestID) -> String {
-        format!("{}", u64::from(id))
+impl<R: RngCore + Send> IdGenerator for Seeded<R> {
+    fn generate(&self, _: &Request) -> u64 {
+        self.0.lock().unwrap().gen()
     }
+}
 
-    #[test]
-    fn unique_ids() {
-        let rkt = rocket::ignite()
-            .attach(RequestIDFairing)
-            .mount("/", routes![req_id]);
-        let c = Client::new(rkt).unwrap();
+/// How a `RequestIDFairing` treats IDs that upstreams assign to requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InboundPolicy {
+    /// Never read inbound IDs; always generate a new one. Use this when requests come from
+    /// untrusted clients whose IDs shouldn't leak into your logs.
+    Ignore,
+    /// Use a request's inbound ID if it has a valid one, and generate one otherwise. This is the
+    /// default.
+    Prefer,
+    /// Only use inbound IDs. Requests without a valid one have no ID: the `RequestID` guard fails
+    /// for them, and their responses don't get an ID header.
+    Require,
+    /// Always generate a new ID, but keep a request's inbound ID, if it has a valid one, as its
+    /// `ParentRequestID`.
+    Parent,
+}
 
-        let mut resp1 = c.get("/").dispatch();
-        let mut resp2 = c.get("/").dispatch();
+/// What a `RequestIDFairing` does with inbound IDs which are longer than it allows; see
+/// `RequestIDFairing::with_oversized_inbound_policy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OversizedInboundPolicy {
+    /// Ignore them, as if the request had no inbound ID. This is the default.
+    Reject,
+    /// Keep only their first `n` bytes. The result still has to be a valid ID, e.g. a `u64`.
+    Truncate(usize),
+    /// Replace them with a hash of their value, so that the same upstream ID always maps to the
+    /// same ID. The hash is computed regardless of the inbound validator.
+    ///
+    /// With either `Truncate` or `Hash`, the whole value may only contain visible ASCII
+    /// characters, since it's kept as the request's `ParentRequestID`.
+    Hash,
+}
 
-        assert_eq!(resp1.status(), Status::Ok);
-        assert_eq!(resp2.status(), Status::Ok);
-        assert_ne!(resp1.body_string(), resp2.body_string());
+/// What a `RequestIDFairing` does with requests whose inbound ID is invalid; see
+/// `RequestIDFairing::with_on_invalid`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidIdAction {
+    /// Handle the request as if it had no inbound ID. This is the default.
+    Ignore,
+    /// Answer the request with a 400 Bad Request naming the header.
+    Reject,
+}
+
+/// What a `RequestIDFairing` does if it can't generate an ID that isn't already in flight; see
+/// `RequestIDFairing::with_collision_checks`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnDuplicateId {
+    /// Log a warning, and give the request the duplicate ID anyway.
+    Warn,
+    /// Panic, failing the request.
+    Panic,
+}
+
+/// How a `RequestIDFairing` renders `u64` IDs; see `RequestIDFairing::with_format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdFormat {
+    /// In decimal, e.g. `43707838`. This is the default.
+    Decimal,
+    /// As 16 lowercase hex digits, e.g. `00000000029aeebe`, so that IDs have a fixed width.
+    Hex,
+    /// In base62 (`0-9A-Za-z`), e.g. `2wDhA`, which is compact and URL-safe. IDs are at most 11
+    /// characters long, but have no leading zeros, so their width varies.
+    Base62,
+    /// In base62, followed by a check character, e.g. `2xOP8Y` for `43707838`, so that an ID
+    /// which was mistyped, e.g. while copying it off a screenshot, is caught rather than looked
+    /// for; see `RequestID::parse_checked`. Inbound IDs are only honored if their check
+    /// character matches.
+    Base62Checked,
+    /// In [Crockford's base32](https://www.crockford.com/base32.html), e.g. `19NVDY`, which
+    /// leaves out letters that are easily mistaken for others, so IDs can be read over the phone.
+    /// IDs are at most 13 characters long, but have no leading zeros. Inbound IDs are honored
+    /// in either case, with `O` read as `0`, `I` and `L` as `1`, and hyphens ignored, as the
+    /// encoding allows.
+    Crockford,
+}
+
+/// The default `RequestIDFairing`.
+///
+/// This exists so that the fairing may be attached as `.attach(RequestIDFairing)`, as it could be
+/// back when it was a unit struct.
+// Every use of the constant is a fresh copy, with generators of its own, which is what we want.
+#[allow(non_upper_case_globals, clippy::declare_interior_mutable_const)]
+pub const RequestIDFairing: RequestIDFairing = RequestIDFairing {
+    header_name: Cow::Borrowed(REQUEST_ID_HEADER),
+    inbound_headers: None,
+    query_param: None,
+    inbound_policy: InboundPolicy::Prefer,
+    kind: IdKind::Number,
+    prefix: "",
+    generator: None,
+    bits: 64,
+    reserved: Cow::Borrowed(&DEFAULT_RESERVED_IDS),
+    instance: None,
+    trusted_proxies: None,
+    max_inbound_len: DEFAULT_MAX_INBOUND_LEN,
+    oversized_inbound_policy: OversizedInboundPolicy::Reject,
+    inbound_validator: None,
+    filter: None,
+    sampling: 1.0,
+    collisions: None,
+    idempotency_keys: None,
+    #[cfg(feature = "signing")]
+    signing_key: None,
+    on_invalid: InvalidIdAction::Ignore,
+    trace_context: false,
+    b3_single: false,
+    b3_multi: false,
+    b3_request_ids: false,
+    amzn_trace_id: false,
+    session_cookie: None,
+    causation_chain: false,
+    max_causation_depth: DEFAULT_MAX_CAUSATION_DEPTH,
+    correlation_id: false,
+    correlate_by_trace: false,
+    header_paths: None,
+    logging: false,
+    server_timing: false,
+    thread_local_id: false,
+    namespace: slot_entry::<DefaultNamespace>,
+    mark_attached: mark_attached::<DefaultNamespace>,
+    rngs: OnceLock::new(),
+};
+
+impl Default for RequestIDFairing {
+    fn default() -> RequestIDFairing {
+        RequestIDFairing
     }
+}
 
-    #[test]
-    fn doesnt_leak() {
-        let rkt = rocket::ignite()
-            .attach(RequestIDFairing)
-            .mount("/", routes![req_id]);
-        let c = Client::new(rkt).unwrap();
+impl RequestIDFairing {
+    /// Returns the default fairing, but allocating IDs with `generate`, as with a custom
+    /// `IdGenerator` (see `with_generator`). The other options may be set as usual.
+    ///
+    /// ```
+    /// use rocket_request_id::{IdFormat, RequestIDFairing};
+    ///
+    /// // Number requests by the length of their path: a bad idea, but a short example.
+    /// let fairing =
+    ///     RequestIDFairing::from_fn(|request| request.uri().path().as_str().len() as u64)
+    ///         .with_format(IdFormat::Hex);
+    /// ```
+    pub fn from_fn<F>(generate: F) -> RequestIDFairing
+    where
+        F: Fn(&Request) -> u64 + Send + Sync + 'static,
+    {
+        RequestIDFairing::default().with_generator(FnGenerator(generate))
+    }
 
-        assert_eq!(c.get("/").dispatch().status(), Status::Ok);
-        assert_eq!(c.get("/").dispatch().status(), Status::Ok);
+    /// Use the given header, rather than `X-Request-ID`, both to read an upstream-assigned ID from
+    /// requests and to surface the ID on responses.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is not a valid HTTP header name, since such a header could never be
+    /// matched on a request.
+    pub fn with_header_name<N: Into<Cow<'static, str>>>(mut self, name: N) -> RequestIDFairing {
+        let name = name.into();
+        if !is_valid_header_name(&name) {
+            panic!("invalid request id header name: {:?}", name);
+        }
+        self.header_name = name;
+        self
+    }
+
+    /// Give requests IDs in the namespace `N`, rather than the default one, so that they're kept
+    /// apart from those of the app's other fairings. Handlers get them with the `RequestID<N>`
+    /// guard. This is for apps which track several IDs per request, each with its own fairing and
+    /// header:
+    ///
+    /// ```
+    /// # #[macro_use] extern crate rocket;
+    /// use rocket_request_id::{Namespace, RequestID, RequestIDFairing};
+    ///
+    /// enum Session {}
+    /// impl Namespace for Session {}
+    ///
+    /// #[get("/")]
+    /// fn index(id: RequestID, session: RequestID<Session>) -> String {
+    ///     format!("request {} in session {}", id, session)
+    /// }
+    ///
+    /// # fn main() {
+    /// let rocket = rocket::build()
+    ///     .attach(RequestIDFairing::default())
+    ///     .attach(
+    ///         RequestIDFairing::default()
+    ///             .in_namespace::<Session>()
+    ///             .with_header_name("X-Session-ID"),
+    ///     )
+    ///     .mount("/", routes![index]);
+    /// # }
+    /// ```
+    ///
+    /// The other guards, such as `RequestIDSource` and `TraceParent`, only see what the fairing
+    /// in the default namespace tracks.
+    pub fn in_namespace<N: Namespace>(mut self) -> RequestIDFairing {
+        self.namespace = slot_entry::<N>;
+        self.mark_attached = mark_attached::<N>;
+        self
+    }
+
+    // with_slot calls f with request's slot in the fairing's namespace.
+    fn with_slot<T, F: FnOnce(&mut Slot) -> T>(&self, request: &Request, f: F) -> T {
+        let mut slot = (self.namespace)(request).lock().unwrap();
+        f(&mut slot)
+    }
+
+    /// Look for an upstream-assigned ID in each of the given headers, in order, rather than only in
+    /// the header set with `with_header_name`. The first header which is present and holds a valid
+    /// ID is used. This doesn't affect which header the ID is surfaced in on responses.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of `names` is not a valid HTTP header name.
+    ///
+    /// ```
+    /// use rocket_request_id::RequestIDFairing;
+    ///
+    /// let fairing = RequestIDFairing::default()
+    ///     .with_inbound_headers(vec!["X-Request-ID", "X-Correlation-ID"]);
+    /// ```
+    pub fn with_inbound_headers<I, N>(mut self, names: I) -> RequestIDFairing
+    where
+        I: IntoIterator<Item = N>,
+        N: Into<Cow<'static, str>>,
+    {
+        let names = names
+            .into_iter()
+            .map(|name| {
+                let name = name.into();
+                if !is_valid_header_name(&name) {
+                    panic!("invalid request id header name: {:?}", name);
+                }
+                name
+            })
+            .collect();
+        self.inbound_headers = Some(names);
+        self
+    }
+
+    /// Also look for an upstream-assigned ID in the query parameter `name`, e.g. `rid` for
+    /// `/webhook?rid=1234`, for upstreams which can't set headers. An inbound ID header takes
+    /// precedence, and requests with neither get a generated ID, as usual.
+    ///
+    /// The parameter is honored as an inbound header would be, under the inbound policy and from
+    /// trusted proxies only, but an invalid one is always ignored, rather than rejected under
+    /// `InvalidIdAction::Reject`.
+    pub fn with_query_param<S: Into<Cow<'static, str>>>(mut self, name: S) -> RequestIDFairing {
+        self.query_param = Some(name.into());
+        self
+    }
+
+    /// Set how IDs that upstreams assign to requests are treated, and so where requests' IDs come
+    /// from: an inbound header, falling back to generating one (`InboundPolicy::Prefer`, the
+    /// default), always generating one (`Ignore`), or only an inbound header (`Require`, under
+    /// which handlers taking an `Option<RequestID>` get `None` for requests without one).
+    pub fn with_inbound_policy(mut self, policy: InboundPolicy) -> RequestIDFairing {
+        self.inbound_policy = policy;
+        self
+    }
+
+    /// Allocate IDs with `generator`, rather than generating random ones.
+    pub fn with_generator<G: IdGenerator + 'static>(mut self, generator: G) -> RequestIDFairing {
+        if !self.kind.is_formatted() {
+            self.kind = IdKind::Number;
+        }
+        self.generator = Some(Box::new(generator));
+        self
+    }
+
+    /// Render IDs (in the response header, and the `RequestID` guard's `Display` implementation)
+    /// in `format`. Inbound IDs are then only honored if they're in the same format, though hex
+    /// digits may be in either case.
+    ///
+    /// IDs are `u64`s in every format, so this undoes `with_uuids`, `with_v7_uuids`,
+    /// `with_128_bit_ids`, `with_short_ids`, `with_ulids`, `with_ksuids`, `with_snowflakes`,
+    /// `with_timestamped_ids`, `with_xids` and `with_nanoids`.
+    pub fn with_format(mut self, format: IdFormat) -> RequestIDFairing {
+        self.kind = match format {
+            IdFormat::Decimal => IdKind::Number,
+            IdFormat::Hex => IdKind::HexNumber,
+            IdFormat::Base62 => IdKind::Base62Number,
+            IdFormat::Base62Checked => IdKind::Base62Checked,
+            IdFormat::Crockford => IdKind::CrockfordNumber,
+        };
+        self
+    }
+
+    /// Prepend `prefix` to IDs wherever they're rendered: in the response header, and the
+    /// `RequestID` guard's `Display` implementation (and so in logs). This distinguishes the IDs
+    /// of different services sharing a log store, e.g. `api-3587425802818797902`.
+    ///
+    /// Inbound IDs may arrive with or without the prefix. Either way, an ID is the same `RequestID`
+    /// as far as comparisons go; the prefix only affects how it's rendered.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `prefix` contains anything but ASCII letters, digits, `-`, `_`, and `.`.
+    pub fn with_prefix(mut self, prefix: &'static str) -> RequestIDFairing {
+        if !is_safe_id(prefix) {
+            panic!("invalid request id prefix {:?}", prefix);
+        }
+        self.prefix = prefix;
+        self
+    }
+
+    /// Generate `u64` IDs which fit in `bits` bits, for downstreams which can't handle bigger ones,
+    /// by keeping only the low `bits` bits of each one: random IDs are just as random within the
+    /// narrower range, and a counter wraps around when it runs out. Fewer bits make collisions more
+    /// likely, though: with 32, there's an even chance of one among about 77,000 random IDs.
+    ///
+    /// Inbound IDs are taken as they are, and this only applies to IDs in an `IdFormat`: UUIDs,
+    /// Snowflake IDs and the like keep their own layouts.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bits` isn't between 1 and 64, or doesn't leave room for more than the
+    /// instance ID (see `with_instance_id`).
+    pub fn with_bits(mut self, bits: u32) -> RequestIDFairing {
+        let instance_bits = self.instance.map_or(0, |(_, bits)| bits);
+        if bits == 0 || bits > 64 || bits <= instance_bits {
+            panic!("invalid request id bit width {}", bits);
+        }
+        self.bits = bits;
+        self
+    }
+
+    /// Never give requests any of `ids`, as well as 0 and `u64::MAX`, e.g. because they mean
+    /// something else to downstreams. If one is generated, another is generated in its place;
+    /// inbound IDs which are reserved are invalid. This only applies to `u64` IDs.
+    ///
+    /// A generator which only generates reserved IDs makes the fairing panic, after 100 attempts.
+    pub fn with_reserved_ids(mut self, ids: &[u64]) -> RequestIDFairing {
+        self.reserved.to_mut().extend_from_slice(ids);
+        self
+    }
+
+    /// End generated `u64` IDs with `instance`, in their low `bits` bits, so that an ID shows
+    /// which of a service's instances handled the request; `instance_id(id, bits)` gets it back.
+    /// The rest of each ID is generated as usual, shifted up to make room, so counters still
+    /// count up.
+    ///
+    /// Like `with_bits`, this only applies to IDs in an `IdFormat`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bits` isn't between 1 and 63, or doesn't leave room for more than the instance
+    /// ID in the fairing's bit width, or `instance` doesn't fit in `bits` bits.
+    pub fn with_instance_id(mut self, instance: u64, bits: u32) -> RequestIDFairing {
+        if bits == 0 || bits >= self.bits {
+            panic!("invalid instance id bits {}", bits);
+        }
+        if instance >> bits != 0 {
+            panic!("invalid instance id {} for {} bits", instance, bits);
+        }
+        self.instance = Some((instance, bits));
+        self
+    }
+
+    /// Like `with_instance_id`, but with a hash of this machine's hostname as the instance ID,
+    /// or, if the hostname can't be found, a random one. With few bits, instances' hashes may
+    /// well collide (with 8 bits and 30 instances, two probably do), so give
+    /// the hash plenty, or prefer numbering instances yourself.
+    ///
+    /// # Panics
+    ///
+    /// Panics as `with_instance_id` does for `bits`.
+    pub fn with_hostname_instance_id(self, bits: u32) -> RequestIDFairing {
+        let instance = match hostname() {
+            Some(hostname) => fnv1a(&hostname) as u64,
+            None => {
+                warn!("unable to find this machine's hostname; using a random instance id");
+                thread_rng().gen()
+            }
+        };
+        let mask = if bits >= 64 { u64::MAX } else { (1 << bits) - 1 };
+        self.with_instance_id(instance & mask, bits)
+    }
+
+    /// Allocate sequential IDs (1, 2, 3, ...), rather than random ones, so that IDs show the
+    /// order requests arrived in. The sequence is shared by the whole process.
+    pub fn with_counter(self) -> RequestIDFairing {
+        self.with_generator(Counter)
+    }
+
+    /// Like `with_counter`, but the sequence starts from `start`, and belongs to this fairing
+    /// alone.
+    pub fn with_counter_from(self, start: u64) -> RequestIDFairing {
+        self.with_generator(OwnedCounter {
+            next: AtomicU64::new(start),
+            high: 0,
+            bits: 64,
+        })
+    }
+
+    /// Like `with_counter_from(1)`, but with `random_bits` random bits, chosen when this is
+    /// called, above the sequence number, so that restarted or parallel instances of a service
+    /// don't give out the same IDs. The sequence numbers still show the order requests arrived
+    /// in, and wrap around if they run out of bits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `random_bits` isn't between 1 and 63.
+    pub fn with_instance_counter(self, random_bits: u32) -> RequestIDFairing {
+        if random_bits == 0 || random_bits >= 64 {
+            panic!("invalid instance counter random bits {}", random_bits);
+        }
+        let bits = 64 - random_bits;
+        self.with_generator(OwnedCounter {
+            next: AtomicU64::new(1),
+            high: thread_rng().gen::<u64>() << bits,
+            bits,
+        })
+    }
+
+    /// Like `with_counter_from(1)`, but each sequence number is scrambled with `key` on its way
+    /// out, so IDs look random, and don't give away how many requests a service handles. Each
+    /// sequence number still gets an ID of its own, and `deobfuscate(id, key)` turns an ID back
+    /// into its sequence number.
+    ///
+    /// This only obscures the sequence: anyone with a few IDs and their sequence numbers could
+    /// work out the key. Keep the key in configuration to deobfuscate IDs later, or pass
+    /// `rand::random()` to pick one at startup if nobody will.
+    pub fn with_obfuscated_counter(self, key: u64) -> RequestIDFairing {
+        self.with_generator(ObfuscatedCounter {
+            next: AtomicU64::new(1),
+            key,
+        })
+    }
+
+    /// Generate random `u64` IDs straight from the operating system's random number generator,
+    /// rather than from a userspace generator seeded from it. This costs a system call per
+    /// request, but leaves nothing about the IDs to guess if they're exposed to clients, e.g. in
+    /// signed URLs.
+    ///
+    /// The default generator is a CSPRNG too, but one that's periodically reseeded from the
+    /// operating system in userspace, so its IDs are only as good as this process's memory is
+    /// private.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the operating system's random number generator can't be read. If it later
+    /// fails, the requests it fails for fail too, rather than getting weaker IDs.
+    pub fn with_secure_ids(self) -> RequestIDFairing {
+        if let Err(err) = OsRandom::try_generate() {
+            panic!("unable to read the operating system's random number generator: {}", err);
+        }
+        self.with_generator(OsRandom)
+    }
+
+    /// Generate random `u64` IDs ahead of time, on a background thread which keeps a pool of
+    /// `size` of them, and tops it up whenever fewer than `refill_at` are left, so that requests
+    /// only have to take one. Taking an ID never blocks: if the pool has run dry, the request's
+    /// ID is generated as usual. The thread stops when the fairing is dropped.
+    ///
+    /// Generating an ID only takes a few nanoseconds to begin with, so this is for services which
+    /// watch their tail latency very closely.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is 0, or `refill_at` is 0 or more than `size`.
+    pub fn with_id_pool(self, size: usize, refill_at: usize) -> RequestIDFairing {
+        if size == 0 || refill_at == 0 || refill_at > size {
+            panic!("invalid id pool size {} with refill threshold {}", size, refill_at);
+        }
+        self.with_generator(IdPool::new(size, refill_at))
+    }
+
+    /// Generate random `u64` IDs from a generator seeded with `seed`, so that a fairing gives
+    /// the same sequence of IDs every time, e.g. to check logged IDs in tests. Don't use this in
+    /// production: anyone who knows the seed can predict every ID.
+    ///
+    /// The sequence for a seed may change when this crate's version of `rand` does.
+    pub fn with_seed(self, seed: u64) -> RequestIDFairing {
+        self.with_rng(StdRng::seed_from_u64(seed))
+    }
+
+    /// Generate random `u64` IDs from `rng`, e.g. a seeded generator of a particular algorithm,
+    /// if `with_seed`'s may change. Requests take turns with the generator, so concurrent
+    /// requests still get successive values from it, though which request gets which depends on
+    /// the order they arrive in.
+    pub fn with_rng<R: RngCore + Send + 'static>(self, rng: R) -> RequestIDFairing {
+        self.with_generator(Seeded(Mutex::new(rng)))
+    }
+
+    /// Also track each request's [W3C Trace Context](https://www.w3.org/TR/trace-context/):
+    /// continue the trace in a request's `traceparent` and `tracestate` headers (if it has a valid
+    /// `traceparent`, and the request would be allowed to set its ID), give the request a new span
+    /// ID, and set `traceparent` and `tracestate` headers for that span on the response. The
+    /// context is available through the `TraceParent` guard.
+    ///
+    /// This is independent of the request's ID.
+    pub fn with_trace_context(mut self) -> RequestIDFairing {
+        self.trace_context = true;
+        self
+    }
+
+    /// Also track each request's [Zipkin B3](https://github.com/openzipkin/b3-propagation) trace
+    /// context, from the single `b3` header: continue the trace in a request's `b3` header (if it
+    /// has a valid one, and the request would be allowed to set its ID), give the request a new
+    /// span ID, and set a `b3` header for that span on the response. The context is available
+    /// through the `B3` guard.
+    ///
+    /// Like `with_trace_context`, this is independent of the request's ID; the trace ID is what
+    /// correlates a request with the rest of its trace.
+    pub fn with_b3(mut self) -> RequestIDFairing {
+        self.b3_single = true;
+        self
+    }
+
+    /// Like `with_b3`, but for B3's older multi-header format (`X-B3-TraceId`, `X-B3-SpanId`,
+    /// `X-B3-ParentSpanId`, `X-B3-Sampled` and `X-B3-Flags`). A request with no `X-B3-TraceId`
+    /// starts a new trace.
+    ///
+    /// This may be combined with `with_b3`, in which case a request's `b3` header takes precedence
+    /// if it has one, and the response gets both forms.
+    pub fn with_b3_multi(mut self) -> RequestIDFairing {
+        self.b3_multi = true;
+        self
+    }
+
+    /// Like `with_b3_multi`, but also use a request's `X-B3-TraceId` as its ID, if it has a valid
+    /// one and no inbound ID header, so that the request ID correlates a request with the rest of
+    /// its trace after all. Requests without one get a newly generated ID, as usual, and the
+    /// response's `X-B3-TraceId` header carries the trace ID back.
+    ///
+    /// `u64` IDs are the low 64 bits of a 128-bit trace ID, as B3 implementations which only
+    /// support 64-bit trace IDs use, so with `with_format(IdFormat::Hex)` a 64-bit trace ID is
+    /// the request ID verbatim.
+    pub fn with_b3_propagation(mut self) -> RequestIDFairing {
+        self.b3_multi = true;
+        self.b3_request_ids = true;
+        self
+    }
+
+    /// Also track each request's AWS `X-Amzn-Trace-Id` header, as set by load balancers and X-Ray:
+    /// keep the `Root` of a request's header (if it has a valid one, and the request would be
+    /// allowed to set its ID) or generate a new one, add a `Self` field for this service, and set
+    /// the resulting header on the response. The header is available through the `AmznTraceId`
+    /// guard.
+    ///
+    /// This is independent of the request's ID; the root is what correlates a request with the
+    /// load balancer's access logs.
+    pub fn with_amzn_trace_id(mut self) -> RequestIDFairing {
+        self.amzn_trace_id = true;
+        self
+    }
+
+    /// Also correlate the requests each browser makes, by keeping a cookie configured by `cookie`:
+    /// a request which presents the cookie with a valid ID (by the same rules as an inbound ID
+    /// header) keeps it, and any other request's own ID starts a new chain. Every response sets
+    /// the cookie again. The ID is available through the `SessionCorrelationID` guard.
+    ///
+    /// The cookie is read regardless of the inbound policy and trusted proxies, since it's the
+    /// browser which sends it, but it has no effect on requests' own IDs.
+    ///
+    /// ```
+    /// use rocket_request_id::{RequestIDFairing, SessionCookie};
+    /// use std::time::Duration;
+    ///
+    /// let fairing = RequestIDFairing::default().with_session_cookie(
+    ///     SessionCookie::new()
+    ///         .with_max_age(Duration::from_secs(24 * 60 * 60))
+    ///         .with_secure(true),
+    /// );
+    /// ```
+    pub fn with_session_cookie(mut self, cookie: SessionCookie) -> RequestIDFairing {
+        self.session_cookie = Some(cookie);
+        self
+    }
+
+    /// Also track the chain of requests which caused each request, from its `X-Causation-ID`
+    /// header (a comma-separated list of IDs, oldest first) and its upstream's ID for it, if the
+    /// request would be allowed to set its ID. The chain is available through the
+    /// `CausationChain` guard, which also returns the header to continue it with on requests made
+    /// on the request's behalf.
+    ///
+    /// IDs in the header must be valid by the same rules as an inbound ID header, and the chain
+    /// only keeps the newest 16 of them; see `with_max_causation_depth` to change that.
+    pub fn with_causation_chain(mut self) -> RequestIDFairing {
+        self.causation_chain = true;
+        self
+    }
+
+    /// Keep at most `depth` IDs in causation chains, rather than 16, so that a client can't make
+    /// the chain grow without bound. Older IDs are dropped first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `depth` is 0.
+    pub fn with_max_causation_depth(mut self, depth: usize) -> RequestIDFairing {
+        if depth == 0 {
+            panic!("invalid causation chain depth: it must be at least 1");
+        }
+        self.max_causation_depth = depth;
+        self
+    }
+
+    /// Also give each request a correlation ID, which stays the same across every service an
+    /// interaction spans: a request which arrives with a valid `X-Correlation-ID` header (by the
+    /// same rules as an inbound ID header, if the request would be allowed to set its ID) keeps
+    /// it, and any other request's own ID starts a new interaction. Every response echoes it in
+    /// an `X-Correlation-ID` header. The ID is available through the `CorrelationID` guard.
+    ///
+    /// This doesn't affect requests' own IDs, so to give each hop a new `RequestID`, while keeping
+    /// the upstream's in the `ParentRequestID` guard, combine it with `InboundPolicy::Parent`:
+    ///
+    /// ```
+    /// use rocket_request_id::{InboundPolicy, RequestIDFairing};
+    ///
+    /// let fairing = RequestIDFairing::default()
+    ///     .with_correlation_id()
+    ///     .with_inbound_policy(InboundPolicy::Parent);
+    /// ```
+    pub fn with_correlation_id(mut self) -> RequestIDFairing {
+        self.correlation_id = true;
+        self
+    }
+
+    /// Like `with_correlation_id`, but a request without a valid `X-Correlation-ID` header takes
+    /// the ID of its W3C trace (as 32 hex digits) as its correlation ID, rather than its own ID, so
+    /// that an interaction is correlated by the `traceparent` header its requests propagate. This
+    /// implies `with_trace_context`, so a request without a valid `traceparent` starts a new trace,
+    /// and every response gets a `traceparent` header.
+    pub fn with_trace_correlation_id(mut self) -> RequestIDFairing {
+        self.correlation_id = true;
+        self.correlate_by_trace = true;
+        self.with_trace_context()
+    }
+
+    /// Generate random (version 4) UUIDs as IDs, rather than `u64`s.
+    ///
+    /// Inbound IDs are then only honored if they are UUIDs, with or without hyphens, and IDs
+    /// (inherited ones included) are rendered in the canonical hyphenated lowercase form. This is
+    /// compatible with the UUID-ish IDs Heroku's router and nginx's `$request_id` assign.
+    #[cfg(feature = "uuid")]
+    pub fn with_uuids(mut self) -> RequestIDFairing {
+        self.kind = IdKind::Uuid;
+        self.generator = None;
+        self
+    }
+
+    /// Generate version 7 UUIDs as IDs, rather than `u64`s.
+    ///
+    /// These start with a millisecond timestamp, so IDs sort in the order they were generated (IDs
+    /// generated within the same millisecond are ordered too). Otherwise, this behaves like
+    /// `with_uuids`.
+    #[cfg(feature = "uuid")]
+    pub fn with_v7_uuids(mut self) -> RequestIDFairing {
+        self.kind = IdKind::UuidV7;
+        self.generator = None;
+        self
+    }
+
+    /// Generate 128 random bits as IDs, rather than 64, for IDs which have to stay unique across
+    /// many services over many years. They're rendered as 32 lowercase hex digits, and inbound IDs
+    /// are then only honored if they're 32 hex digits, in either case. Use `u128::from` to get an
+    /// ID's bits; `u64::from` only gives the low 64.
+    pub fn with_128_bit_ids(mut self) -> RequestIDFairing {
+        self.kind = IdKind::Hex128;
+        self.generator = None;
+        self
+    }
+
+    /// Render hex IDs, those of `IdFormat::Hex` and `with_128_bit_ids`, in uppercase, e.g.
+    /// `00000000029AEEBE`, still zero-padded to 16 or 32 digits. Inbound IDs are honored in either
+    /// case, as before. This needs to come after the option choosing hex IDs, which undoes it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the fairing doesn't render IDs in hex, or renders them in groups (see
+    /// `with_grouping`), which are always lowercase.
+    pub fn with_uppercase_hex(mut self) -> RequestIDFairing {
+        self.kind = match self.kind {
+            IdKind::HexNumber => IdKind::UpperHex { digits: 16 },
+            IdKind::Hex128 => IdKind::UpperHex { digits: 32 },
+            kind @ IdKind::UpperHex { .. } => kind,
+            _ => panic!("invalid uppercase hex: only ungrouped hex ids can be uppercase"),
+        };
+        self
+    }
+
+    /// Render hex IDs, those of `IdFormat::Hex` and `with_128_bit_ids`, in groups of `size`
+    /// digits joined by `separator`, which are easier to read and compare, after a `version`
+    /// digit if one is given, as AWS renders its request IDs. For example, with
+    /// `with_grouping(4, '-', Some(1))`, `0x029aeebe` is `1-0000-0000-029a-eebe`.
+    ///
+    /// Inbound IDs are then honored whether they're grouped or not, so `00000000029aeebe` is the
+    /// same ID, but their separators must be in the right places, and their version digit, if
+    /// they have one, must match. This needs to come after the option choosing hex IDs, which
+    /// undoes it. Groups are always lowercase, even after `with_uppercase_hex`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the fairing doesn't render IDs in hex, if `size` is 0 or leaves IDs in a single
+    /// group, if `separator` isn't `-`, `_` or `.` (which inbound IDs may contain by default), or
+    /// if `version` is over 15, so that it isn't a single hex digit.
+    pub fn with_grouping(
+        mut self,
+        size: u8,
+        separator: char,
+        version: Option<u8>,
+    ) -> RequestIDFairing {
+        let digits = match self.kind {
+            IdKind::HexNumber => 16,
+            IdKind::Hex128 => 32,
+            IdKind::GroupedHex(grouping) => grouping.digits,
+            IdKind::UpperHex { digits } => digits,
+            _ => panic!("invalid grouping: only hex ids can be grouped"),
+        };
+        if size == 0 || size >= digits {
+            panic!("invalid grouping size {} for {} digits", size, digits);
+        }
+        if !['-', '_', '.'].contains(&separator) {
+            panic!("invalid grouping separator {:?}", separator);
+        }
+        if version.is_some_and(|version| version > 15) {
+            panic!("invalid grouping version {}", version.unwrap());
+        }
+        self.kind = IdKind::GroupedHex(Grouping {
+            digits,
+            size,
+            separator: separator as u8,
+            version,
+        });
+        self
+    }
+
+    /// Generate [ULIDs](https://github.com/ulid/spec) as IDs, rather than `u64`s.
+    ///
+    /// ULIDs start with a millisecond timestamp, so, like version 7 UUIDs, they sort in the order
+    /// they were generated, but they're rendered as 26 characters of Crockford base32 with no
+    /// hyphens, e.g. `01ARZ3NDEKTSV4RRFFQ69G5FAV`. Inbound IDs are then only honored if they are
+    /// ULIDs, in either case, and are rendered in uppercase.
+    pub fn with_ulids(mut self) -> RequestIDFairing {
+        self.kind = IdKind::Ulid;
+        self.generator = None;
+        self
+    }
+
+    /// Generate [KSUIDs](https://github.com/segmentio/ksuid) as IDs, rather than `u64`s.
+    ///
+    /// KSUIDs are 160 bits long: a timestamp in seconds, then 128 random bits. They're rendered
+    /// as 27 characters of base62, e.g. `0ujtsYcgvSTl8PAuAdqWYSMnLOv`, which sort in the order
+    /// they were generated, to the second. Inbound IDs are then only honored if they are KSUIDs.
+    pub fn with_ksuids(mut self) -> RequestIDFairing {
+        self.kind = IdKind::Ksuid;
+        self.generator = None;
+        self
+    }
+
+    /// Generate random `u64` IDs which fit in 32 bits, rendered as 8 lowercase hex digits, e.g.
+    /// `3fa9c2e1`, which are easier to read and remember than full-width IDs. Inbound IDs are then
+    /// only honored if they're 8 hex digits, in either case.
+    ///
+    /// With only 32 bits, collisions are a real prospect once a service handles tens of thousands
+    /// of requests, so this also turns on `with_collision_checks(3, OnDuplicateId::Warn)`, unless
+    /// collision checks are already configured. Even so, IDs only stay unique among the requests
+    /// in flight: don't use this for IDs which must be unique across a service's logs.
+    pub fn with_short_ids(mut self) -> RequestIDFairing {
+        self.kind = IdKind::ShortHex;
+        self.bits = self.bits.min(32);
+        self.generator = None;
+        if self.collisions.is_none() {
+            self = self.with_collision_checks(3, OnDuplicateId::Warn);
+        }
+        self
+    }
+
+    /// Generate `u64` IDs which say when they were generated: a 42-bit Unix timestamp in
+    /// milliseconds, then 22 random bits. They're still rendered as plain decimal numbers, but
+    /// the `RequestID` guard's `timestamp` reads the time back out, and they sort in the order
+    /// they were generated, to the millisecond. Unlike Snowflake IDs, they need no worker IDs, but
+    /// two requests in the same millisecond have a one in four million chance of sharing an ID.
+    pub fn with_timestamped_ids(mut self) -> RequestIDFairing {
+        self.kind = IdKind::Timestamped;
+        self.generator = None;
+        self
+    }
+
+    /// Generate Snowflake IDs, rather than random `u64`s: a 41-bit timestamp in milliseconds, then
+    /// `worker_id` in 10 bits, then a 12-bit sequence number. Give each instance of a service its
+    /// own worker ID, and their IDs are unique without any coordination; IDs sort in the order
+    /// they were generated, and always fit in an `i64`.
+    ///
+    /// An instance can generate 4096 IDs a millisecond; past that, requests wait for the next
+    /// millisecond. If the clock goes backwards, IDs keep the last timestamp until it catches up.
+    /// IDs are rendered in decimal, and inbound IDs are only honored if they fit in an `i64`. The
+    /// `RequestID` guard's `timestamp_ms`, `worker_id` and `sequence` take IDs apart again.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `worker_id` is greater than `MAX_SNOWFLAKE_WORKER_ID` (1023).
+    pub fn with_snowflakes(mut self, worker_id: u16) -> RequestIDFairing {
+        if worker_id > MAX_SNOWFLAKE_WORKER_ID {
+            panic!("invalid snowflake worker id {}", worker_id);
+        }
+        self.kind = IdKind::Snowflake(worker_id);
+        self.generator = None;
+        self
+    }
+
+    /// Generate [xids](https://github.com/rs/xid) as IDs, rather than `u64`s.
+    ///
+    /// xids are 96 bits long: a timestamp in seconds, a machine ID, the process ID, and a counter
+    /// which starts at a random value. They're rendered as 20 characters of lowercase base32hex,
+    /// e.g. `9m4e2mr0ui3e8a215n4g`, which sort in the order they were generated, to the second,
+    /// and interchange with other xid implementations. Inbound IDs are then only honored if they
+    /// are xids.
+    ///
+    /// The machine ID is a hash of the hostname; see `with_xid_machine_id` to set it instead.
+    pub fn with_xids(self) -> RequestIDFairing {
+        self.with_xid_machine_id(xid::machine_id())
+    }
+
+    /// Like `with_xids`, but with `machine_id` as the machine ID, for hosts whose hostnames
+    /// aren't unique, e.g. containers.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `machine_id` is greater than `MAX_XID_MACHINE_ID` (`0xffffff`).
+    pub fn with_xid_machine_id(mut self, machine_id: u32) -> RequestIDFairing {
+        if machine_id > MAX_XID_MACHINE_ID {
+            panic!("invalid xid machine id {}", machine_id);
+        }
+        self.kind = IdKind::Xid(machine_id);
+        self.generator = None;
+        self
+    }
+
+    /// Generate short, URL-safe [nanoid](https://github.com/ai/nanoid)-style IDs, rather than
+    /// `u64`s: 21 random symbols from `A-Za-z0-9_-`, e.g. `V1StGXR8_Z5jdHi6B-myT`. See
+    /// `with_nanoid_format` to use another alphabet or length.
+    pub fn with_nanoids(self) -> RequestIDFairing {
+        self.with_nanoid_format(DEFAULT_NANOID_ALPHABET, DEFAULT_NANOID_LEN)
+    }
+
+    /// Generate nanoid-style IDs of `len` random symbols from `alphabet`, each equally likely.
+    /// Inbound IDs are then only honored if they're spelled the same way.
+    ///
+    /// Shorter IDs collide sooner: with `n` IDs of `len` symbols from an alphabet of `k`, the
+    /// chance that any two are the same is about `n² / (2 × k^len)`. The default 21 symbols of 64
+    /// hold 126 bits, so a one in a million chance of a collision takes about 10^16 IDs; 10 symbols
+    /// of 64 would take only about 1.5 million.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `alphabet` has fewer than 2 symbols, repeats a symbol, or has any but ASCII
+    /// letters, digits, `-`, `_`, and `.`, or if there are more possible IDs of `len` symbols
+    /// than fit in 128 bits (e.g. more than 21 symbols of the default alphabet), or none.
+    pub fn with_nanoid_format(mut self, alphabet: &'static str, len: u8) -> RequestIDFairing {
+        let unique = alphabet
+            .bytes()
+            .enumerate()
+            .all(|(i, b)| !alphabet.as_bytes()[..i].contains(&b));
+        if alphabet.len() < 2 || !unique || !is_safe_id(alphabet) {
+            panic!("invalid nanoid alphabet {:?}", alphabet);
+        }
+        if len == 0 || !nanoid::fits(alphabet.len(), len) {
+            panic!("invalid nanoid length {} for a {} symbol alphabet", len, alphabet.len());
+        }
+        self.kind = IdKind::Nanoid {
+            alphabet,
+            len,
+        };
+        self.generator = None;
+        self
+    }
+
+    /// Only honor an inbound ID header on requests from one of the given addresses or CIDR ranges
+    /// (e.g. `"10.0.0.0/8"`), such as the load balancers in front of rocket. Requests from anywhere
+    /// else, or whose remote address is unknown, always get a newly generated ID.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of `proxies` is not a valid address or CIDR range.
+    ///
+    /// ```
+    /// use rocket_request_id::RequestIDFairing;
+    ///
+    /// let fairing = RequestIDFairing::default().with_trusted_proxies(&["10.0.0.0/8", "::1"]);
+    /// ```
+    pub fn with_trusted_proxies<I, S>(mut self, proxies: I) -> RequestIDFairing
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let proxies = proxies
+            .into_iter()
+            .map(|proxy| match proxy.as_ref().parse() {
+                Ok(cidr) => cidr,
+                Err(e) => panic!("invalid trusted proxy: {}", e),
+            })
+            .collect();
+        self.trusted_proxies = Some(proxies);
+        self
+    }
+
+    /// Ignore inbound IDs longer than `len` bytes, rather than 128.
+    pub fn with_max_inbound_len(mut self, len: usize) -> RequestIDFairing {
+        self.max_inbound_len = len;
+        self
+    }
+
+    /// Handle inbound IDs which are longer than the maximum length according to `policy`, rather
+    /// than ignoring them. The policy applies before the ID is stored, so the guard, response
+    /// header and logs all see the same ID. The `ParentRequestID` guard still returns the
+    /// upstream's original ID.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `policy` is `Truncate(0)`.
+    pub fn with_oversized_inbound_policy(
+        mut self,
+        policy: OversizedInboundPolicy,
+    ) -> RequestIDFairing {
+        if policy == OversizedInboundPolicy::Truncate(0) {
+            panic!("invalid oversized inbound policy: can't truncate ids to nothing");
+        }
+        self.oversized_inbound_policy = policy;
+        self
+    }
+
+    /// Check that each generated ID isn't already the ID of a request in flight, and generate up
+    /// to `retries` more until one isn't; if they all are, do as `on_duplicate` says. Collisions
+    /// between random `u64`s are vanishingly unlikely, but not with narrow IDs (see `with_bits`),
+    /// small nanoid alphabets, or custom generators.
+    ///
+    /// This costs a lock around the set of IDs in flight, which every request takes twice. Only
+    /// generated IDs are checked: inbound IDs are taken as they are, even if another request has
+    /// the same one.
+    pub fn with_collision_checks(
+        mut self,
+        retries: u32,
+        on_duplicate: OnDuplicateId,
+    ) -> RequestIDFairing {
+        self.collisions = Some((Arc::new(InFlight::default()), retries, on_duplicate));
+        self
+    }
+
+    /// Give requests with the same `Idempotency-Key` header the same ID, if they arrive within
+    /// `ttl` of the first, so that a client's retries of a request share its ID in the logs.
+    ///
+    /// A key is an inbound ID of a sort, so it's only honored under `InboundPolicy::Prefer` and
+    /// `Require`, from trusted proxies (see `with_trusted_proxies`), if it's as valid as an
+    /// inbound ID must be, and only for requests without an inbound ID of their own. Under
+    /// `Require`, a request with a key but no inbound ID gets an ID, too.
+    ///
+    /// The fairing remembers every key it has seen within the last `ttl`, so a long `ttl` costs
+    /// memory on a busy service.
+    pub fn with_idempotency_keys(mut self, ttl: Duration) -> RequestIDFairing {
+        self.idempotency_keys = Some(IdempotencyKeys::new(ttl));
+        self
+    }
+
+    /// Sign the IDs in response headers with a truncated HMAC-SHA256 of them, keyed by `key`,
+    /// e.g. `1234.5bdcc146bf60754e`, so that an ID a client reports can be checked with
+    /// `RequestID::verify` before anyone goes looking for it. Requires the `signing` feature.
+    ///
+    /// Inbound IDs, from headers or the query parameter (see `with_query_param`), are only honored
+    /// if they're signed with the same key, so a fleet of services sharing a key can pass signed
+    /// IDs along while clients can't forge them. Anything else is logged and treated as an
+    /// unparseable inbound ID: the request gets a generated ID, unless the fairing rejects invalid
+    /// IDs (see `with_on_invalid`). Inbound IDs must fit `with_max_inbound_len` with their
+    /// tags, which are 17 characters long.
+    ///
+    /// Only the response header is signed: the `RequestID` guard, logs, and other headers have the
+    /// bare ID. `RequestID::sign` signs one for anywhere else it's needed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` is empty.
+    #[cfg(feature = "signing")]
+    pub fn with_signed_ids(mut self, key: &[u8]) -> RequestIDFairing {
+        if key.is_empty() {
+            panic!("invalid signing key: it's empty");
+        }
+        self.signing_key = Some(key.to_vec());
+        self
+    }
+
+    /// Only give IDs to requests for which `filter` returns `true`, e.g. to leave health checks
+    /// and static assets out of the logs. Other requests get no ID and no response headers, and
+    /// the `RequestID` guard fails for them, so their handlers should take an
+    /// `Option<RequestID>` if they want one at all.
+    ///
+    /// ```
+    /// use rocket_request_id::RequestIDFairing;
+    ///
+    /// let fairing = RequestIDFairing::default()
+    ///     .with_filter(|request| request.uri().path().as_str() != "/health");
+    /// ```
+    pub fn with_filter<F>(mut self, filter: F) -> RequestIDFairing
+    where
+        F: Fn(&Request) -> bool + Send + Sync + 'static,
+    {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Only give IDs to a random `rate` of requests, e.g. 0.1 for about one in ten, to save the
+    /// work for the rest on very busy endpoints. Requests which aren't sampled are treated as
+    /// those `with_filter` filters out: they get no ID and no response headers, even if they
+    /// arrive with an inbound ID.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` isn't between 0 and 1.
+    pub fn with_sampling(mut self, rate: f64) -> RequestIDFairing {
+        if !(0.0..=1.0).contains(&rate) {
+            panic!("invalid sampling rate {}", rate);
+        }
+        self.sampling = rate;
+        self
+    }
+
+    /// Replace the check that inbound IDs only contain safe characters with `validator`, for
+    /// upstreams whose IDs use an unusual format. Inbound IDs for which it returns `false` are
+    /// ignored; the maximum length still applies.
+    ///
+    /// ```
+    /// use rocket_request_id::RequestIDFairing;
+    ///
+    /// let fairing = RequestIDFairing::default()
+    ///     .with_inbound_validator(|id| id.bytes().all(|b| b.is_ascii_digit()));
+    /// ```
+    pub fn with_inbound_validator<F>(mut self, validator: F) -> RequestIDFairing
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.inbound_validator = Some(Box::new(validator));
+        self
+    }
+
+    /// Handle requests whose inbound ID is invalid (it's too long, fails validation, or doesn't
+    /// parse as the kind of ID the fairing generates) according to `action`, rather than ignoring
+    /// the inbound ID. This only applies to requests whose inbound IDs are read at all, e.g. not
+    /// under `InboundPolicy::Ignore` or from untrusted clients.
+    ///
+    /// A fairing can't stop a request from being routed, so with `InvalidIdAction::Reject`, a
+    /// rejected request's `RequestID` guard fails with a 400 Bad Request, which stops any handler
+    /// that takes it from running; whatever the response would have been, the fairing then
+    /// replaces it with a 400 whose body names the header. Handlers with side effects should take
+    /// the guard. The request still gets a newly generated ID for its response header.
+    pub fn with_on_invalid(mut self, action: InvalidIdAction) -> RequestIDFairing {
+        self.on_invalid = action;
+        self
+    }
+
+    /// Set the ID header on responses to requests for `prefix` and the paths under it. Once this
+    /// has been called, responses to requests for any other path don't get the header, unless
+    /// some other call allows them. Prefixes match whole path segments, so `/api` matches
+    /// `/api/users` but not `/apiv2`.
+    ///
+    /// Requests still get IDs regardless of their path; only the response header is affected.
+    ///
+    /// If several prefixes given to this and `skip_header_on` match a path, the longest one wins,
+    /// so more specific rules take precedence:
+    ///
+    /// ```
+    /// use rocket_request_id::RequestIDFairing;
+    ///
+    /// // /api/users gets the header, but /api/internal/stats, /assets/logo.png, and / don't.
+    /// let fairing = RequestIDFairing::default()
+    ///     .emit_header_on("/api")
+    ///     .skip_header_on("/api/internal");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `prefix` doesn't start with a `/`.
+    pub fn emit_header_on<S: Into<Cow<'static, str>>>(self, prefix: S) -> RequestIDFairing {
+        self.with_path_rule(prefix.into(), true)
+    }
+
+    /// Don't set the ID header on responses to requests for `prefix` and the paths under it. See
+    /// `emit_header_on` for how prefixes match.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `prefix` doesn't start with a `/`.
+    pub fn skip_header_on<S: Into<Cow<'static, str>>>(self, prefix: S) -> RequestIDFairing {
+        self.with_path_rule(prefix.into(), false)
+    }
+
+    /// Log each request's method, path and ID when it arrives, and its status and ID when its
+    /// response is ready, at the info level. This is off by default.
+    pub fn with_logging(mut self, logging: bool) -> RequestIDFairing {
+        self.logging = logging;
+        self
+    }
+
+    /// Report how long each request took, from when the fairing saw it arrive to when its
+    /// response was ready, in a `Server-Timing: total;dur=<milliseconds>` header, so that
+    /// browsers' developer tools can show the server's share of a request's latency. Any
+    /// `Server-Timing` metrics the handler reported are kept alongside it.
+    ///
+    /// Like the ID header, this tells clients something about the service's internals, so it's
+    /// off by default.
+    pub fn with_server_timing(mut self) -> RequestIDFairing {
+        self.server_timing = true;
+        self
+    }
+
+    /// Also make each request's ID available to `current_request_id` on the thread the request
+    /// arrives on, until its response is ready, so that e.g. a `log` formatter can add it to every
+    /// line logged while handling the request.
+    ///
+    /// Rocket's handlers are async, so this is best effort: a request which awaits something may
+    /// carry on on another thread, where the ID isn't set, while another request's task runs on
+    /// this one. Code which runs before a request first awaits anything, such as other guards and
+    /// synchronous handlers, sees the right ID. For anything more, use the `RequestSpan` guard,
+    /// with the `tracing` feature.
+    pub fn with_thread_local_id(mut self) -> RequestIDFairing {
+        self.thread_local_id = true;
+        self
+    }
+
+    fn with_path_rule(mut self, prefix: Cow<'static, str>, emit: bool) -> RequestIDFairing {
+        if !prefix.starts_with('/') {
+            panic!("invalid path prefix {:?}: it must start with /", prefix);
+        }
+        self.header_paths
+            .get_or_insert_with(Vec::new)
+            .push(PathRule {
+                prefix,
+                emit,
+            });
+        self
+    }
+
+    // emits_header_on reports whether the response to a request for path gets the ID header.
+    fn emits_header_on(&self, path: &str) -> bool {
+        let rules = match self.header_paths {
+            Some(ref rules) => rules,
+            None => return true,
+        };
+        // The longest matching prefix wins; skipping wins a tie.
+        let matched = rules
+            .iter()
+            .filter(|rule| rule.matches(path))
+            .max_by_key(|rule| (rule.prefix.trim_end_matches('/').len(), !rule.emit));
+        match matched {
+            Some(rule) => rule.emit,
+            // Without a rule for the path, emit it unless the header is restricted to some paths.
+            None => !rules.iter().any(|rule| rule.emit),
+        }
+    }
+
+    // filters reports whether the fairing's filter lets a request have an ID.
+    fn filters(&self, request: &Request) -> bool {
+        match self.filter {
+            Some(ref filter) => filter(request),
+            None => true,
+        }
+    }
+
+    // validates runs the configured inbound ID check, which is is_safe_id unless replaced.
+    fn validates(&self, id: &str) -> bool {
+        match self.inbound_validator {
+            Some(ref validator) => validator(id),
+            None => is_safe_id(id),
+        }
+    }
+
+    // trusts reports whether request's client may assign it an ID.
+    fn trusts(&self, request: &Request) -> bool {
+        match self.trusted_proxies {
+            None => true,
+            Some(ref proxies) => match request.remote() {
+                Some(remote) => proxies.iter().any(|proxy| proxy.contains(remote.ip())),
+                None => false,
+            },
+        }
+    }
+
+    // read_inbound finds the ID an upstream assigned to this request, if it sent a valid one.
+    fn read_inbound(&self, request: &Request) -> Inbound {
+        let mut inbound = Inbound::default();
+        if !self.trusts(request) {
+            return inbound;
+        }
+
+        let names = match self.inbound_headers {
+            Some(ref names) => &names[..],
+            None => slice::from_ref(&self.header_name),
+        };
+        for name in names {
+            let header = match request.headers().get_one(name) {
+                Some(header) => header,
+                None => continue,
+            };
+            let id = match self.apply_oversized_inbound_policy(header) {
+                Some(Ok(id)) if self.is_valid_inbound(id) => match self.unsign(id) {
+                    Some(id) => self
+                        .kind
+                        .parse(self.strip_prefix(id))
+                        .filter(|&id| !self.is_reserved(id)),
+                    None => {
+                        inbound.invalid = inbound.invalid.or_else(|| Some(name.clone()));
+                        continue;
+                    }
+                },
+                Some(Ok(_)) | None => {
+                    inbound.invalid = inbound.invalid.or_else(|| Some(name.clone()));
+                    continue;
+                }
+                // A hash of an oversized ID can't be verified.
+                Some(Err(_)) if self.signs_ids() => {
+                    warn!("ignoring unsigned inbound request id {}", loggable(header));
+                    None
+                }
+                Some(Err(hash)) => {
+                    Some(self.kind.id_from_hash(hash)).filter(|&id| !self.is_reserved(id))
+                }
+            };
+            if inbound.raw.is_none() {
+                inbound.raw = Some(header.to_string());
+            }
+            match id {
+                Some(id) => {
+                    inbound.id = Some((id, name.clone()));
+                    break;
+                }
+                None => {
+                    debug!("ignoring unparseable inbound request id {:?}", header);
+                    inbound.invalid = inbound.invalid.or_else(|| Some(name.clone()));
+                }
+            }
+        }
+        inbound
+    }
+
+    // query_param_id returns the ID a request inherits from the fairing's query parameter, if it
+    // has one and a valid ID in it.
+    fn query_param_id(&self, request: &Request) -> Option<((u32, u128), RequestIDSource)> {
+        let name = self.query_param.as_ref()?;
+        if !self.trusts(request) {
+            return None;
+        }
+        let (_, value) = request.uri().query()?.segments().find(|&(key, _)| key == &name[..])?;
+        if !self.is_valid_inbound(value) {
+            return None;
+        }
+        let id = self
+            .kind
+            .parse(self.strip_prefix(self.unsign(value)?))
+            .filter(|&id| !self.is_reserved(id));
+        if id.is_none() {
+            debug!("ignoring unparseable inbound request id {:?}", value);
+        }
+        Some((id?, RequestIDSource::QueryParam(name.clone())))
+    }
+
+    // apply_oversized_inbound_policy shortens header if it's too long and the policy allows that.
+    // It returns the header or its prefix to use as an ID, or the hash to derive the ID from, or
+    // None if the header should be ignored.
+    fn apply_oversized_inbound_policy<'h>(&self, header: &'h str) -> Option<Result<&'h str, u128>> {
+        if header.len() <= self.max_inbound_len {
+            return Some(Ok(header));
+        }
+        if self.oversized_inbound_policy == OversizedInboundPolicy::Reject {
+            warn!("ignoring oversized inbound request id {}", loggable(header));
+            return None;
+        }
+        if !header.bytes().all(|b| b > 0x20 && b < 0x7f) {
+            warn!("ignoring invalid inbound request id {}", loggable(header));
+            return None;
+        }
+        match self.oversized_inbound_policy {
+            OversizedInboundPolicy::Truncate(len) => Some(Ok(&header[..len.min(header.len())])),
+            OversizedInboundPolicy::Hash => Some(Err(fnv1a(header))),
+            OversizedInboundPolicy::Reject => unreachable!(),
+        }
+    }
+
+    // signs_ids reports whether the fairing signs IDs, with `with_signed_ids`.
+    fn signs_ids(&self) -> bool {
+        #[cfg(feature = "signing")]
+        {
+            self.signing_key.is_some()
+        }
+        #[cfg(not(feature = "signing"))]
+        {
+            false
+        }
+    }
+
+    // unsign verifies an inbound ID's tag and removes it, if the fairing signs IDs. It returns None
+    // if the ID isn't signed with the fairing's key.
+    fn unsign<'h>(&self, id: &'h str) -> Option<&'h str> {
+        #[cfg(feature = "signing")]
+        {
+            if let Some(ref key) = self.signing_key {
+                return match signing::verify(id, key) {
+                    Ok(id) => Some(id),
+                    Err(e) => {
+                        warn!("ignoring inbound request id {}: {}", loggable(id), e);
+                        None
+                    }
+                };
+            }
+        }
+        Some(id)
+    }
+
+    // header_value renders id for the fairing's response header, signed if the fairing signs IDs.
+    fn header_value(&self, id: RequestID) -> String {
+        #[cfg(feature = "signing")]
+        {
+            if let Some(ref key) = self.signing_key {
+                return id.sign(key);
+            }
+        }
+        id.to_string()
+    }
+
+    // strip_prefix removes the fairing's prefix from an inbound ID, if it has it.
+    fn strip_prefix<'h>(&self, id: &'h str) -> &'h str {
+        id.strip_prefix(self.prefix).unwrap_or(id)
+    }
+
+    fn is_valid_inbound(&self, header: &str) -> bool {
+        if header.len() > self.max_inbound_len || !self.validates(header) {
+            warn!("ignoring invalid inbound request id {}", loggable(header));
+            return false;
+        }
+        true
+    }
+
+    // b3_request_id returns the ID a request inherits from its X-B3-TraceId header, if the fairing
+    // uses B3 trace IDs as request IDs and the request has a valid one.
+    fn b3_request_id(&self, request: &Request) -> Option<((u32, u128), Cow<'static, str>)> {
+        if !self.b3_request_ids || !self.trusts(request) {
+            return None;
+        }
+        let (trace_id, _) = b3::trace_id(request.headers().get_one(X_B3_TRACE_ID_HEADER)?)?;
+        let id = Some(self.kind.id_from_hash(trace_id)).filter(|&id| !self.is_reserved(id))?;
+        Some((id, Cow::Borrowed(X_B3_TRACE_ID_HEADER)))
+    }
+
+    // idempotent_id returns the ID a request shares with earlier requests with the same
+    // Idempotency-Key header, if the fairing maps keys to IDs and the request has a valid key.
+    fn idempotent_id(&self, request: &Request) -> Option<((u32, u128), Cow<'static, str>)> {
+        let keys = self.idempotency_keys.as_ref()?;
+        if !self.trusts(request) {
+            return None;
+        }
+        let key = request.headers().get_one(IDEMPOTENCY_KEY_HEADER)?;
+        if !self.is_valid_inbound(key) {
+            return None;
+        }
+        let id = keys.id_for(key, || self.generate(request));
+        Some((id, Cow::Borrowed(IDEMPOTENCY_KEY_HEADER)))
+    }
+
+    // generate allocates an ID for a request which didn't inherit one, which isn't reserved.
+    fn generate(&self, request: &Request) -> (u32, u128) {
+        for _ in 0..MAX_RESERVED_RETRIES {
+            let id = self.generate_any(request);
+            if !self.is_reserved(id) {
+                return id;
+            }
+            debug!("generated a reserved request id; generating another");
+        }
+        panic!("generated {} reserved request ids in a row", MAX_RESERVED_RETRIES);
+    }
+
+    // generate_any allocates an ID for a request which didn't inherit one.
+    fn generate_any(&self, request: &Request) -> (u32, u128) {
+        match self.generator {
+            Some(ref generator) => (0, self.mask(generator.generate(request) as u128)),
+            None => {
+                let (high, id) = self.rngs().with(|rng| self.kind.generate(rng));
+                (high, self.mask(id))
+            }
+        }
+    }
+
+    // rngs returns the fairing's generators, seeding them if it hasn't been attached yet.
+    fn rngs(&self) -> &RngPool {
+        self.rngs.get_or_init(RngPool::new)
+    }
+
+    // claim checks that a generated ID isn't already in flight, if the fairing checks for
+    // collisions, and generates another if it is, and returns the ID and its claim.
+    fn claim(&self, request: &Request, mut id: (u32, u128)) -> ((u32, u128), Option<Claim>) {
+        let (in_flight, retries, on_duplicate) = match self.collisions {
+            Some((ref in_flight, retries, on_duplicate)) => (in_flight, retries, on_duplicate),
+            None => return (id, None),
+        };
+        for _ in 0..retries {
+            match InFlight::try_claim(in_flight, id) {
+                Some(claim) => return (id, Some(claim)),
+                None => {
+                    debug!("generated request id is already in flight; generating another");
+                    id = self.generate(request);
+                }
+            }
+        }
+        if let Some(claim) = InFlight::try_claim(in_flight, id) {
+            return (id, Some(claim));
+        }
+        match on_duplicate {
+            OnDuplicateId::Warn => {
+                warn!("generated a request id which is already in flight, {} times", retries + 1);
+                (id, Some(InFlight::claim(in_flight, id)))
+            }
+            OnDuplicateId::Panic => panic!(
+                "generated a request id which is already in flight, {} times",
+                retries + 1
+            ),
+        }
+    }
+
+    // is_reserved reports whether an ID is one no request may have.
+    fn is_reserved(&self, (high, id): (u32, u128)) -> bool {
+        self.kind.is_u64()
+            && high == 0
+            && self.reserved.iter().any(|&reserved| reserved as u128 == id)
+    }
+
+    // mask appends the fairing's instance ID to a generated u64 ID and truncates it to the
+    // fairing's bit width, unless it's one of a kind with a layout of its own.
+    fn mask(&self, id: u128) -> u128 {
+        match self.kind {
+            kind if kind.is_formatted() => {
+                let id = match self.instance {
+                    Some((instance, bits)) => (id as u64) << bits | instance,
+                    None => id as u64,
+                };
+                let bits = match kind {
+                    IdKind::ShortHex => self.bits.min(32),
+                    _ => self.bits,
+                };
+                match bits {
+                    64 => id as u128,
+                    bits => (id & ((1 << bits) - 1)) as u128,
+                }
+            }
+            _ => id,
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl rocket::fairing::Fairing for RequestIDFairing {
+    fn info(&self) -> rocket::fairing::Info {
+        rocket::fairing::Info {
+            kind: rocket::fairing::Kind::Ignite
+                | rocket::fairing::Kind::Request
+                | rocket::fairing::Kind::Response,
+            name: "request id",
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> rocket::fairing::Result {
+        // Seed the generators now, rather than on some unlucky request.
+        self.rngs();
+        Ok((self.mark_attached)(rocket))
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        let started = Instant::now();
+        self.with_slot(request, |slot| slot.attached = true);
+        if self.thread_local_id {
+            CURRENT_REQUEST_ID.with(|current| current.set(None));
+        }
+        if !self.filters(request) {
+            debug!("request is filtered out; not assigning a request id");
+            return;
+        }
+        if self.sampling < 1.0 && !self.rngs().with(|rng| rng.gen_bool(self.sampling)) {
+            debug!("request isn't sampled; not assigning a request id");
+            return;
+        }
+        let inbound = match self.inbound_policy {
+            InboundPolicy::Ignore => Inbound::default(),
+            _ => self.read_inbound(request),
+        };
+        // Trace headers are inbound IDs of a sort, so the inbound policy applies to them, too.
+        let continue_trace = self.inbound_policy != InboundPolicy::Ignore && self.trusts(request);
+        let trace = if self.trace_context {
+            let header = if continue_trace {
+                request.headers().get_one(TRACEPARENT_HEADER)
+            } else {
+                None
+            };
+            let tracestate = request.headers().get(TRACESTATE_HEADER);
+            Some(TraceParent::for_request(header, tracestate))
+        } else {
+            None
+        };
+        let b3 = if self.b3_single || self.b3_multi {
+            let header = if continue_trace && self.b3_single {
+                request.headers().get_one(B3_HEADER)
+            } else {
+                None
+            };
+            match header {
+                Some(header) => Some(B3::for_request(Some(header))),
+                None if continue_trace && self.b3_multi => {
+                    Some(B3::for_multi_headers(request.headers()))
+                }
+                None => Some(B3::for_request(None)),
+            }
+        } else {
+            None
+        };
+        let amzn = if self.amzn_trace_id {
+            let header = if continue_trace {
+                request.headers().get_one(AMZN_TRACE_ID_HEADER)
+            } else {
+                None
+            };
+            Some(AmznTraceId::for_request(header))
+        } else {
+            None
+        };
+        let rejected = match self.on_invalid {
+            InvalidIdAction::Reject => inbound.invalid,
+            InvalidIdAction::Ignore => None,
+        };
+        let inherited = match self.inbound_policy {
+            _ if rejected.is_some() => None,
+            InboundPolicy::Prefer | InboundPolicy::Require => inbound
+                .id
+                .map(|(id, header)| (id, RequestIDSource::Header(header)))
+                .or_else(|| self.query_param_id(request))
+                .or_else(|| {
+                    self.b3_request_id(request)
+                        .or_else(|| self.idempotent_id(request))
+                        .map(|(id, header)| (id, RequestIDSource::Header(header)))
+                }),
+            _ => None,
+        };
+        let ((high, id), source) = match inherited {
+            Some((id, source)) => (id, source),
+            // A rejected request still gets an ID, so that its response can be traced.
+            None if self.inbound_policy == InboundPolicy::Require && rejected.is_none() => {
+                debug!("request has no usable inbound request id; not assigning one");
+                return;
+            }
+            None => (self.generate(request), RequestIDSource::Generated),
+        };
+        let ((high, id), in_flight) = match source {
+            RequestIDSource::Generated => self.claim(request, (high, id)),
+            RequestIDSource::Header(_) | RequestIDSource::QueryParam(_) => ((high, id), None),
+        };
+        let id = RequestID {
+            id,
+            high,
+            kind: self.kind,
+            prefix: self.prefix,
+            namespace: PhantomData,
+        };
+        if source != RequestIDSource::Generated {
+            debug!("using inbound request id {} {}", id, source);
+        }
+        if self.logging {
+            info!(
+                "request {} ({}): {} {}",
+                id,
+                source,
+                request.method(),
+                request.uri().path()
+            );
+        }
+        let session = self.session_cookie.as_ref().map(|cookie| {
+            let presented = request
+                .cookies()
+                .get(cookie.name())
+                .map(|cookie| cookie.value().to_string());
+            match presented {
+                Some(value) => {
+                    if !value.is_empty()
+                        && session::is_cookie_value(&value)
+                        && self.is_valid_inbound(&value)
+                    {
+                        session::correlation_id(value)
+                    } else {
+                        session::correlation_id(id.to_string())
+                    }
+                }
+                None => session::correlation_id(id.to_string()),
+            }
+        });
+        let causation = if self.causation_chain {
+            let header = if continue_trace {
+                request.headers().get_one(CAUSATION_ID_HEADER)
+            } else {
+                None
+            };
+            // A parent the request adopted as its own ID didn't cause it.
+            let parent = match source {
+                RequestIDSource::Generated => inbound.raw.as_ref().map(|parent| &parent[..]),
+                RequestIDSource::Header(_) | RequestIDSource::QueryParam(_) => None,
+            };
+            Some(causation::for_request(
+                header,
+                parent,
+                id.to_string(),
+                self.max_causation_depth,
+                |cause| cause.len() <= self.max_inbound_len && self.validates(cause),
+            ))
+        } else {
+            None
+        };
+        let correlation = if self.correlation_id {
+            let header = if continue_trace {
+                request.headers().get_one(CORRELATION_ID_HEADER)
+            } else {
+                None
+            };
+            let id = match (header, trace.as_ref()) {
+                (Some(header), _) if !header.is_empty() && self.is_valid_inbound(header) => {
+                    header.to_string()
+                }
+                (_, Some(trace)) if self.correlate_by_trace => format!("{:032x}", trace.trace_id()),
+                _ => id.to_string(),
+            };
+            Some(correlation::correlation_id(id))
+        } else {
+            None
+        };
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!("request", request_id = %id);
+        #[cfg(feature = "opentelemetry")]
+        let otel_span = otel::start(request, id, trace.as_ref());
+        let tracked = Tracked {
+            id,
+            source,
+            started,
+            parent: inbound.raw,
+            trace,
+            b3,
+            amzn,
+            session,
+            causation,
+            correlation,
+            suppress_headers: false,
+            rejected,
+            in_flight,
+            #[cfg(feature = "tracing")]
+            span,
+            #[cfg(feature = "opentelemetry")]
+            otel_span: Some(otel_span),
+        };
+        self.with_slot(request, |slot| {
+            if slot.tracked.replace(tracked).is_none() {
+                ACTIVE_REQUESTS.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+        if self.thread_local_id {
+            CURRENT_REQUEST_ID.with(|current| current.set(Some(id)));
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let tracked = self.with_slot(request, |slot| {
+            let tracked = slot.tracked.take();
+            if slot.attached && tracked.is_some() {
+                ACTIVE_REQUESTS.fetch_sub(1, Ordering::Relaxed);
+            }
+            tracked
+        });
+        if self.thread_local_id {
+            // Leave the ID of any other request which has since arrived on this thread alone.
+            let id = tracked.as_ref().map(|tracked| tracked.id);
+            CURRENT_REQUEST_ID.with(|current| {
+                if id.is_some() && current.get() == id {
+                    current.set(None);
+                }
+            });
+        }
+
+        if let Some(Tracked {
+            id,
+            started,
+            trace,
+            b3,
+            amzn,
+            session,
+            correlation,
+            suppress_headers,
+            rejected,
+            #[cfg(feature = "opentelemetry")]
+            otel_span,
+            ..
+        }) = tracked
+        {
+            let elapsed = started.elapsed();
+            #[cfg(feature = "opentelemetry")]
+            if let Some(span) = otel_span {
+                let status = match rejected {
+                    Some(_) => Status::BadRequest,
+                    None => response.status(),
+                };
+                otel::end(span, status);
+            }
+            if let Some(ref header) = rejected {
+                let body = format!("invalid {} header\n", header);
+                *response = Response::build()
+                    .status(Status::BadRequest)
+                    .header(ContentType::Plain)
+                    .sized_body(body.len(), Cursor::new(body))
+                    .finalize();
+            }
+            if self.logging {
+                info!("request {}: {} in {:?}", id, response.status(), elapsed);
+            }
+            if suppress_headers && rejected.is_none() {
+                debug!("not setting request id headers: the handler suppressed them");
+                return;
+            }
+            if self.server_timing {
+                let timing = format!("total;dur={:.3}", elapsed.as_secs_f64() * 1000.0);
+                response.adjoin_header(Header::new(SERVER_TIMING_HEADER, timing));
+            }
+            if !response.headers().contains(&self.header_name)
+                && self.emits_header_on(request.uri().path().as_str())
+            {
+                response.set_header(Header::new(self.header_name.clone(), self.header_value(id)));
+            }
+            if let Some(trace) = trace {
+                if !response.headers().contains(TRACEPARENT_HEADER) {
+                    for header in trace.headers() {
+                        response.set_header(header);
+                    }
+                }
+            }
+            if let Some(b3) = b3 {
+                if self.b3_single && !response.headers().contains(B3_HEADER) {
+                    response.set_header(b3.header());
+                }
+                if self.b3_multi && !response.headers().contains(X_B3_TRACE_ID_HEADER) {
+                    for header in b3.multi_headers() {
+                        response.set_header(header);
+                    }
+                }
+            }
+            if let Some(amzn) = amzn {
+                if !response.headers().contains(AMZN_TRACE_ID_HEADER) {
+                    response.set_header(amzn.header());
+                }
+            }
+            if let Some(correlation) = correlation {
+                if !response.headers().contains(CORRELATION_ID_HEADER) {
+                    response.set_header(correlation.header());
+                }
+            }
+            if let (Some(session), Some(cookie)) = (session, self.session_cookie.as_ref()) {
+                response.adjoin_header(session::set_cookie(cookie, session.as_str()));
+            }
+        }
+    }
+}
+
+// is_safe_id is the default check for inbound IDs: they may only contain characters which can't
+// break up or disguise a log line.
+fn is_safe_id(id: &str) -> bool {
+    id.bytes()
+        .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || b == b'.')
+}
+
+// fnv1a hashes value with 128-bit FNV-1a, which is simple and, unlike std's hashers, guaranteed to
+// give the same hash in every build, so that hashed IDs are stable across deploys.
+fn fnv1a(value: &str) -> u128 {
+    const OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013b;
+
+    value.bytes().fold(OFFSET_BASIS, |hash, b| {
+        (hash ^ b as u128).wrapping_mul(PRIME)
+    })
+}
+
+// hostname returns this machine's hostname, if it can be found.
+fn hostname() -> Option<String> {
+    ["/proc/sys/kernel/hostname", "/etc/hostname"]
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .chain(std::env::var("HOSTNAME").ok())
+        .chain(std::env::var("COMPUTERNAME").ok())
+        .map(|hostname| hostname.trim().to_string())
+        .find(|hostname| !hostname.is_empty())
+}
+
+// loggable shortens and escapes an untrusted value so that it can be safely logged.
+fn loggable(value: &str) -> String {
+    const MAX_LOGGED_CHARS: usize = 32;
+
+    let mut logged = format!("{:?}", value.chars().take(MAX_LOGGED_CHARS).collect::<String>());
+    if value.chars().count() > MAX_LOGGED_CHARS {
+        logged.push_str("...");
+    }
+    logged
+}
+
+// is_valid_header_name checks that name is a non-empty RFC 7230 token, which is what header field
+// names must be.
+fn is_valid_header_name(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|b| match b {
+        _ if b.is_ascii_alphanumeric() => true,
+        b'!' | b'#' | b'$' | b'%' | b'&' | b'\'' | b'*' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`'
+        | b'|' | b'~' => true,
+        _ => false,
+    })
+}
+
+// IdKind is the sort of ID a fairing generates, which determines how IDs are parsed and rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum IdKind {
+    // A random u64, rendered in decimal.
+    Number,
+    // A random u64, rendered as 16 lowercase hex digits.
+    HexNumber,
+    // A random u64, rendered in base62.
+    Base62Number,
+    // A random u64, rendered in base62 with a Luhn mod 62 check character.
+    Base62Checked,
+    // A random u64, rendered in Crockford's base32.
+    CrockfordNumber,
+    // A random u64 which fits in 32 bits, rendered as 8 lowercase hex digits.
+    ShortHex,
+    // A random (version 4) UUID, rendered in its canonical hyphenated form.
+    #[cfg(feature = "uuid")]
+    Uuid,
+    // A time-ordered (version 7) UUID, rendered in its canonical hyphenated form.
+    #[cfg(feature = "uuid")]
+    UuidV7,
+    // 128 random bits, rendered as 32 hex digits.
+    Hex128,
+    // A random u64 or 128 random bits, rendered as 16 or 32 uppercase hex digits.
+    UpperHex { digits: u8 },
+    // A random u64 or 128 random bits, rendered as hex digits in groups.
+    GroupedHex(Grouping),
+    // A ULID, rendered in Crockford base32.
+    Ulid,
+    // A KSUID, rendered in base62. These are 160 bits long.
+    Ksuid,
+    // A Snowflake ID generated by the given worker, rendered in decimal.
+    Snowflake(u16),
+    // A u64 of a millisecond timestamp and random bits, rendered in decimal.
+    Timestamped,
+    // An xid generated by the given machine, rendered in base32hex. These are 96 bits long.
+    Xid(u32),
+    // A nanoid-style ID of len symbols from alphabet.
+    Nanoid { alphabet: &'static str, len: u8 },
+}
+
+impl IdKind {
+    // generate returns a new ID of this kind, as the bits above the low 128 (which only KSUIDs
+    // have), and the low 128.
+    // Random bits come from rng, one of the fairing's streams.
+    fn generate<R: Rng>(&self, rng: &mut R) -> (u32, u128) {
+        match *self {
+            IdKind::Number
+            | IdKind::HexNumber
+            | IdKind::Base62Number
+            | IdKind::Base62Checked
+            | IdKind::CrockfordNumber => (0, rng.gen::<u64>() as u128),
+            IdKind::ShortHex => (0, rng.gen::<u32>() as u128),
+            #[cfg(feature = "uuid")]
+            IdKind::Uuid => (0, uuid::Builder::from_random_bytes(rng.gen()).into_uuid().as_u128()),
+            #[cfg(feature = "uuid")]
+            IdKind::UuidV7 => (0, uuid_v7::generate(rng)),
+            IdKind::Hex128 => (0, rng.gen()),
+            IdKind::GroupedHex(Grouping { digits: 16, .. }) => {
+                (0, rng.gen::<u64>() as u128)
+            }
+            IdKind::GroupedHex(_) => (0, rng.gen()),
+            IdKind::UpperHex { digits: 16 } => (0, rng.gen::<u64>() as u128),
+            IdKind::UpperHex { .. } => (0, rng.gen()),
+            IdKind::Ulid => (0, ulid::generate(rng)),
+            IdKind::Ksuid => ksuid::generate(rng),
+            IdKind::Snowflake(worker_id) => (0, snowflake::generate(worker_id) as u128),
+            IdKind::Timestamped => (0, timestamped::generate() as u128),
+            IdKind::Xid(machine_id) => (0, xid::generate(machine_id)),
+            IdKind::Nanoid { alphabet, len } => (0, nanoid::generate(alphabet, len, rng)),
+        }
+    }
+
+    // id_from_hash derives an ID of this kind from a hash, or any other 128 bits; u64 IDs keep the
+    // low 64.
+    fn id_from_hash(&self, hash: u128) -> (u32, u128) {
+        match *self {
+            IdKind::Number
+            | IdKind::HexNumber
+            | IdKind::Base62Number
+            | IdKind::Base62Checked
+            | IdKind::CrockfordNumber => (0, hash as u64 as u128),
+            IdKind::ShortHex => (0, hash as u32 as u128),
+            #[cfg(feature = "uuid")]
+            IdKind::Uuid | IdKind::UuidV7 => (0, hash),
+            IdKind::Hex128 | IdKind::Ulid | IdKind::Ksuid => (0, hash),
+            IdKind::GroupedHex(Grouping { digits: 16, .. }) => (0, hash as u64 as u128),
+            IdKind::GroupedHex(_) => (0, hash),
+            IdKind::UpperHex { digits: 16 } => (0, hash as u64 as u128),
+            IdKind::UpperHex { .. } => (0, hash),
+            IdKind::Snowflake(_) => (0, (hash as u64 & snowflake::MAX_ID) as u128),
+            IdKind::Timestamped => (0, hash as u64 as u128),
+            IdKind::Xid(_) => (0, hash & ((1 << 96) - 1)),
+            IdKind::Nanoid { alphabet, len } => {
+                (0, hash % (alphabet.len() as u128).pow(len as u32))
+            }
+        }
+    }
+
+    fn parse(&self, s: &str) -> Option<(u32, u128)> {
+        let id = match *self {
+            IdKind::Number => s.parse::<u64>().ok().map(|id| id as u128),
+            IdKind::HexNumber => hex::decode(s, 16),
+            IdKind::Base62Number => base62::decode(s).filter(|&id| id <= u64::MAX as u128),
+            IdKind::Base62Checked => RequestID::parse_checked(s).ok().map(|id| id.id),
+            IdKind::CrockfordNumber => crockford::decode(s).map(|id| id as u128),
+            IdKind::ShortHex => hex::decode(s, 8),
+            #[cfg(feature = "uuid")]
+            IdKind::Uuid | IdKind::UuidV7 => Uuid::parse_str(s).ok().map(|uuid| uuid.as_u128()),
+            IdKind::Hex128 => hex::decode(s, 32),
+            IdKind::GroupedHex(grouping) => grouping.decode(s),
+            IdKind::UpperHex { digits } => hex::decode(s, digits as usize),
+            IdKind::Ulid => ulid::decode(s),
+            IdKind::Ksuid => return ksuid::decode(s),
+            IdKind::Timestamped => s.parse::<u64>().ok().map(|id| id as u128),
+            IdKind::Snowflake(_) => s
+                .parse::<u64>()
+                .ok()
+                .filter(|&id| id <= snowflake::MAX_ID)
+                .map(|id| id as u128),
+            IdKind::Xid(_) => xid::decode(s),
+            IdKind::Nanoid { alphabet, len } => nanoid::decode(alphabet, len, s),
+        };
+        id.map(|id| (0, id))
+    }
+
+    // is_formatted reports whether IDs of this kind are plain u64s, which the fairing narrows
+    // and adds instance IDs to, rather than IDs with a layout of their own.
+    fn is_formatted(&self) -> bool {
+        match *self {
+            IdKind::Number | IdKind::HexNumber | IdKind::Base62Number | IdKind::ShortHex => true,
+            IdKind::Base62Checked | IdKind::CrockfordNumber => true,
+            IdKind::GroupedHex(grouping) => grouping.digits == 16,
+            IdKind::UpperHex { digits } => digits == 16,
+            _ => false,
+        }
+    }
+
+    // is_u64 reports whether IDs of this kind are u64s.
+    fn is_u64(&self) -> bool {
+        match *self {
+            IdKind::Number | IdKind::HexNumber | IdKind::Base62Number | IdKind::ShortHex => true,
+            IdKind::Base62Checked | IdKind::CrockfordNumber => true,
+            IdKind::Snowflake(_) | IdKind::Timestamped => true,
+            #[cfg(feature = "uuid")]
+            IdKind::Uuid | IdKind::UuidV7 => false,
+            IdKind::Hex128 | IdKind::Ulid | IdKind::Ksuid => false,
+            IdKind::GroupedHex(grouping) => grouping.digits == 16,
+            IdKind::UpperHex { digits } => digits == 16,
+            IdKind::Xid(_) | IdKind::Nanoid { .. } => false,
+        }
+    }
+}
+
+///
+/// A unique ID for a given rocket request.
+/// This ID should be retrieved via its `FromRequest` implementation; that is to say, add an
+/// argument of the type `RequestID` to a rocket handler. That argument can then be rendered with
+/// `Display`, or converted with `as_u64` (or `u64::from`) to access the ID.
+///
+/// If multiple parameters of this type are requested, each will have the same ID.
+/// This property holds even if they are instantiated by other request guards.
+///
+/// A request has one `RequestID` per namespace. `RequestID` itself is in the default namespace;
+/// `RequestID<N>` gets the ID a fairing configured with `RequestIDFairing::in_namespace::<N>`
+/// gave the request, which is independent of the others. For IDs that outlive a request, see
+/// `CorrelationID`, which follows a request across services, and `SessionCorrelationID`, which
+/// follows a browser's requests.
+///
+/// Without a `RequestIDFairing`, the guard gives each request a random `u64` ID the first time
+/// it's used. The fairing is needed for anything more: inheriting inbound IDs, the response
+/// header, and the other guards.
+///
+/// The guard only fails if the fairing is attached and won't give the request an ID (see
+/// `InboundPolicy::Require`), or is rejecting it (see `InvalidIdAction::Reject`). Take an
+/// `Option<RequestID>` to handle requests without an ID anyway, as `None`.
+///
+/// An ID is a `u64`, unless the fairing was configured to generate another kind of ID, such as
+/// UUIDs (see `RequestIDFairing::with_uuids`, available with the `uuid` feature) or ULIDs (see
+/// `with_ulids`). Either way, its `Display` implementation renders it the same way as the
+/// `X-Request-ID` response header. Converting a longer ID to a `u64` truncates it to its low 64
+/// bits, so use `u128::from` or `as_uuid` for those instead.
+///
+/// IDs are `Copy` and `Hash`, so they can key a `HashMap` of per-request data. Two IDs are equal
+/// if they're the same kind of ID with the same value; the fairing's prefix doesn't matter.
+///
+/// For example, the following is a typical usage:
+/// ```
+/// # #[macro_use] extern crate rocket;
+/// #[get("/")]
+/// fn test_req_id(id: rocket_request_id::RequestID) -> String {
+///     format!("Hello, your request had ID {}", id)
+/// }
+/// # fn main() {}
+/// ```
+pub struct RequestID<N = DefaultNamespace> {
+    id: u128,
+    // The bits above id. Only KSUIDs, which are 160 bits long, use these, for their timestamp.
+    high: u32,
+    kind: IdKind,
+    prefix: &'static str,
+    namespace: PhantomData<fn() -> N>,
+}
+
+impl<N> RequestID<N> {
+    // in_namespace returns the same ID, in namespace M.
+    fn in_namespace<M>(self) -> RequestID<M> {
+        RequestID {
+            id: self.id,
+            high: self.high,
+            kind: self.kind,
+            prefix: self.prefix,
+            namespace: PhantomData,
+        }
+    }
+}
+
+// These are implemented by hand, since deriving them would require namespaces to implement them.
+impl<N> fmt::Debug for RequestID<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RequestID")
+            .field("id", &self.id)
+            .field("high", &self.high)
+            .field("kind", &self.kind)
+            .field("prefix", &self.prefix)
+            .finish()
+    }
+}
+
+impl<N> Clone for RequestID<N> {
+    fn clone(&self) -> RequestID<N> {
+        *self
+    }
+}
+
+impl<N> Copy for RequestID<N> {}
+
+/// IDs are compared by their value alone; the fairing's prefix isn't part of an ID's identity.
+impl<N> PartialEq for RequestID<N> {
+    fn eq(&self, other: &RequestID<N>) -> bool {
+        (self.id, self.high, self.kind) == (other.id, other.high, other.kind)
+    }
+}
+
+impl<N> Eq for RequestID<N> {}
+
+impl<N> Hash for RequestID<N> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.id, self.high, self.kind).hash(state)
+    }
+}
+
+/// A marker type for a namespace of request IDs, which are kept apart from those in other
+/// namespaces. See `RequestIDFairing::in_namespace`.
+pub trait Namespace: Send + Sync + 'static {}
+
+/// The namespace of plain `RequestID`s, and of fairings which aren't given another one.
+#[derive(Debug)]
+pub enum DefaultNamespace {}
+
+impl Namespace for DefaultNamespace {}
+
+#[cfg(feature = "uuid")]
+impl RequestID {
+    /// Returns the ID as a UUID. This is only a valid version 4 UUID if the fairing was
+    /// configured to generate UUIDs; otherwise, it is the `u64` ID zero-extended to 128 bits.
+    pub fn as_uuid(&self) -> Uuid {
+        Uuid::from_u128(self.id)
+    }
+
+    // from_uuid returns uuid as an ID which renders as a UUID, keeping track of whether it's v7.
+    fn from_uuid(uuid: Uuid) -> RequestID {
+        RequestID {
+            id: uuid.as_u128(),
+            high: 0,
+            kind: match uuid.get_version_num() {
+                7 => IdKind::UuidV7,
+                _ => IdKind::Uuid,
+            },
+            prefix: "",
+            namespace: PhantomData,
+        }
+    }
+}
+
+impl RequestID {
+    /// Returns the ID in lowercase hex, regardless of how the fairing renders IDs: 16 digits for
+    /// `u64` IDs, 24 for xids, 32 for 128-bit IDs, UUIDs and ULIDs, and 40 for KSUIDs. This
+    /// doesn't include the fairing's prefix, if any.
+    pub fn to_hex(&self) -> String {
+        match self.kind {
+            _ if self.kind.is_u64() => format!("{:016x}", self.id),
+            IdKind::Xid(_) => format!("{:024x}", self.id),
+            IdKind::Ksuid => format!("{:08x}{:032x}", self.high, self.id),
+            _ => format!("{:032x}", self.id),
+        }
+    }
+
+    /// Returns the ID in base62 (`0-9A-Za-z`), regardless of how the fairing renders IDs, e.g. for
+    /// a short, URL-safe link that quotes it. KSUIDs lose their timestamp. This doesn't include
+    /// the fairing's prefix, if any.
+    pub fn to_base62(&self) -> String {
+        base62::encode(self.id)
+    }
+
+    /// Parses an ID rendered by `to_base62`, or by a fairing with `IdFormat::Base62`. There are no
+    /// leading zeros in base62 IDs, so those, and the empty string, are rejected. IDs past
+    /// `u64::MAX`, such as those of `RequestIDFairing::with_128_bit_ids`, are parsed as 128-bit
+    /// IDs, which render as 32 hex digits; anything past `u128::MAX` is rejected.
+    pub fn from_base62(s: &str) -> Option<RequestID> {
+        let id = base62::decode(s)?;
+        if id > u64::MAX as u128 {
+            return Some(RequestID::from_u128(id));
+        }
+        Some(RequestID {
+            id,
+            high: 0,
+            kind: IdKind::Base62Number,
+            prefix: "",
+            namespace: PhantomData,
+        })
+    }
+
+    /// Returns the ID in Crockford's base32, as a fairing with `IdFormat::Crockford` renders it,
+    /// regardless of how the fairing renders IDs. For IDs longer than 64 bits, this is only their
+    /// low 64 bits. This doesn't include the fairing's prefix, if any.
+    pub fn to_crockford(&self) -> String {
+        crockford::encode(self.id as u64)
+    }
+
+    /// Parses a `u64` ID rendered by `to_crockford`, or by a fairing with `IdFormat::Crockford`,
+    /// as leniently as a fairing with `IdFormat::Crockford` parses inbound IDs, e.g. an ID a user
+    /// read out with hyphens in it. Leading zeros are also allowed, as they don't change the ID.
+    pub fn from_crockford(s: &str) -> Option<RequestID> {
+        let id = crockford::decode(s)?;
+        Some(RequestID {
+            id: id as u128,
+            high: 0,
+            kind: IdKind::CrockfordNumber,
+            prefix: "",
+            namespace: PhantomData,
+        })
+    }
+
+    /// Returns the ID as it's rendered, followed by a truncated HMAC-SHA256 of it keyed by `key`,
+    /// as a fairing with `RequestIDFairing::with_signed_ids` sends it in its response header.
+    /// Requires the `signing` feature.
+    #[cfg(feature = "signing")]
+    pub fn sign(&self, key: &[u8]) -> String {
+        signing::sign(&self.to_string(), key)
+    }
+
+    /// Checks an ID signed by `sign`, or by a fairing with `RequestIDFairing::with_signed_ids`,
+    /// with the same `key`, e.g. one a client quoted to support, and returns it without its tag.
+    /// Tags are compared in constant time. Requires the `signing` feature.
+    #[cfg(feature = "signing")]
+    pub fn verify(s: &str, key: &[u8]) -> Result<VerifiedId, SignatureError> {
+        signing::verify(s, key).map(|id| VerifiedId(id.to_string()))
+    }
+
+    /// Returns the ID in base62 followed by its check character, as a fairing with
+    /// `IdFormat::Base62Checked` renders it, e.g. to print on an error page for users to quote.
+    /// This doesn't include the fairing's prefix, if any.
+    pub fn to_base62_checked(&self) -> String {
+        base62::encode_checked(self.id)
+    }
+
+    /// Parses a `u64` ID rendered by `to_base62_checked`, or by a fairing with
+    /// `IdFormat::Base62Checked`, e.g. one a user quoted to support. Unlike `from_base62`, this
+    /// tells an ID whose check character doesn't match, which was most likely mistyped, apart
+    /// from a string which isn't an ID at all.
+    pub fn parse_checked(s: &str) -> Result<RequestID, ParseCheckedIDError> {
+        let split = s.len().checked_sub(1).filter(|&i| i > 0 && s.is_char_boundary(i));
+        let (encoded, check) = match split {
+            Some(i) => s.split_at(i),
+            None => return Err(ParseCheckedIDError::Invalid),
+        };
+        match base62::check_char(encoded) {
+            Some(expected) if check.starts_with(expected) => {}
+            // Only a base62 digit could be a mistyped check character.
+            Some(_) if base62::check_char(check).is_some() => {
+                return Err(ParseCheckedIDError::ChecksumMismatch);
+            }
+            _ => return Err(ParseCheckedIDError::Invalid),
+        }
+        let id = IdKind::Base62Number
+            .parse(encoded)
+            .ok_or(ParseCheckedIDError::Invalid)?
+            .1;
+        Ok(RequestID {
+            id,
+            high: 0,
+            kind: IdKind::Base62Checked,
+            prefix: "",
+            namespace: PhantomData,
+        })
+    }
+
+    /// Returns the ID as a `u64`, as `u64::from` does, but from a borrowed `RequestID`, e.g. one in
+    /// a struct field. For IDs longer than 64 bits, such as UUIDs, this is only their low 64 bits.
+    pub fn as_u64(&self) -> u64 {
+        self.id as u64
+    }
+
+    /// Returns the ID as a ULID: 26 characters of Crockford base32, regardless of how the fairing
+    /// renders IDs. This is only a real ULID if the fairing was configured to generate them (see
+    /// `RequestIDFairing::with_ulids`); otherwise, it is the ID zero-extended to 128 bits. This
+    /// doesn't include the fairing's prefix, if any.
+    pub fn to_ulid_string(&self) -> String {
+        ulid::encode(self.id)
+    }
+
+    /// Returns the Unix timestamp, in milliseconds, that the ID was generated at, if it's a ULID,
+    /// a KSUID, an xid, a Snowflake ID, a timestamped ID or a version 7 UUID (see
+    /// `RequestIDFairing::with_ulids`, `with_ksuids`, `with_xids`, `with_snowflakes`,
+    /// `with_timestamped_ids` and `with_v7_uuids`); e.g. to work out how long ago an inherited
+    /// ID's request started. It returns `None` for any other kind of ID. KSUIDs and xids only
+    /// have a timestamp to the second.
+    pub fn timestamp_ms(&self) -> Option<u64> {
+        self.timestamp().map(|timestamp| {
+            let since_epoch = timestamp
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or(Duration::from_secs(0));
+            since_epoch.as_secs() * 1000 + since_epoch.subsec_millis() as u64
+        })
+    }
+
+    /// Returns the time the ID was generated at, as `timestamp_ms` does.
+    pub fn timestamp(&self) -> Option<SystemTime> {
+        match self.kind {
+            #[cfg(feature = "uuid")]
+            IdKind::UuidV7 => {
+                Some(UNIX_EPOCH + Duration::from_millis(uuid_v7::timestamp_ms(self.id)))
+            }
+            IdKind::Ulid => Some(UNIX_EPOCH + Duration::from_millis(ulid::timestamp_ms(self.id))),
+            IdKind::Ksuid => Some(ksuid::timestamp(self.high)),
+            IdKind::Xid(_) => Some(xid::timestamp(self.id)),
+            IdKind::Snowflake(_) => {
+                Some(UNIX_EPOCH + Duration::from_millis(snowflake::timestamp_ms(self.id as u64)))
+            }
+            IdKind::Timestamped => {
+                Some(UNIX_EPOCH + Duration::from_millis(timestamped::timestamp_ms(self.id as u64)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the ID of the worker which generated the ID, if it's a Snowflake ID (see
+    /// `RequestIDFairing::with_snowflakes`). This is read from the ID, so an inherited ID says
+    /// which worker generated it, not this one's ID.
+    pub fn worker_id(&self) -> Option<u16> {
+        match self.kind {
+            IdKind::Snowflake(_) => Some(snowflake::worker_id(self.id as u64)),
+            _ => None,
+        }
+    }
+
+    /// Returns the sequence number which tells the ID apart from others generated by the same
+    /// worker in the same millisecond, if it's a Snowflake ID.
+    pub fn sequence(&self) -> Option<u16> {
+        match self.kind {
+            IdKind::Snowflake(_) => Some(snowflake::sequence(self.id as u64)),
+            _ => None,
+        }
+    }
+
+    // from_ulid returns ulid as an ID which renders as a ULID.
+    fn from_ulid(ulid: u128) -> RequestID {
+        RequestID {
+            id: ulid,
+            high: 0,
+            kind: IdKind::Ulid,
+            prefix: "",
+            namespace: PhantomData,
+        }
+    }
+
+    // from_u128 returns id as an ID which renders as 32 hex digits.
+    fn from_u128(id: u128) -> RequestID {
+        RequestID {
+            id,
+            high: 0,
+            kind: IdKind::Hex128,
+            prefix: "",
+            namespace: PhantomData,
+        }
+    }
+
+    // from_xid returns xid as an ID which renders as an xid.
+    fn from_xid(xid: u128) -> RequestID {
+        RequestID {
+            id: xid,
+            high: 0,
+            kind: IdKind::Xid(xid::machine(xid)),
+            prefix: "",
+            namespace: PhantomData,
+        }
+    }
+
+    // from_ksuid returns the KSUID made up of timestamp and payload as an ID which renders as one.
+    fn from_ksuid(timestamp: u32, payload: u128) -> RequestID {
+        RequestID {
+            id: payload,
+            high: timestamp,
+            kind: IdKind::Ksuid,
+            prefix: "",
+            namespace: PhantomData,
+        }
+    }
+
+    /// Returns `request`'s ID, without going through the request guard; this is for code which
+    /// only has the `Request`, such as catchers:
+    ///
+    /// ```
+    /// # #[macro_use] extern crate rocket;
+    /// use rocket::Request;
+    /// use rocket_request_id::RequestID;
+    ///
+    /// #[catch(404)]
+    /// fn not_found(request: &Request) -> String {
+    ///     match RequestID::of(request) {
+    ///         Some(id) => format!("Nothing here; quote {} if you think there should be", id),
+    ///         None => "Nothing here".to_string(),
+    ///     }
+    /// }
+    /// # fn main() {}
+    /// ```
+    ///
+    /// It returns `None` if the request has no ID, e.g. because the
+    /// fairing isn't attached and no `RequestID` guard has given it one yet.
+    pub fn of(request: &Request) -> Option<RequestID> {
+        with_tracked(request, |tracked| tracked.id)
+    }
+
+    /// Returns how long ago the fairing saw `request` arrive, e.g. to log a slow handler's
+    /// latency before its response is ready. If the request only got its ID from a `RequestID`
+    /// guard, because the fairing isn't attached, this is the time since the guard ran.
+    ///
+    /// It returns `None` if the request has no ID, as for `RequestID::of`.
+    pub fn elapsed(request: &Request) -> Option<Duration> {
+        with_tracked(request, |tracked| tracked.started.elapsed())
+    }
+
+    /// Replaces `request`'s ID with `id`, e.g. one derived once the request is authenticated, and
+    /// returns the new ID. From then on, `RequestID` guards, `RequestID::of` and the response
+    /// header all give the new ID; guards which already ran, and spans already opened for the
+    /// request, keep the old one. The new ID gets the old one's prefix, and is rendered in the
+    /// same `IdFormat`, or in decimal if the old ID wasn't a `u64`.
+    ///
+    /// This returns `None` without replacing anything if the request has no ID yet, as for
+    /// `RequestID::of`.
+    pub fn set(request: &Request, id: u64) -> Option<RequestID> {
+        let (old, new) = with_tracked_mut(request, |tracked| {
+            let old = tracked.id;
+            tracked.id = RequestID {
+                id: id as u128,
+                high: 0,
+                kind: if old.kind.is_u64() { old.kind } else { IdKind::Number },
+                prefix: old.prefix,
+                namespace: PhantomData,
+            };
+            (old, tracked.id)
+        })?;
+        CURRENT_REQUEST_ID.with(|current| {
+            if current.get() == Some(old) {
+                current.set(Some(new));
+            }
+        });
+        Some(new)
+    }
+}
+
+/// Converts the ID to a `u64`. For IDs longer than 64 bits, such as UUIDs and the IDs of
+/// `RequestIDFairing::with_128_bit_ids`, this is only their low 64 bits.
+impl From<RequestID> for u64 {
+    fn from(r: RequestID) -> u64 {
+        r.id as u64
+    }
+}
+
+/// Converts the ID to an `i64`, e.g. to store in a SQL `BIGINT` column, by reinterpreting the bits
+/// of `u64::from`'s `u64`: IDs of 2^63 or more become negative, and are the same ID again when cast
+/// back with `as u64`.
+impl From<RequestID> for i64 {
+    fn from(r: RequestID) -> i64 {
+        r.id as u64 as i64
+    }
+}
+
+/// Converts the ID to a `u128`. `u64` IDs are zero-extended, and KSUIDs lose their timestamp.
+impl From<RequestID> for u128 {
+    fn from(r: RequestID) -> u128 {
+        r.id
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl From<RequestID> for Uuid {
+    fn from(r: RequestID) -> Uuid {
+        r.as_uuid()
+    }
+}
+
+/// Renders the ID as `to_hex` does, zero-padded to the same number of digits whatever its value,
+/// so that every team's `{:x}` of the same ID is the same. This doesn't include the fairing's
+/// prefix, if any, but `{:#x}` adds a `0x`.
+impl fmt::LowerHex for RequestID {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad_integral(true, "0x", &self.to_hex())
+    }
+}
+
+/// Renders the ID as `LowerHex` does, in uppercase.
+impl fmt::UpperHex for RequestID {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad_integral(true, "0x", &self.to_hex().to_ascii_uppercase())
+    }
+}
+
+impl<N> fmt::Display for RequestID<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.prefix)?;
+        match self.kind {
+            IdKind::Number | IdKind::Snowflake(_) | IdKind::Timestamped => write!(f, "{}", self.id),
+            IdKind::HexNumber => f.write_str(&hex::encode(self.id, 16)),
+            IdKind::Base62Number => f.write_str(&base62::encode(self.id)),
+            IdKind::Base62Checked => f.write_str(&base62::encode_checked(self.id)),
+            IdKind::CrockfordNumber => f.write_str(&crockford::encode(self.id as u64)),
+            IdKind::ShortHex => f.write_str(&hex::encode(self.id, 8)),
+            IdKind::Hex128 => f.write_str(&hex::encode(self.id, 32)),
+            IdKind::GroupedHex(grouping) => f.write_str(&grouping.encode(self.id)),
+            IdKind::UpperHex { digits } => {
+                f.write_str(&hex::encode_upper(self.id, digits as usize))
+            }
+            #[cfg(feature = "uuid")]
+            IdKind::Uuid | IdKind::UuidV7 => write!(f, "{}", Uuid::from_u128(self.id).hyphenated()),
+            IdKind::Ulid => f.write_str(&ulid::encode(self.id)),
+            IdKind::Ksuid => f.write_str(&ksuid::encode(self.high, self.id)),
+            IdKind::Xid(_) => f.write_str(&xid::encode(self.id)),
+            IdKind::Nanoid { alphabet, len } => {
+                f.write_str(&nanoid::encode(alphabet, len, self.id))
+            }
+        }
+    }
+}
+
+/// Serializes the ID as a `u64`, or, for 128-bit IDs, UUIDs, ULIDs, KSUIDs, xids and nanoids, as a
+/// string in the form the `Display` implementation renders it in.
+#[cfg(feature = "serde")]
+impl serde::Serialize for RequestID {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.kind.is_u64() {
+            serializer.serialize_u64(self.id as u64)
+        } else {
+            serializer.collect_str(self)
+        }
+    }
+}
+
+/// Deserializes an ID serialized by the `Serialize` implementation, except for nanoids, which can't
+/// be told apart from other strings without their alphabet.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for RequestID {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<RequestID, D::Error> {
+        deserializer.deserialize_any(RequestIDVisitor)
+    }
+}
+
+#[cfg(feature = "serde")]
+struct RequestIDVisitor;
+
+#[cfg(feature = "serde")]
+impl<'de> serde::de::Visitor<'de> for RequestIDVisitor {
+    type Value = RequestID;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if cfg!(feature = "uuid") {
+            f.write_str("a u64, u128, UUID, ULID, KSUID or xid request id")
+        } else {
+            f.write_str("a u64, u128, ULID, KSUID or xid request id")
+        }
+    }
+
+    fn visit_u64<E: serde::de::Error>(self, id: u64) -> Result<RequestID, E> {
+        Ok(RequestID {
+            id: id as u128,
+            high: 0,
+            kind: IdKind::Number,
+            prefix: "",
+            namespace: PhantomData,
+        })
+    }
+
+    fn visit_str<E: serde::de::Error>(self, s: &str) -> Result<RequestID, E> {
+        if let Some(id) = ulid::decode(s) {
+            return Ok(RequestID::from_ulid(id));
+        }
+        if let Some((timestamp, payload)) = ksuid::decode(s) {
+            return Ok(RequestID::from_ksuid(timestamp, payload));
+        }
+        if let Some(id) = xid::decode(s) {
+            return Ok(RequestID::from_xid(id));
+        }
+        #[cfg(feature = "uuid")]
+        {
+            if let Ok(uuid) = Uuid::parse_str(s) {
+                return Ok(RequestID::from_uuid(uuid));
+            }
+        }
+        if let Some((_, id)) = IdKind::Hex128.parse(s) {
+            return Ok(RequestID::from_u128(id));
+        }
+        Err(E::invalid_value(serde::de::Unexpected::Str(s), &self))
+    }
+}
+
+/// Parses an ID as rendered by the `Display` implementation: a decimal `u64`, a ULID, a KSUID, an
+/// xid, a 128-bit ID, or, with the `uuid` feature, a UUID. With that feature, 32 hex digits parse
+/// as a UUID, rather than a 128-bit ID; they're the same bits either way.
+impl FromStr for RequestID {
+    type Err = ParseRequestIDError;
+
+    fn from_str(s: &str) -> Result<RequestID, ParseRequestIDError> {
+        if let Some((_, id)) = IdKind::Number.parse(s) {
+            return Ok(RequestID {
+                id,
+                high: 0,
+                kind: IdKind::Number,
+                prefix: "",
+                namespace: PhantomData,
+            });
+        }
+        if let Some(id) = ulid::decode(s) {
+            return Ok(RequestID::from_ulid(id));
+        }
+        if let Some((timestamp, payload)) = ksuid::decode(s) {
+            return Ok(RequestID::from_ksuid(timestamp, payload));
+        }
+        if let Some(id) = xid::decode(s) {
+            return Ok(RequestID::from_xid(id));
+        }
+        #[cfg(feature = "uuid")]
+        {
+            if let Ok(uuid) = Uuid::parse_str(s) {
+                return Ok(RequestID::from_uuid(uuid));
+            }
+        }
+        if let Some((_, id)) = IdKind::Hex128.parse(s) {
+            return Ok(RequestID::from_u128(id));
+        }
+        Err(ParseRequestIDError(()))
+    }
+}
+
+/// The error returned when a string can't be parsed as a `RequestID`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseRequestIDError(());
+
+impl fmt::Display for ParseRequestIDError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid request id")
+    }
+}
+
+impl Error for ParseRequestIDError {}
+
+/// The error returned when `RequestID::parse_checked` can't parse a string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseCheckedIDError {
+    /// The string isn't an ID with a check character.
+    Invalid,
+    /// The string is made of the right characters, but its check character doesn't match the
+    /// rest: one of them was most likely mistyped, so there's no use looking the ID up.
+    ChecksumMismatch,
+}
+
+impl fmt::Display for ParseCheckedIDError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseCheckedIDError::Invalid => f.write_str("invalid request id"),
+            ParseCheckedIDError::ChecksumMismatch => {
+                f.write_str("request id check character doesn't match")
+            }
+        }
+    }
+}
+
+impl Error for ParseCheckedIDError {}
+
+/// Why one of this crate's request guards failed. Every guard fails with a 500 Internal Server
+/// Error, except as noted.
+///
+/// Where what a guard returns is optional enrichment, take an `Option` of it instead: that's
+/// `None` whenever the guard would fail, e.g. because the fairing isn't configured to track it,
+/// so the request is still handled.
+/// ```
+/// # #[macro_use] extern crate rocket;
+/// use rocket_request_id::TraceParent;
+///
+/// #[get("/")]
+/// fn index(trace: Option<TraceParent>) -> String {
+///     match trace {
+///         Some(trace) => format!("trace {:032x}", trace.trace_id()),
+///         None => "no trace".to_string(),
+///     }
+/// }
+/// # fn main() {}
+/// ```
+///
+/// The guards which need the fairing are Rocket sentinels, so a rocket with routes which take
+/// them, or an `Option` of them, refuses to launch without a `RequestIDFairing` in the default
+/// namespace. Take an `Optional` of a guard to launch without the fairing anyway. The `RequestID`
+/// guard doesn't need the fairing at all.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RequestIDError {
+    /// No `RequestIDFairing` is attached, so there's nothing for the guard to return. Sentinels
+    /// stop this from happening to routes' own guards, so this is for guards which other guards
+    /// run, and those taken as an `Optional`. The `RequestID` guard itself never fails for this
+    /// reason, since it works without the fairing.
+    FairingNotAttached,
+    /// The fairing isn't configured to track what the guard returns, e.g. the `TraceParent`
+    /// guard was used without `RequestIDFairing::with_trace_context`.
+    NotTracked,
+    /// The fairing didn't give the request an ID, because it had no usable inbound ID under
+    /// `InboundPolicy::Require`.
+    NoRequestID,
+    /// The request is being rejected for the invalid inbound ID in the named header, under
+    /// `InvalidIdAction::Reject`. The guard fails with a 400 Bad Request.
+    InvalidInboundID(Cow<'static, str>),
+}
+
+impl fmt::Display for RequestIDError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RequestIDError::FairingNotAttached => {
+                f.write_str("the request id fairing isn't attached")
+            }
+            RequestIDError::NotTracked => f.write_str("the request id fairing isn't tracking it"),
+            RequestIDError::NoRequestID => f.write_str("the request has no request id"),
+            RequestIDError::InvalidInboundID(ref header) => write!(f, "invalid {} header", header),
+        }
+    }
+}
+
+impl Error for RequestIDError {}
+
+#[rocket::async_trait]
+impl<'r, N: Namespace> FromRequest<'r> for RequestID<N> {
+    type Error = RequestIDError;
+
+    async fn from_request(request: &'r Request<'_>) -> ReqOutcome<Self, Self::Error> {
+        let tracked = with_slot_in::<N, _, _>(request, |slot| {
+            if !slot.attached && slot.tracked.is_none() {
+                let id = RequestID {
+                    id: IdKind::Number.generate(&mut thread_rng()).1,
+                    high: 0,
+                    kind: IdKind::Number,
+                    prefix: "",
+                    namespace: PhantomData,
+                };
+                slot.tracked = Some(Tracked::generated(id));
+            }
+            slot.tracked
+                .as_ref()
+                .map(|tracked| (tracked.id, tracked.rejected.clone()))
+        });
+        match tracked {
+            Some((_, Some(header))) => {
+                Outcome::Error((Status::BadRequest, RequestIDError::InvalidInboundID(header)))
+            }
+            Some((id, None)) => Outcome::Success(id.in_namespace()),
+            None => {
+                debug!("request has no request id; the fairing didn't assign one");
+                Outcome::Error((Status::InternalServerError, RequestIDError::NoRequestID))
+            }
+        }
+    }
+}
+
+///
+/// The ID an upstream assigned to a rocket request, exactly as it was sent.
+///
+/// This is available whenever a `RequestIDFairing` reads a valid inbound ID header, even if the ID
+/// doesn't parse as one the fairing would generate. It's most useful with
+/// `InboundPolicy::Parent`, where the request's `RequestID` is always newly generated and this is
+/// the only record of the upstream's ID.
+///
+/// If the request has no upstream ID, this guard forwards; take an `Option<ParentRequestID>` to
+/// handle that case in the same handler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParentRequestID {
+    id: String,
+}
+
+impl ParentRequestID {
+    /// Returns the upstream's ID for the request.
+    pub fn as_str(&self) -> &str {
+        &self.id
+    }
+}
+
+impl From<ParentRequestID> for String {
+    fn from(p: ParentRequestID) -> String {
+        p.id
+    }
+}
+
+impl fmt::Display for ParentRequestID {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.id)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ParentRequestID {
+    type Error = RequestIDError;
+
+    async fn from_request(request: &'r Request<'_>) -> ReqOutcome<Self, Self::Error> {
+        let parent = tracked_guard(request, "parent request id", |tracked| {
+            Some(tracked.parent.clone())
+        });
+        parent.and_then(|parent| match parent {
+            Some(id) => Outcome::Success(ParentRequestID { id }),
+            None => Outcome::Forward(Status::NotFound),
+        })
+    }
+}
+
+impl Sentinel for ParentRequestID {
+    fn abort(rocket: &Rocket<Ignite>) -> bool {
+        fairing_missing(rocket, "ParentRequestID")
+    }
+}
+
+/// Where a request's `RequestID` came from: whether the fairing generated it, or honored one an
+/// upstream (which may be the client) sent.
+///
+/// This is a request guard, too:
+/// ```
+/// # #[macro_use] extern crate rocket;
+/// use rocket_request_id::RequestIDSource;
+///
+/// #[get("/")]
+/// fn audited(source: RequestIDSource) -> String {
+///     format!("request id {}", source)
+/// }
+/// # fn main() {}
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RequestIDSource {
+    /// The fairing generated the ID.
+    Generated,
+    /// The ID was inherited from the named inbound header.
+    Header(Cow<'static, str>),
+    /// The ID was inherited from the named query parameter; see
+    /// `RequestIDFairing::with_query_param`.
+    QueryParam(Cow<'static, str>),
+}
+
+impl RequestIDSource {
+    /// Returns where `request`'s ID came from, without going through the request guard. It
+    /// returns `None` if the request has no ID, e.g. because the fairing isn't attached.
+    pub fn of(request: &Request) -> Option<RequestIDSource> {
+        with_tracked(request, |tracked| tracked.source.clone())
+    }
+}
+
+/// Renders the source for logs, e.g. `generated` or `from X-Request-ID header`.
+impl fmt::Display for RequestIDSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RequestIDSource::Generated => f.write_str("generated"),
+            RequestIDSource::Header(ref name) => write!(f, "from {} header", name),
+            RequestIDSource::QueryParam(ref name) => write!(f, "from {} query parameter", name),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RequestIDSource {
+    type Error = RequestIDError;
+
+    async fn from_request(request: &'r Request<'_>) -> ReqOutcome<Self, Self::Error> {
+        tracked_guard(request, "request id source", |tracked| Some(tracked.source.clone()))
+    }
+}
+
+impl Sentinel for RequestIDSource {
+    fn abort(rocket: &Rocket<Ignite>) -> bool {
+        fairing_missing(rocket, "RequestIDSource")
+    }
+}
+
+/// A request guard for a request's ID along with its client's IP address, which access logs
+/// usually want together:
+/// ```
+/// # #[macro_use] extern crate rocket;
+/// use rocket_request_id::RequestContext;
+///
+/// #[get("/")]
+/// fn index(context: RequestContext) -> &'static str {
+///     println!("request {} from {:?}", context.id, context.ip);
+///     "ok"
+/// }
+/// # fn main() {}
+/// ```
+///
+/// This fails whenever the `RequestID` guard would.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestContext {
+    /// The request's ID, as the `RequestID` guard gives it.
+    pub id: RequestID,
+    /// The client's IP address, as `Request::client_ip` gives it: from the header Rocket is
+    /// configured to read it from (`X-Real-IP` by default), or else the address the request came
+    /// from. This is `None` if neither is known.
+    pub ip: Option<IpAddr>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RequestContext {
+    type Error = RequestIDError;
+
+    async fn from_request(request: &'r Request<'_>) -> ReqOutcome<Self, Self::Error> {
+        RequestID::from_request(request).await.map(|id| RequestContext {
+            id,
+            ip: request.client_ip(),
+        })
+    }
+}
+
+///
+/// A request guard which is `Option<T>`, for a guard `T` which needs the fairing, without `T`'s
+/// sentinel: a rocket whose routes take an `Option<T>` refuses to launch without a
+/// `RequestIDFairing`, but one whose routes take an `Optional<T>` launches, and gets `None`.
+///
+/// ```
+/// # #[macro_use] extern crate rocket;
+/// use rocket_request_id::{Optional, ParentRequestID};
+///
+/// #[get("/")]
+/// fn index(parent: Optional<ParentRequestID>) -> String {
+///     match parent.0 {
+///         Some(parent) => format!("called by {}", parent),
+///         None => "no parent".to_string(),
+///     }
+/// }
+///
+/// # fn main() {
+/// // This launches, even though there's no fairing.
+/// let rocket = rocket::build().mount("/", routes![index]);
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Optional<T>(pub Option<T>);
+
+impl<T> From<Optional<T>> for Option<T> {
+    fn from(optional: Optional<T>) -> Option<T> {
+        optional.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, T: FromRequest<'r>> FromRequest<'r> for Optional<T> {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> ReqOutcome<Self, Self::Error> {
+        Outcome::Success(Optional(T::from_request(request).await.succeeded()))
+    }
+}
+
+impl<T> Sentinel for Optional<T> {
+    fn abort(_: &Rocket<Ignite>) -> bool {
+        false
+    }
+}
+
+/// A request guard which stops the fairing from setting any of its headers (the request ID
+/// header, any trace headers, and the session cookie) on the request's response.
+///
+/// This is for responses which must not have extra headers, e.g. signed redirects and cached
+/// assets. The request keeps its ID, and other guards work as usual.
+///
+/// ```
+/// # #[macro_use] extern crate rocket;
+/// use rocket_request_id::SuppressRequestIDHeader;
+///
+/// #[get("/asset")]
+/// fn asset(_suppress: SuppressRequestIDHeader) -> &'static str {
+///     "cache me"
+/// }
+/// # fn main() {}
+/// ```
+#[derive(Debug)]
+pub struct SuppressRequestIDHeader(());
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SuppressRequestIDHeader {
+    type Error = ();
+
+    // This always succeeds: without an entry, there are no headers to suppress anyway.
+    async fn from_request(request: &'r Request<'_>) -> ReqOutcome<Self, Self::Error> {
+        with_tracked_mut(request, |tracked| tracked.suppress_headers = true);
+        Outcome::Success(SuppressRequestIDHeader(()))
+    }
+}
+
+///
+/// A request guard for the request's `request` span, which has a `request_id` field. This is only
+/// available with the `tracing` feature.
+///
+/// It dereferences to the span, so that handlers can enter it, or instrument futures with it:
+/// ```
+/// # #[macro_use] extern crate rocket;
+/// use rocket_request_id::RequestSpan;
+///
+/// #[get("/")]
+/// fn traced(span: RequestSpan) -> &'static str {
+///     span.in_scope(|| tracing::info!("handling request"));
+///     "ok"
+/// }
+/// # fn main() {}
+/// ```
+#[cfg(feature = "tracing")]
+#[derive(Debug, Clone)]
+pub struct RequestSpan(tracing::Span);
+
+#[cfg(feature = "tracing")]
+impl std::ops::Deref for RequestSpan {
+    type Target = tracing::Span;
+
+    fn deref(&self) -> &tracing::Span {
+        &self.0
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl From<RequestSpan> for tracing::Span {
+    fn from(span: RequestSpan) -> tracing::Span {
+        span.0
+    }
+}
+
+#[cfg(feature = "tracing")]
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RequestSpan {
+    type Error = RequestIDError;
+
+    async fn from_request(request: &'r Request<'_>) -> ReqOutcome<Self, Self::Error> {
+        tracked_guard(request, "request span", |tracked| Some(RequestSpan(tracked.span.clone())))
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Sentinel for RequestSpan {
+    fn abort(rocket: &Rocket<Ignite>) -> bool {
+        fairing_missing(rocket, "RequestSpan")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::http::Cookie;
+    use rocket::local::blocking::Client;
+    use rocket::{get, routes};
+    use std::collections::{HashMap, HashSet};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[get("/")]
+    fn req_id(id: RequestID) -> String {
+        format!("{}", u64::from(id))
+    }
+
+    #[test]
+    fn unique_ids() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing)
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp1 = c.get("/").dispatch();
+        let resp2 = c.get("/").dispatch();
+
+        assert_eq!(resp1.status(), Status::Ok);
+        assert_eq!(resp2.status(), Status::Ok);
+        assert_ne!(resp1.into_string(), resp2.into_string());
+    }
+
+    #[test]
+    fn doesnt_leak() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing)
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        assert_eq!(c.get("/").dispatch().status(), Status::Ok);
+        assert_eq!(c.get("/").dispatch().status(), Status::Ok);
+
+        assert_eq!(active_request_count(), 0);
+    }
+
+    #[get("/")]
+    fn active() -> String {
+        active_request_count().to_string()
+    }
+
+    #[test]
+    fn counts_active_requests() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing)
+            .mount("/", routes![active]);
+        let c = Client::tracked(rkt).unwrap();
+
+        // Other tests' requests may be in flight too, but this one certainly is.
+        let resp = c.get("/").dispatch();
+        assert!(resp.into_string().unwrap().parse::<usize>().unwrap() >= 1);
+    }
+
+    #[get("/")]
+    fn req_ids(id: RequestID, again: RequestID) -> String {
+        assert_eq!(id, again);
+        id.to_string()
+    }
+
+    #[test]
+    fn ids_without_fairing() {
+        let rkt = rocket::build().mount("/", routes![req_ids]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp1 = c.get("/").dispatch();
+        assert!(!resp1.headers().contains(REQUEST_ID_HEADER));
+        let id1 = resp1.into_string().unwrap();
+        let id2 = c.get("/").dispatch().into_string().unwrap();
+        assert!(id1.parse::<u64>().is_ok());
+        assert_ne!(id1, id2);
+    }
+
+    #[test]
+    fn sets_response_header() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing)
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let header = resp.headers().get_one(REQUEST_ID_HEADER).map(|h| h.to_string());
+
+        assert!(header.is_some());
+        assert_eq!(header, resp.into_string());
+    }
+
+    #[get("/")]
+    fn no_guard() -> &'static str {
+        "no id here"
+    }
+
+    #[test]
+    fn sets_response_header_without_guard() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing)
+            .mount("/", routes![no_guard]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let header = resp.headers().get_one(REQUEST_ID_HEADER);
+
+        assert!(header.unwrap().parse::<u64>().is_ok());
+    }
+
+    #[test]
+    fn sets_response_header_on_errors() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing)
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/missing").dispatch();
+
+        assert_eq!(resp.status(), Status::NotFound);
+        assert!(resp.headers().get_one(REQUEST_ID_HEADER).is_some());
+    }
+
+    // Authenticated stands in for a guard which gives requests an ID of their own once it knows
+    // who they're from.
+    struct Authenticated;
+
+    #[rocket::async_trait]
+    impl<'r> FromRequest<'r> for Authenticated {
+        type Error = ();
+
+        async fn from_request(request: &'r Request<'_>) -> ReqOutcome<Self, Self::Error> {
+            RequestID::set(request, 42).unwrap();
+            Outcome::Success(Authenticated)
+        }
+    }
+
+    #[get("/")]
+    fn authenticated(before: RequestID, _auth: Authenticated, after: RequestID) -> String {
+        format!("{} {}", before, after)
+    }
+
+    #[test]
+    fn sets_ids() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_prefix("api-"))
+            .mount("/", routes![authenticated]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        assert_eq!(resp.headers().get_one(REQUEST_ID_HEADER), Some("api-42"));
+        let body = resp.into_string().unwrap();
+        let (before, after) = body.split_once(' ').unwrap();
+        assert_ne!(before, "api-42");
+        assert_eq!(after, "api-42");
+
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_128_bit_ids())
+            .mount("/", routes![authenticated]);
+        let c = Client::tracked(rkt).unwrap();
+        let resp = c.get("/").dispatch();
+        assert_eq!(resp.headers().get_one(REQUEST_ID_HEADER), Some("42"));
+    }
+
+    struct Elapsed(Option<Duration>);
+
+    #[rocket::async_trait]
+    impl<'r> FromRequest<'r> for Elapsed {
+        type Error = ();
+
+        async fn from_request(request: &'r Request<'_>) -> ReqOutcome<Self, Self::Error> {
+            rocket::tokio::time::sleep(Duration::from_millis(20)).await;
+            Outcome::Success(Elapsed(RequestID::elapsed(request)))
+        }
+    }
+
+    #[get("/")]
+    fn elapsed(elapsed: Elapsed) -> String {
+        format!("{:?}", elapsed.0.map(|elapsed| elapsed >= Duration::from_millis(20)))
+    }
+
+    #[test]
+    fn elapsed_times() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_logging(true))
+            .mount("/", routes![elapsed]);
+        let c = Client::tracked(rkt).unwrap();
+        assert_eq!(c.get("/").dispatch().into_string(), Some("Some(true)".to_string()));
+
+        // Without the fairing, nothing has given the request an ID.
+        let rkt = rocket::build().mount("/", routes![elapsed]);
+        let c = Client::tracked(rkt).unwrap();
+        assert_eq!(c.get("/").dispatch().into_string(), Some("None".to_string()));
+    }
+
+    #[rocket::catch(404)]
+    fn not_found(request: &Request) -> String {
+        RequestID::of(request).unwrap().to_string()
+    }
+
+    #[test]
+    fn ids_in_catchers() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing)
+            .mount("/", routes![req_id])
+            .register("/", rocket::catchers![not_found]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/missing").dispatch();
+        assert_eq!(resp.status(), Status::NotFound);
+        let header = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        assert_eq!(resp.into_string(), Some(header));
+
+        let resp = c.get("/missing").header(Header::new(REQUEST_ID_HEADER, "1234")).dispatch();
+        assert_eq!(resp.headers().get_one(REQUEST_ID_HEADER), Some("1234"));
+        assert_eq!(resp.into_string(), Some("1234".to_string()));
+    }
+
+    #[derive(rocket::Responder)]
+    struct WithHeader(&'static str, Header<'static>);
+
+    #[get("/")]
+    fn own_header() -> WithHeader {
+        WithHeader("", Header::new(REQUEST_ID_HEADER, "handler-set"))
+    }
+
+    #[test]
+    fn keeps_existing_response_header() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing)
+            .mount("/", routes![own_header]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let headers: Vec<_> = resp.headers().get(REQUEST_ID_HEADER).collect();
+
+        assert_eq!(headers, vec!["handler-set"]);
+    }
+
+    #[test]
+    fn inherits_inbound_id() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing)
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "1234"))
+            .dispatch();
+
+        assert_eq!(resp.headers().get_one(REQUEST_ID_HEADER), Some("1234"));
+        assert_eq!(resp.into_string(), Some("1234".to_string()));
+    }
+
+    #[test]
+    fn inherits_first_inbound_id() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing)
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "1"))
+            .header(Header::new(REQUEST_ID_HEADER, "2"))
+            .dispatch();
+
+        assert_eq!(resp.into_string(), Some("1".to_string()));
+    }
+
+    #[test]
+    fn ignores_unparseable_inbound_id() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing)
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "not-a-number"))
+            .dispatch();
+
+        assert_eq!(resp.status(), Status::Ok);
+        let body = resp.into_string().unwrap();
+        assert!(body.parse::<u64>().is_ok());
+    }
+
+    #[test]
+    fn custom_header_name() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_header_name("X-Correlation-ID"))
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/")
+            .header(Header::new("X-Correlation-ID", "1234"))
+            .header(Header::new(REQUEST_ID_HEADER, "5678"))
+            .dispatch();
+
+        assert_eq!(resp.headers().get_one("X-Correlation-ID"), Some("1234"));
+        assert_eq!(resp.headers().get_one(REQUEST_ID_HEADER), None);
+        assert_eq!(resp.into_string(), Some("1234".to_string()));
+    }
+
+    #[test]
+    fn asp_net_style_header_name() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_header_name("Request-Id"))
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let header = resp.headers().get_one("Request-Id").map(|h| h.to_string());
+
+        assert!(header.is_some());
+        assert_eq!(header, resp.into_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid request id header name")]
+    fn rejects_invalid_header_name() {
+        RequestIDFairing::default().with_header_name("X-Request ID:");
+    }
+
+    #[test]
+    fn validates_header_names() {
+        assert!(is_valid_header_name("X-Request-ID"));
+        assert!(is_valid_header_name("x_trace.id"));
+        assert!(!is_valid_header_name(""));
+        assert!(!is_valid_header_name("X-Request-ID\r\n"));
+        assert!(!is_valid_header_name("Request(ID)"));
+    }
+
+    #[test]
+    fn displays_as_number() {
+        let id: RequestID = RequestID {
+            id: 1234567890,
+            high: 0,
+            kind: IdKind::Number,
+            prefix: "",
+            namespace: PhantomData,
+        };
+        assert_eq!(format!("{}", id), "1234567890");
+    }
+
+    #[test]
+    fn only_trusts_configured_proxies() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_trusted_proxies(["10.0.0.0/8"]))
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let dispatch = |remote: Option<&str>| {
+            let mut req = c.get("/").header(Header::new(REQUEST_ID_HEADER, "1234"));
+            if let Some(remote) = remote {
+                req = req.remote(remote.parse().unwrap());
+            }
+            req.dispatch().into_string().unwrap()
+        };
+
+        assert_eq!(dispatch(Some("10.1.2.3:8000")), "1234");
+        assert_ne!(dispatch(Some("192.168.1.1:8000")), "1234");
+        assert_ne!(dispatch(None), "1234");
+    }
+
+    #[test]
+    fn idempotency_keys() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_idempotency_keys(Duration::from_secs(60)))
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let dispatch = |key: Option<&str>| {
+            let mut req = c.get("/");
+            if let Some(key) = key {
+                req = req.header(Header::new(IDEMPOTENCY_KEY_HEADER, key.to_string()));
+            }
+            req.dispatch().into_string().unwrap()
+        };
+        let first = dispatch(Some("order-1234"));
+        assert_eq!(dispatch(Some("order-1234")), first);
+        assert_eq!(dispatch(Some("order-1234")), first);
+        let other = dispatch(Some("order-5678"));
+        assert_ne!(other, first);
+        assert_eq!(dispatch(Some("order-5678")), other);
+        assert_ne!(dispatch(None), dispatch(None));
+        // An inbound ID takes precedence.
+        let resp = c.get("/")
+            .header(Header::new(IDEMPOTENCY_KEY_HEADER, "order-1234"))
+            .header(Header::new(REQUEST_ID_HEADER, "1234"))
+            .dispatch();
+        assert_eq!(resp.into_string(), Some("1234".to_string()));
+
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_idempotency_keys(Duration::from_millis(20)))
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+        let dispatch = || {
+            c.get("/")
+                .header(Header::new(IDEMPOTENCY_KEY_HEADER, "order-1234"))
+                .dispatch()
+                .into_string()
+                .unwrap()
+        };
+        let first = dispatch();
+        thread::sleep(Duration::from_millis(30));
+        assert_ne!(dispatch(), first);
+    }
+
+    #[get("/")]
+    fn context(context: RequestContext) -> String {
+        format!("{} {:?}", u64::from(context.id), context.ip)
+    }
+
+    #[test]
+    fn request_contexts() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing)
+            .mount("/", routes![context]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "1234"))
+            .remote("192.0.2.1:8000".parse().unwrap())
+            .dispatch();
+        assert_eq!(resp.into_string(), Some("1234 Some(192.0.2.1)".to_string()));
+
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "1234"))
+            .header(Header::new("X-Real-IP", "198.51.100.7"))
+            .remote("10.0.0.1:8000".parse().unwrap())
+            .dispatch();
+        assert_eq!(resp.into_string(), Some("1234 Some(198.51.100.7)".to_string()));
+
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "not an id!"))
+            .remote("192.0.2.1:8000".parse().unwrap())
+            .dispatch();
+        let id = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        assert_eq!(resp.into_string(), Some(format!("{} Some(192.0.2.1)", id)));
+    }
+
+    #[get("/")]
+    fn req_id_display(id: RequestID) -> String {
+        id.to_string()
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn uuid_ids() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_uuids())
+            .mount("/", routes![req_id_display]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let header = resp.headers().get_one(REQUEST_ID_HEADER).map(|h| h.to_string());
+        let body = resp.into_string().unwrap();
+        let uuid = Uuid::parse_str(&body).unwrap();
+
+        assert_eq!(uuid.get_version_num(), 4);
+        assert_eq!(body, uuid.hyphenated().to_string());
+        assert_eq!(header, Some(body.clone()));
+
+        let other = c.get("/").dispatch().into_string().unwrap();
+        assert_ne!(other, body);
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn converts_uuid_ids() {
+        let uuid = Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000").unwrap();
+        let id = RequestID::from_uuid(uuid);
+        assert_eq!(id.as_uuid(), uuid);
+        assert_eq!(Uuid::from(id), uuid);
+        assert_eq!(u64::from(id), 0xa716_4466_5544_0000);
+
+        let id = RequestID {
+            id: 1234,
+            high: 0,
+            kind: IdKind::Number,
+            prefix: "",
+            namespace: PhantomData,
+        };
+        assert_eq!(id.as_uuid().to_string(), "00000000-0000-0000-0000-0000000004d2");
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn inherits_uuids() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_uuids())
+            .mount("/", routes![req_id_display]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let canonical = "550e8400-e29b-41d4-a716-446655440000";
+        for inbound in [canonical, "550E8400E29B41D4A716446655440000"].iter() {
+            let resp = c.get("/")
+                .header(Header::new(REQUEST_ID_HEADER, *inbound))
+                .dispatch();
+            assert_eq!(resp.headers().get_one(REQUEST_ID_HEADER), Some(canonical));
+            assert_eq!(resp.into_string(), Some(canonical.to_string()));
+        }
+
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "0123456789"))
+            .dispatch();
+        let body = resp.into_string().unwrap();
+        assert_ne!(body, "0123456789");
+        assert_eq!(Uuid::parse_str(&body).unwrap().get_version_num(), 4);
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn v7_uuids_sort_in_creation_order() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_v7_uuids())
+            .mount("/", routes![req_id_display]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let first = c.get("/").dispatch().into_string().unwrap();
+        let second = c.get("/").dispatch().into_string().unwrap();
+
+        assert_eq!(Uuid::parse_str(&first).unwrap().get_version_num(), 7);
+        assert!(first < second);
+    }
+
+    #[cfg(feature = "uuid")]
+    #[get("/")]
+    fn req_id_timestamp(id: RequestID) -> String {
+        format!("{} {:?}", id, id.timestamp_ms())
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn v7_uuid_timestamps() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_v7_uuids())
+            .mount("/", routes![req_id_timestamp]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let start = uuid_v7::timestamp_ms(uuid_v7::generate(&mut thread_rng()));
+        let mut last = (String::new(), start);
+        for _ in 0..100 {
+            let body = c.get("/").dispatch().into_string().unwrap();
+            let mut parts = body.split(' ');
+            let id = parts.next().unwrap().to_string();
+            let ms: u64 = parts.next().unwrap()
+                .trim_start_matches("Some(")
+                .trim_end_matches(')')
+                .parse()
+                .unwrap();
+
+            assert_eq!(Uuid::parse_str(&id).unwrap().get_version_num(), 7);
+            assert!(id > last.0);
+            assert!(ms >= last.1);
+            last = (id, ms);
+        }
+
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_uuids())
+            .mount("/", routes![req_id_timestamp]);
+        let c = Client::tracked(rkt).unwrap();
+        assert!(c.get("/").dispatch().into_string().unwrap().ends_with(" None"));
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn inherits_inbound_uuid() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_uuids())
+            .mount("/", routes![req_id_display]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let inbound = "550e8400-e29b-41d4-a716-446655440000";
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, inbound))
+            .dispatch();
+        assert_eq!(resp.into_string(), Some(inbound.to_string()));
+
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "1234"))
+            .dispatch();
+        assert_ne!(resp.into_string(), Some("1234".to_string()));
+    }
+
+    #[test]
+    fn rejects_pathological_inbound_ids() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing)
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let huge = "1".repeat(10 * 1024);
+        let pathological = vec![
+            "1234\nINFO forged log line",
+            "12\x0034",
+            "\u{1b}[31m1234",
+            "12 34",
+            huge.as_str(),
+        ];
+        for inbound in pathological {
+            let resp = c.get("/")
+                .header(Header::new(REQUEST_ID_HEADER, inbound.to_string()))
+                .dispatch();
+            let body = resp.into_string().unwrap();
+
+            assert!(body.parse::<u64>().is_ok());
+            assert_ne!(body, inbound);
+        }
+    }
+
+    #[test]
+    fn custom_inbound_validation() {
+        let banned = '0';
+        let rkt = rocket::build()
+            .attach(
+                RequestIDFairing::default()
+                    .with_max_inbound_len(4)
+                    .with_inbound_validator(move |id| !id.starts_with(banned)),
+            )
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let dispatch = |inbound: &'static str| {
+            c.get("/")
+                .header(Header::new(REQUEST_ID_HEADER, inbound))
+                .dispatch()
+                .into_string()
+                .unwrap()
+        };
+
+        assert_eq!(dispatch("1234"), "1234");
+        assert_ne!(dispatch("12345"), "12345");
+        assert_ne!(dispatch("0123"), "123");
+    }
+
+    #[test]
+    fn loggable_escapes_and_truncates() {
+        assert_eq!(loggable("abc\n"), "\"abc\\n\"");
+        assert_eq!(loggable(&"a".repeat(40)), format!("{:?}...", "a".repeat(32)));
+    }
+
+    #[test]
+    fn inbound_header_precedence() {
+        let rkt = rocket::build()
+            .attach(
+                RequestIDFairing::default()
+                    .with_inbound_headers(vec!["X-Request-ID", "X-Correlation-ID"]),
+            )
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let both = c.get("/")
+            .header(Header::new("X-Correlation-ID", "2"))
+            .header(Header::new("X-Request-ID", "1"))
+            .dispatch();
+        assert_eq!(both.headers().get_one(REQUEST_ID_HEADER), Some("1"));
+        assert_eq!(both.into_string(), Some("1".to_string()));
+
+        let invalid_first = c.get("/")
+            .header(Header::new("X-Request-ID", "nope"))
+            .header(Header::new("X-Correlation-ID", "2"))
+            .dispatch();
+        assert_eq!(invalid_first.into_string(), Some("2".to_string()));
+
+        let neither = c.get("/")
+            .header(Header::new("X-Trace-ID", "3"))
+            .dispatch();
+        let generated = neither.into_string().unwrap();
+        assert!(generated.parse::<u64>().is_ok());
+        assert_ne!(generated, "3");
+    }
+
+    #[test]
+    fn inbound_policy_ignore() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_inbound_policy(InboundPolicy::Ignore))
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "1234"))
+            .dispatch();
+        let header = resp.headers().get_one(REQUEST_ID_HEADER).map(|h| h.to_string());
+        let body = resp.into_string();
+
+        assert_ne!(body, Some("1234".to_string()));
+        assert_eq!(header, body);
+    }
+
+    #[test]
+    fn inbound_policy_prefer() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_inbound_policy(InboundPolicy::Prefer))
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let inherited = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "1234"))
+            .dispatch();
+        assert_eq!(inherited.into_string(), Some("1234".to_string()));
+
+        let generated = c.get("/").dispatch();
+        assert_eq!(generated.status(), Status::Ok);
+        assert!(generated.headers().get_one(REQUEST_ID_HEADER).is_some());
+    }
+
+    #[test]
+    fn inbound_policy_require() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_inbound_policy(InboundPolicy::Require))
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let inherited = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "1234"))
+            .dispatch();
+        assert_eq!(inherited.into_string(), Some("1234".to_string()));
+
+        let missing = c.get("/").dispatch();
+        assert_eq!(missing.status(), Status::InternalServerError);
+        assert_eq!(missing.headers().get_one(REQUEST_ID_HEADER), None);
+    }
+
+    #[test]
+    fn inbound_policies_choose_id_sources() {
+        // Whether each policy takes an inbound ID, and whether it gives requests without one an
+        // ID anyway.
+        let policies = [
+            (InboundPolicy::Prefer, true, true),
+            (InboundPolicy::Ignore, false, true),
+            (InboundPolicy::Require, true, false),
+        ];
+        for &(policy, inherits, generates) in policies.iter() {
+            let rkt = rocket::build()
+                .attach(RequestIDFairing::default().with_inbound_policy(policy))
+                .mount("/", routes![optional_id]);
+            let c = Client::tracked(rkt).unwrap();
+
+            let inbound = c.get("/")
+                .header(Header::new(REQUEST_ID_HEADER, "1234"))
+                .dispatch()
+                .into_string()
+                .unwrap();
+            assert!(inbound.starts_with("request "), "{:?}: {}", policy, inbound);
+            assert_eq!(inbound == "request 1234", inherits, "{:?}: {}", policy, inbound);
+
+            let missing = c.get("/").dispatch();
+            let header = missing.headers().get_one(REQUEST_ID_HEADER).map(str::to_string);
+            let body = missing.into_string().unwrap();
+            assert_eq!(header.is_some(), generates, "{:?}", policy);
+            match header {
+                Some(id) => assert_eq!(body, format!("request {}", id)),
+                None => assert_eq!(body, "no request id"),
+            }
+        }
+    }
+
+    #[get("/")]
+    async fn slow_id(id: RequestID) -> String {
+        rocket::tokio::time::sleep(Duration::from_millis(50)).await;
+        id.to_string()
+    }
+
+    #[rocket::async_test]
+    async fn collision_checks() {
+        use rocket::futures::future::join_all;
+        use rocket::local::asynchronous::Client;
+
+        // Each fairing generates 7, 7, then 8.
+        let ids = |retries| async move {
+            let calls = AtomicU64::new(0);
+            let fairing = RequestIDFairing::from_fn(move |_| {
+                match calls.fetch_add(1, Ordering::Relaxed) {
+                    0 | 1 => 7,
+                    _ => 8,
+                }
+            });
+            let rkt = rocket::build()
+                .attach(fairing.with_collision_checks(retries, OnDuplicateId::Warn))
+                .mount("/", routes![slow_id]);
+            let c = Client::tracked(rkt).await.unwrap();
+            let responses = join_all((0..2).map(|_| c.get("/").dispatch())).await;
+            let mut ids = Vec::new();
+            for resp in responses {
+                ids.push(resp.into_string().await.unwrap());
+            }
+            ids.sort();
+            ids
+        };
+
+        assert_eq!(ids(3).await, ["7", "8"]);
+        assert_eq!(ids(0).await, ["7", "7"]);
+    }
+
+    #[test]
+    fn filters_requests() {
+        let skipped = String::from("/health");
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_filter(move |request| {
+                !request.uri().path().as_str().starts_with(&skipped)
+            }))
+            .mount("/", routes![req_id])
+            .mount("/health", routes![optional_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        assert!(resp.headers().get_one(REQUEST_ID_HEADER).is_some());
+        assert!(resp.into_string().unwrap().parse::<u64>().is_ok());
+
+        let resp = c.get("/health")
+            .header(Header::new(REQUEST_ID_HEADER, "1234"))
+            .dispatch();
+        assert_eq!(resp.headers().get_one(REQUEST_ID_HEADER), None);
+        assert_eq!(resp.into_string(), Some("no request id".to_string()));
+        assert_eq!(c.get("/health/missing").dispatch().headers().get_one(REQUEST_ID_HEADER), None);
+    }
+
+    #[test]
+    fn samples_requests() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_sampling(0.1))
+            .mount("/", routes![optional_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let mut sampled = 0;
+        for _ in 0..1000 {
+            let resp = c.get("/").dispatch();
+            let header = resp.headers().get_one(REQUEST_ID_HEADER).map(str::to_string);
+            let body = resp.into_string().unwrap();
+            match header {
+                Some(id) => {
+                    assert_eq!(body, format!("request {}", id));
+                    sampled += 1;
+                }
+                None => assert_eq!(body, "no request id"),
+            }
+        }
+        // The odds of falling outside this range are less than one in a billion.
+        assert!(sampled > 40 && sampled < 170, "{}", sampled);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid sampling rate")]
+    fn rejects_invalid_sampling_rates() {
+        RequestIDFairing::default().with_sampling(1.5);
+    }
+
+    // Rigged is a random number generator which isn't: it returns 0, then counts up from 1234.
+    struct Rigged(u64);
+
+    impl RngCore for Rigged {
+        fn next_u32(&mut self) -> u32 {
+            self.next_u64() as u32
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.0 += 1;
+            match self.0 {
+                1 => 0,
+                n => 1232 + n,
+            }
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            for chunk in dest.chunks_mut(8) {
+                let bytes = self.next_u64().to_le_bytes();
+                chunk.copy_from_slice(&bytes[..chunk.len()]);
+            }
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn reserved_ids() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_rng(Rigged(0)).with_reserved_ids(&[1235]))
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        assert_eq!(c.get("/").dispatch().into_string(), Some("1234".to_string()));
+        assert_eq!(c.get("/").dispatch().into_string(), Some("1236".to_string()));
+        for inbound in ["0", "1235", "18446744073709551615"].iter() {
+            let resp = c.get("/")
+                .header(Header::new(REQUEST_ID_HEADER, *inbound))
+                .dispatch();
+            let id = resp.into_string().unwrap();
+            assert_ne!(id, *inbound);
+            assert!(id.parse::<u64>().unwrap() > 1236);
+        }
+        let resp = c.get("/").header(Header::new(REQUEST_ID_HEADER, "1")).dispatch();
+        assert_eq!(resp.into_string(), Some("1".to_string()));
+
+        // Nor are reserved IDs taken from B3 trace IDs.
+        let rkt = rocket::build()
+            .attach(
+                RequestIDFairing::default()
+                    .with_b3_propagation()
+                    .with_reserved_ids(&[1235]),
+            )
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+        let resp = c.get("/")
+            .header(Header::new("x-b3-traceid", "00000000000004d3"))
+            .dispatch();
+        assert_ne!(resp.into_string(), Some("1235".to_string()));
+        let resp = c.get("/")
+            .header(Header::new("x-b3-traceid", "00000000000004d2"))
+            .dispatch();
+        assert_eq!(resp.into_string(), Some("1234".to_string()));
+    }
+
+    struct Constant(u64);
+
+    impl IdGenerator for Constant {
+        fn generate(&self, _: &Request) -> u64 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn custom_generator() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_generator(Constant(42)))
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        assert_eq!(resp.headers().get_one(REQUEST_ID_HEADER), Some("42"));
+        assert_eq!(resp.into_string(), Some("42".to_string()));
+
+        let inherited = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "1234"))
+            .dispatch();
+        assert_eq!(inherited.into_string(), Some("1234".to_string()));
+    }
+
+    // PathGenerator gives requests for the same path the same ID.
+    struct PathGenerator;
+
+    impl IdGenerator for PathGenerator {
+        fn generate(&self, request: &Request) -> u64 {
+            match request.uri().path().as_str() {
+                "/" => 1,
+                "/other" => 2,
+                _ => 0,
+            }
+        }
+    }
+
+    #[get("/other")]
+    fn other_req_id(id: RequestID) -> String {
+        id.to_string()
+    }
+
+    #[test]
+    fn generators_see_requests() {
+        let rkt = rocket::build()
+            .attach(
+                RequestIDFairing::default()
+                    .with_generator(PathGenerator)
+                    .with_format(IdFormat::Hex),
+            )
+            .mount("/", routes![req_id_display, other_req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        assert_eq!(c.get("/").dispatch().into_string(), Some("0000000000000001".to_string()));
+        let resp = c.get("/other").dispatch();
+        assert_eq!(resp.headers().get_one(REQUEST_ID_HEADER), Some("0000000000000002"));
+        assert_eq!(resp.into_string(), Some("0000000000000002".to_string()));
+    }
+
+    #[test]
+    fn closure_generator() {
+        let calls = Arc::new(AtomicU64::new(0));
+        let counted = calls.clone();
+        let rkt = rocket::build()
+            .attach(
+                RequestIDFairing::from_fn(move |_| counted.fetch_add(1, Ordering::SeqCst) + 100)
+                    .with_header_name("X-Trace"),
+            )
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        assert_eq!(resp.headers().get_one("X-Trace"), Some("100"));
+        assert_eq!(resp.into_string(), Some("100".to_string()));
+        assert_eq!(c.get("/").dispatch().into_string(), Some("101".to_string()));
+
+        // Inherited IDs don't need generating.
+        let resp = c.get("/").header(Header::new("X-Trace", "1234")).dispatch();
+        assert_eq!(resp.into_string(), Some("1234".to_string()));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn counter_ids_increase() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_counter())
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let ids: Vec<u64> = (0..5)
+            .map(|_| c.get("/").dispatch().into_string().unwrap().parse().unwrap())
+            .collect();
+
+        assert!(ids[0] >= 1);
+        for pair in ids.windows(2) {
+            assert!(pair[0] < pair[1]);
+        }
+    }
+
+    #[rocket::async_test]
+    async fn counter_from_concurrent_requests() {
+        use rocket::futures::future::join_all;
+        use rocket::local::asynchronous::Client;
+
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_counter_from(1000))
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).await.unwrap();
+
+        let responses = join_all((0..50).map(|_| c.get("/").dispatch())).await;
+        let mut ids = Vec::new();
+        for resp in responses {
+            let header: u64 = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().parse().unwrap();
+            assert_eq!(resp.into_string().await, Some(header.to_string()));
+            ids.push(header);
+        }
+        ids.sort();
+        assert_eq!(ids, (1000..1050).collect::<Vec<u64>>());
+
+        // Later requests get later IDs.
+        let next = c.get("/").dispatch().await.into_string().await.unwrap();
+        assert_eq!(next, "1050");
+    }
+
+    #[test]
+    fn instance_counters() {
+        let ids = || {
+            let rkt = rocket::build()
+                .attach(RequestIDFairing::default().with_instance_counter(32))
+                .mount("/", routes![req_id]);
+            let c = Client::tracked(rkt).unwrap();
+            (0..3)
+                .map(|_| c.get("/").dispatch().into_string().unwrap().parse().unwrap())
+                .collect::<Vec<u64>>()
+        };
+
+        let first = ids();
+        assert_eq!(first.iter().map(|id| id & 0xffff_ffff).collect::<Vec<_>>(), [1, 2, 3]);
+        assert!(first.iter().all(|id| id >> 32 == first[0] >> 32));
+        // Two instances could pick the same random bits, but not three times running.
+        let others = [ids(), ids(), ids()];
+        assert!(others.iter().any(|other| other[0] >> 32 != first[0] >> 32));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid instance counter random bits")]
+    fn rejects_instance_counters_without_a_sequence() {
+        RequestIDFairing::default().with_instance_counter(64);
+    }
+
+    #[test]
+    fn narrow_ids() {
+        let ids = |fairing: RequestIDFairing| {
+            let rkt = rocket::build().attach(fairing).mount("/", routes![req_id]);
+            let c = Client::tracked(rkt).unwrap();
+            (0..20)
+                .map(|_| c.get("/").dispatch().into_string().unwrap().parse().unwrap())
+                .collect::<Vec<u64>>()
+        };
+
+        let narrow = ids(RequestIDFairing::default().with_bits(32));
+        assert!(narrow.iter().all(|&id| id <= u32::MAX as u64));
+        assert!(narrow.iter().any(|&id| id > u16::MAX as u64));
+        let tiny = ids(RequestIDFairing::default().with_bits(3));
+        assert!(tiny.iter().all(|&id| id < 8));
+        let counted = ids(RequestIDFairing::default().with_counter_from(6).with_bits(3));
+        // 0 is reserved, so it's skipped.
+        assert_eq!(counted[..4], [6, 7, 1, 2]);
+        assert!(ids(RequestIDFairing::default().with_bits(64)).iter().any(|&id| id >> 32 != 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid request id bit width")]
+    fn rejects_invalid_bit_widths() {
+        RequestIDFairing::default().with_bits(65);
+    }
+
+    #[test]
+    fn instance_ids() {
+        let ids = |fairing: RequestIDFairing| {
+            let rkt = rocket::build().attach(fairing).mount("/", routes![req_id]);
+            let c = Client::tracked(rkt).unwrap();
+            (0..10)
+                .map(|_| c.get("/").dispatch().into_string().unwrap().parse().unwrap())
+                .collect::<Vec<u64>>()
+        };
+
+        let random = ids(RequestIDFairing::default().with_instance_id(7, 8));
+        assert!(random.iter().all(|&id| instance_id(id, 8) == 7));
+        assert!(random.iter().any(|&id| id >> 8 != random[0] >> 8));
+        let counted = ids(RequestIDFairing::default().with_counter_from(1).with_instance_id(7, 8));
+        assert_eq!(counted[..3], [1 << 8 | 7, 2 << 8 | 7, 3 << 8 | 7]);
+        let narrow = ids(RequestIDFairing::default().with_bits(32).with_instance_id(0x3ff, 10));
+        assert!(narrow.iter().all(|&id| id <= u32::MAX as u64 && instance_id(id, 10) == 0x3ff));
+
+        let hosted = ids(RequestIDFairing::default().with_hostname_instance_id(16));
+        let again = ids(RequestIDFairing::default().with_hostname_instance_id(16));
+        if hostname().is_some() {
+            assert_eq!(instance_id(hosted[0], 16), instance_id(again[0], 16));
+        }
+        assert!(hosted.iter().all(|&id| instance_id(id, 16) == instance_id(hosted[0], 16)));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid instance id 256 for 8 bits")]
+    fn rejects_oversized_instance_ids() {
+        RequestIDFairing::default().with_instance_id(256, 8);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid request id bit width 8")]
+    fn rejects_bit_widths_without_room_for_ids() {
+        RequestIDFairing::default().with_instance_id(1, 8).with_bits(8);
+    }
+
+    #[test]
+    fn obfuscated_counters() {
+        let key = 0xfeed_f00d_dead_beef;
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_obfuscated_counter(key))
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let ids: Vec<u64> = (0..3)
+            .map(|_| c.get("/").dispatch().into_string().unwrap().parse().unwrap())
+            .collect();
+        assert!(ids.iter().all(|&id| id > 1 << 32));
+        assert_eq!(ids.iter().map(|&id| deobfuscate(id, key)).collect::<Vec<_>>(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn id_pools() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_id_pool(4, 2))
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        // More requests than the pool holds, so some of them may find it empty.
+        let ids: HashSet<String> = (0..50)
+            .map(|_| c.get("/").dispatch().into_string().unwrap())
+            .collect();
+        assert_eq!(ids.len(), 50);
+        assert!(ids.iter().all(|id| id != "0"));
+        // This stops the pool's thread.
+        drop(c);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid id pool size 4 with refill threshold 5")]
+    fn rejects_invalid_id_pools() {
+        RequestIDFairing::default().with_id_pool(4, 5);
+    }
+
+    #[test]
+    fn secure_ids() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_secure_ids())
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let header = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        assert_eq!(resp.into_string(), Some(header.clone()));
+        let ids: HashSet<String> = (0..5)
+            .map(|_| c.get("/").dispatch().into_string().unwrap())
+            .collect();
+        assert_eq!(ids.len(), 5);
+        assert!(!ids.contains(&header));
+        assert!(ids.iter().all(|id| id != "0"));
+    }
+
+    #[test]
+    fn seeded_ids() {
+        let ids = |seed| {
+            let rkt = rocket::build()
+                .attach(RequestIDFairing::default().with_seed(seed))
+                .mount("/", routes![req_id]);
+            let c = Client::tracked(rkt).unwrap();
+            (0..3)
+                .map(|_| c.get("/").dispatch().into_string().unwrap())
+                .collect::<Vec<String>>()
+        };
+
+        let first = ids(42);
+        assert_eq!(first, ids(42));
+        assert_ne!(first[0], first[1]);
+        assert_ne!(first, ids(43));
+    }
+
+    #[rocket::async_test]
+    async fn rng_concurrent_requests() {
+        use rocket::futures::future::join_all;
+        use rocket::local::asynchronous::Client;
+
+        let ids = |fairing| async move {
+            let rkt = rocket::build().attach(fairing).mount("/", routes![req_id]);
+            let c = Client::tracked(rkt).await.unwrap();
+            let responses = join_all((0..50).map(|_| c.get("/").dispatch())).await;
+            let mut ids = Vec::new();
+            for resp in responses {
+                ids.push(resp.into_string().await.unwrap());
+            }
+            ids.sort();
+            ids
+        };
+
+        let seeded = || RequestIDFairing::default().with_rng(StdRng::seed_from_u64(42));
+        let first = ids(seeded()).await;
+        assert_eq!(first.iter().collect::<HashSet<_>>().len(), 50);
+        assert_eq!(first, ids(seeded()).await);
+        assert_eq!(first, ids(RequestIDFairing::default().with_seed(42)).await);
+    }
+
+    #[get("/")]
+    fn parent_ids(id: RequestID, parent: Option<ParentRequestID>) -> String {
+        match parent {
+            Some(parent) => format!("{} {}", id, parent),
+            None => format!("{} none", id),
+        }
+    }
+
+    #[test]
+    fn oversized_inbound_policies() {
+        let inbound = "12345678901234567890123";
+        let dispatch = |policy| {
+            let rkt = rocket::build()
+                .attach(
+                    RequestIDFairing::default()
+                        .with_max_inbound_len(8)
+                        .with_oversized_inbound_policy(policy),
+                )
+                .mount("/", routes![parent_ids]);
+            let c = Client::tracked(rkt).unwrap();
+            let resp = c.get("/")
+                .header(Header::new(REQUEST_ID_HEADER, inbound))
+                .dispatch();
+            let header = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+            let body = resp.into_string().unwrap();
+            assert!(body.starts_with(&format!("{} ", header)));
+            body
+        };
+
+        let body = dispatch(OversizedInboundPolicy::Reject);
+        assert!(body.ends_with(" none"));
+        assert!(!body.starts_with("12345678 "));
+
+        let body = dispatch(OversizedInboundPolicy::Truncate(4));
+        assert_eq!(body, format!("1234 {}", inbound));
+
+        let body = dispatch(OversizedInboundPolicy::Hash);
+        assert_eq!(body, format!("{} {}", fnv1a(inbound) as u64, inbound));
+        assert_eq!(dispatch(OversizedInboundPolicy::Hash), body);
+    }
+
+    #[test]
+    fn truncation_respects_validation() {
+        let rkt = rocket::build()
+            .attach(
+                RequestIDFairing::default()
+                    .with_max_inbound_len(8)
+                    .with_oversized_inbound_policy(OversizedInboundPolicy::Truncate(6)),
+            )
+            .mount("/", routes![parent_ids]);
+        let c = Client::tracked(rkt).unwrap();
+
+        // The part after the truncation has to be safe too, since it's kept as the parent.
+        for inbound in ["123456\n890123", "123456é890123"].iter() {
+            let resp = c.get("/")
+                .header(Header::new(REQUEST_ID_HEADER, *inbound))
+                .dispatch();
+            let body = resp.into_string().unwrap();
+            assert!(body.ends_with(" none"), "{:?} gave {:?}", inbound, body);
+            assert!(!body.starts_with("123456 "));
+        }
+
+        // The truncated ID has to parse, too.
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "12abc67890123"))
+            .dispatch();
+        let body = resp.into_string().unwrap();
+        assert!(body.ends_with(" 12abc67890123"));
+        assert!(!body.starts_with("12abc6 "));
+    }
+
+    #[test]
+    fn fnv1a_hashes() {
+        // Test vectors from the FNV reference implementation.
+        assert_eq!(fnv1a(""), 0x6c62272e07bb014262b821756295c58d);
+        assert_eq!(fnv1a("a"), 0xd228cb696f1a8caf78912b704e4a8964);
+    }
+
+    #[test]
+    fn parent_request_id() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_inbound_policy(InboundPolicy::Parent))
+            .mount("/", routes![parent_ids]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "1234"))
+            .dispatch();
+        let header = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        assert_ne!(header, "1234");
+        assert_eq!(resp.into_string(), Some(format!("{} 1234", header)));
+
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "not-a-number"))
+            .dispatch();
+        assert!(resp.into_string().unwrap().ends_with(" not-a-number"));
+
+        let resp = c.get("/").dispatch();
+        assert!(resp.into_string().unwrap().ends_with(" none"));
+    }
+
+    #[test]
+    fn parent_request_id_with_inherited_id() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing)
+            .mount("/", routes![parent_ids]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "1234"))
+            .dispatch();
+        assert_eq!(resp.into_string(), Some("1234 1234".to_string()));
+    }
+
+    lazy_static!{
+        static ref IN_FLIGHT: Mutex<HashSet<u64>> = Mutex::new(HashSet::new());
+    }
+
+    #[get("/")]
+    fn in_flight(id: RequestID) -> &'static str {
+        let id = u64::from(id);
+        if !IN_FLIGHT.lock().unwrap().insert(id) {
+            return "duplicate";
+        }
+        thread::sleep(Duration::from_millis(1));
+        IN_FLIGHT.lock().unwrap().remove(&id);
+        "unique"
+    }
+
+    // Counter IDs can't collide, so two in-flight requests sharing an ID can only mean they shared
+    // an entry.
+    #[test]
+    fn concurrent_requests_have_distinct_ids() {
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                thread::spawn(|| {
+                    let rkt = rocket::build()
+                        .attach(RequestIDFairing::default().with_counter())
+                        .mount("/", routes![in_flight]);
+                    let c = Client::tracked(rkt).unwrap();
+
+                    for _ in 0..50 {
+                        let resp = c.get("/").dispatch();
+                        assert_eq!(resp.into_string(), Some("unique".to_string()));
+                    }
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn random_ids_across_threads() {
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                thread::spawn(|| {
+                    let rkt = rocket::build()
+                        .attach(RequestIDFairing::default())
+                        .mount("/", routes![req_id]);
+                    let c = Client::tracked(rkt).unwrap();
+                    (0..250)
+                        .map(|_| c.get("/").dispatch().into_string().unwrap())
+                        .collect::<Vec<String>>()
+                })
+            })
+            .collect();
+
+        let mut ids = HashSet::new();
+        for thread in threads {
+            for id in thread.join().unwrap() {
+                assert!(ids.insert(id.clone()), "duplicate id {}", id);
+            }
+        }
+        assert_eq!(ids.len(), 2000);
+    }
+
+    #[get("/")]
+    fn trace_ids(trace: TraceParent) -> String {
+        format!("{:032x} {:016x}", trace.trace_id(), trace.span_id())
+    }
+
+    #[cfg(feature = "opentelemetry")]
+    #[test]
+    fn opentelemetry_spans() {
+        use opentelemetry::trace::{SpanId, TraceId};
+        use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+        use opentelemetry_sdk::trace::TracerProvider;
+
+        let exporter = InMemorySpanExporter::default();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        opentelemetry::global::set_tracer_provider(provider.clone());
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_trace_context())
+            .mount("/", routes![trace_ids]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let inbound = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+        let resp = c.get("/")
+            .header(Header::new(TRACEPARENT_HEADER, inbound))
+            .header(Header::new(TRACESTATE_HEADER, "rojo=00f067aa0ba902b7"))
+            .dispatch();
+        let id = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        let body = resp.into_string().unwrap();
+        let missing = c.get("/missing").dispatch();
+        let missing_id = missing.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+
+        // The simple span processor exports spans on a background thread.
+        for result in provider.force_flush() {
+            result.unwrap();
+        }
+        // Other tests' requests may have spans too.
+        let spans = exporter.get_finished_spans().unwrap();
+        let span_for = |id: &str| {
+            spans
+                .iter()
+                .find(|span| {
+                    span.attributes.iter().any(|attribute| {
+                        attribute.key.as_str() == "request_id" && attribute.value.as_str() == id
+                    })
+                })
+                .unwrap()
+        };
+        let status = |span: &opentelemetry_sdk::export::trace::SpanData| {
+            span.attributes
+                .iter()
+                .find(|attribute| attribute.key.as_str() == "http.response.status_code")
+                .map(|attribute| attribute.value.as_str().into_owned())
+        };
+
+        let span = span_for(&id);
+        assert_eq!(span.name, "GET");
+        assert_eq!(
+            span.span_context.trace_id(),
+            TraceId::from_hex("0af7651916cd43dd8448eb211c80319c").unwrap()
+        );
+        assert_eq!(span.parent_span_id, SpanId::from_hex("b7ad6b7169203331").unwrap());
+        assert_eq!(span.span_context.trace_state().header(), "rojo=00f067aa0ba902b7");
+        assert_eq!(span.span_context.span_id(), SpanId::from_hex(&body[33..]).unwrap());
+        assert_eq!(status(span), Some("200".to_string()));
+        assert_eq!(status(span_for(&missing_id)), Some("404".to_string()));
+    }
+
+    #[test]
+    fn trace_context() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_trace_context())
+            .mount("/", routes![trace_ids]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let inbound = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+        let resp = c.get("/")
+            .header(Header::new(TRACEPARENT_HEADER, inbound))
+            .header(Header::new(TRACESTATE_HEADER, "rojo=00f067aa0ba902b7"))
+            .dispatch();
+        let outbound = resp.headers().get_one(TRACEPARENT_HEADER).unwrap().to_string();
+        assert_eq!(
+            resp.headers().get_one(TRACESTATE_HEADER),
+            Some("rojo=00f067aa0ba902b7")
+        );
+        let body = resp.into_string().unwrap();
+
+        assert_eq!(&outbound[3..35], "0af7651916cd43dd8448eb211c80319c");
+        assert_ne!(&outbound[36..52], "b7ad6b7169203331");
+        assert_eq!(body, format!("{} {}", &outbound[3..35], &outbound[36..52]));
+
+        let resp = c.get("/")
+            .header(Header::new(TRACEPARENT_HEADER, "00-not-a-trace-01"))
+            .dispatch();
+        let outbound = resp.headers().get_one(TRACEPARENT_HEADER).unwrap().to_string();
+        assert_eq!(resp.into_string().unwrap()[..32], outbound[3..35]);
+    }
+
+    #[test]
+    fn no_trace_context_by_default() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing)
+            .mount("/", routes![trace_ids]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        assert_eq!(resp.status(), Status::InternalServerError);
+        assert_eq!(resp.headers().get_one(TRACEPARENT_HEADER), None);
+    }
+
+    #[get("/")]
+    fn b3_ids(b3: B3) -> String {
+        format!("{:x} {:016x}", b3.trace_id(), b3.span_id())
+    }
+
+    #[test]
+    fn b3() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_b3())
+            .mount("/", routes![b3_ids]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let inbound = "80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1-1-05e3ac9a4f6e3b90";
+        let resp = c.get("/").header(Header::new("B3", inbound)).dispatch();
+        let outbound = resp.headers().get_one(B3_HEADER).unwrap().to_string();
+        let body = resp.into_string().unwrap();
+
+        assert_eq!(&outbound[..32], "80f198ee56343ba864fe8b2a57d3eff7");
+        assert_ne!(&outbound[33..49], "e457b5a2e4d86bd1");
+        assert_eq!(&outbound[49..], "-1-e457b5a2e4d86bd1");
+        assert_eq!(body, format!("{} {}", &outbound[..32], &outbound[33..49]));
+
+        let resp = c.get("/")
+            .header(Header::new(B3_HEADER, "a3ce929d0e0e4736-00f067aa0ba902b7-0"))
+            .dispatch();
+        let outbound = resp.headers().get_one(B3_HEADER).unwrap().to_string();
+        assert!(outbound.starts_with("a3ce929d0e0e4736-"));
+        assert!(outbound.ends_with("-0-00f067aa0ba902b7"));
+        assert!(resp.into_string().unwrap().starts_with("a3ce929d0e0e4736 "));
+
+        let resp = c.get("/").header(Header::new(B3_HEADER, "0")).dispatch();
+        let outbound = resp.headers().get_one(B3_HEADER).unwrap();
+        assert_eq!(outbound.len(), 51);
+        assert!(outbound.ends_with("-0"));
+    }
+
+    #[test]
+    fn b3_only_from_trusted_requests() {
+        let rkt = rocket::build()
+            .attach(
+                RequestIDFairing::default()
+                    .with_b3()
+                    .with_inbound_policy(InboundPolicy::Ignore),
+            )
+            .mount("/", routes![b3_ids]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let inbound = "80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1-1";
+        let resp = c.get("/").header(Header::new(B3_HEADER, inbound)).dispatch();
+        let outbound = resp.headers().get_one(B3_HEADER).unwrap();
+        assert!(!outbound.starts_with("80f198ee56343ba864fe8b2a57d3eff7"));
+    }
+
+    #[test]
+    fn b3_multi() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_b3_multi())
+            .mount("/", routes![b3_ids]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/")
+            .header(Header::new("x-b3-traceid", "a3ce929d0e0e4736"))
+            .header(Header::new("x-b3-spanid", "00f067aa0ba902b7"))
+            .header(Header::new("x-b3-sampled", "1"))
+            .dispatch();
+        let span_id = resp.headers().get_one("X-B3-SpanId").unwrap().to_string();
+        assert_eq!(resp.headers().get_one("X-B3-TraceId"), Some("a3ce929d0e0e4736"));
+        assert_eq!(resp.headers().get_one("X-B3-ParentSpanId"), Some("00f067aa0ba902b7"));
+        assert_eq!(resp.headers().get_one("X-B3-Sampled"), Some("1"));
+        assert_eq!(resp.headers().get_one(B3_HEADER), None);
+        assert_ne!(span_id, "00f067aa0ba902b7");
+        assert_eq!(resp.into_string(), Some(format!("a3ce929d0e0e4736 {}", span_id)));
+
+        let resp = c.get("/").dispatch();
+        assert_eq!(resp.headers().get_one("X-B3-TraceId").unwrap().len(), 32);
+        assert_eq!(resp.headers().get_one("X-B3-ParentSpanId"), None);
+    }
+
+    #[test]
+    fn b3_both_formats() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_b3().with_b3_multi())
+            .mount("/", routes![b3_ids]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/")
+            .header(Header::new(B3_HEADER, "80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1"))
+            .header(Header::new("X-B3-TraceId", "a3ce929d0e0e4736"))
+            .dispatch();
+        let single = resp.headers().get_one(B3_HEADER).unwrap();
+        assert!(single.starts_with("80f198ee56343ba864fe8b2a57d3eff7-"));
+        assert_eq!(
+            resp.headers().get_one("X-B3-TraceId"),
+            Some("80f198ee56343ba864fe8b2a57d3eff7")
+        );
+        assert_eq!(resp.headers().get_one("X-B3-SpanId"), Some(&single[33..49]));
+    }
+
+    #[test]
+    fn b3_propagation() {
+        let rkt = rocket::build()
+            .attach(
+                RequestIDFairing::default()
+                    .with_b3_propagation()
+                    .with_format(IdFormat::Hex),
+            )
+            .mount("/", routes![req_id_display]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/")
+            .header(Header::new("x-b3-traceid", "a3ce929d0e0e4736"))
+            .header(Header::new("x-b3-spanid", "00f067aa0ba902b7"))
+            .dispatch();
+        assert_eq!(resp.headers().get_one(REQUEST_ID_HEADER), Some("a3ce929d0e0e4736"));
+        assert_eq!(resp.headers().get_one("X-B3-TraceId"), Some("a3ce929d0e0e4736"));
+        assert_eq!(resp.into_string(), Some("a3ce929d0e0e4736".to_string()));
+
+        let resp = c.get("/")
+            .header(Header::new("x-b3-traceid", "80f198ee56343ba864fe8b2a57d3eff7"))
+            .dispatch();
+        assert_eq!(resp.headers().get_one(REQUEST_ID_HEADER), Some("64fe8b2a57d3eff7"));
+        assert_eq!(
+            resp.headers().get_one("X-B3-TraceId"),
+            Some("80f198ee56343ba864fe8b2a57d3eff7")
+        );
+
+        // An inbound ID header takes precedence.
+        let resp = c.get("/")
+            .header(Header::new("x-b3-traceid", "a3ce929d0e0e4736"))
+            .header(Header::new(REQUEST_ID_HEADER, "00000000000004d2"))
+            .dispatch();
+        assert_eq!(resp.into_string(), Some("00000000000004d2".to_string()));
+    }
+
+    #[test]
+    fn b3_propagation_without_b3_headers() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_b3_propagation())
+            .mount("/", routes![req_id_display]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let id = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        assert!(id.parse::<u64>().is_ok());
+        assert_eq!(resp.headers().get_one("X-B3-TraceId").unwrap().len(), 32);
+        assert_eq!(resp.into_string(), Some(id.clone()));
+        let second = c.get("/").dispatch().into_string().unwrap();
+        assert_ne!(id, second);
+
+        let resp = c.get("/")
+            .header(Header::new("x-b3-traceid", "not a trace id"))
+            .dispatch();
+        assert!(resp.into_string().unwrap().parse::<u64>().is_ok());
+
+        let rkt = rocket::build()
+            .attach(
+                RequestIDFairing::default()
+                    .with_b3_propagation()
+                    .with_inbound_policy(InboundPolicy::Ignore),
+            )
+            .mount("/", routes![req_id_display]);
+        let c = Client::tracked(rkt).unwrap();
+        let resp = c.get("/")
+            .header(Header::new("x-b3-traceid", "a3ce929d0e0e4736"))
+            .dispatch();
+        assert_ne!(resp.into_string(), Some(0xa3ce929d0e0e4736u64.to_string()));
+    }
+
+    #[get("/")]
+    fn amzn_root(trace: AmznTraceId) -> String {
+        trace.root().to_string()
+    }
+
+    #[test]
+    fn amzn_trace_id() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_amzn_trace_id())
+            .mount("/", routes![amzn_root]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let inbound = "Root=1-5759e988-bd862e3fe1be46a994272793;Sampled=1";
+        let resp = c.get("/")
+            .header(Header::new(AMZN_TRACE_ID_HEADER, inbound))
+            .dispatch();
+        let outbound = resp.headers().get_one(AMZN_TRACE_ID_HEADER).unwrap().to_string();
+        assert_eq!(
+            resp.into_string(),
+            Some("1-5759e988-bd862e3fe1be46a994272793".to_string())
+        );
+        assert!(outbound.starts_with(&format!("{};Self=1-", inbound)));
+
+        let resp = c.get("/").dispatch();
+        let outbound = resp.headers().get_one(AMZN_TRACE_ID_HEADER).unwrap().to_string();
+        let root = resp.into_string().unwrap();
+        assert!(outbound.starts_with(&format!("Root={};Self=1-", root)));
+    }
+
+    #[get("/")]
+    fn causes(chain: CausationChain) -> String {
+        format!("{} {}", chain.causes().join(","), chain.header().value())
+    }
+
+    #[test]
+    fn causation_chain() {
+        let rkt = rocket::build()
+            .attach(
+                RequestIDFairing::default()
+                    .with_inbound_policy(InboundPolicy::Parent)
+                    .with_causation_chain()
+                    .with_max_causation_depth(3),
+            )
+            .mount("/", routes![causes]);
+        let c = Client::tracked(rkt).unwrap();
+
+        // The first hop is caused by the client's request.
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "origin"))
+            .dispatch();
+        let first = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        let body = resp.into_string().unwrap();
+        assert_eq!(body, format!("origin origin,{}", first));
+
+        // It calls the second, passing on its own ID and the chain.
+        let outbound = body.split(' ').nth(1).unwrap().to_string();
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, first.clone()))
+            .header(Header::new(CAUSATION_ID_HEADER, outbound))
+            .dispatch();
+        let second = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        assert_eq!(
+            resp.into_string(),
+            Some(format!("origin,{} origin,{},{}", first, first, second))
+        );
+
+        // Only the newest IDs are kept.
+        let resp = c.get("/")
+            .header(Header::new(CAUSATION_ID_HEADER, "a,b,c,d,e"))
+            .dispatch();
+        let id = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        assert_eq!(resp.into_string(), Some(format!("c,d,e d,e,{}", id)));
+
+        // Empty entries are skipped, even though the default validator accepts them.
+        let resp = c.get("/")
+            .header(Header::new(CAUSATION_ID_HEADER, "a,,b, ,c,"))
+            .dispatch();
+        let id = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        assert_eq!(resp.into_string(), Some(format!("a,b,c b,c,{}", id)));
+    }
+
+    #[test]
+    fn causation_chain_not_tracked_by_default() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing)
+            .mount("/", routes![causes]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/")
+            .header(Header::new(CAUSATION_ID_HEADER, "a"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::InternalServerError);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid causation chain depth")]
+    fn rejects_zero_causation_depth() {
+        RequestIDFairing::default().with_max_causation_depth(0);
+    }
+
+    enum Trace {}
+    impl Namespace for Trace {}
+
+    enum Session {}
+    impl Namespace for Session {}
+
+    #[get("/")]
+    fn namespaced(
+        id: RequestID,
+        trace: RequestID<Trace>,
+        session: RequestID<Session>,
+        again: RequestID<Session>,
+    ) -> String {
+        assert_eq!(session, again);
+        format!("{} {} {}", id, trace, session)
+    }
+
+    #[test]
+    fn namespaced_ids() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default())
+            .attach(
+                RequestIDFairing::default()
+                    .in_namespace::<Trace>()
+                    .with_header_name("X-Trace-ID"),
+            )
+            .attach(
+                RequestIDFairing::default()
+                    .in_namespace::<Session>()
+                    .with_header_name("X-Session-ID")
+                    .with_format(IdFormat::Hex),
+            )
+            .mount("/", routes![namespaced]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").header(Header::new("X-Trace-ID", "1234")).dispatch();
+        let id = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        let session = resp.headers().get_one("X-Session-ID").unwrap().to_string();
+        assert_eq!(resp.headers().get_one("X-Trace-ID"), Some("1234"));
+        assert_eq!(session.len(), 16);
+        assert_ne!(id, "1234");
+        assert_ne!(id, session);
+        assert_eq!(resp.into_string(), Some(format!("{} 1234 {}", id, session)));
+    }
+
+    #[get("/")]
+    fn correlated(id: RequestID, correlation: CorrelationID, again: CorrelationID) -> String {
+        assert_eq!(correlation, again);
+        format!("{} {}", id, correlation)
+    }
+
+    #[test]
+    fn correlation_id() {
+        let rkt = rocket::build()
+            .attach(
+                RequestIDFairing::default()
+                    .with_correlation_id()
+                    .with_inbound_policy(InboundPolicy::Parent),
+            )
+            .mount("/", routes![correlated]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/")
+            .header(Header::new(CORRELATION_ID_HEADER, "interaction-1"))
+            .header(Header::new(REQUEST_ID_HEADER, "1234"))
+            .dispatch();
+        let id = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        assert_ne!(id, "1234");
+        assert_eq!(resp.headers().get_one(CORRELATION_ID_HEADER), Some("interaction-1"));
+        assert_eq!(resp.into_string(), Some(format!("{} interaction-1", id)));
+
+        // Without a valid correlation ID, the request starts a new interaction.
+        for header in [None, Some("not valid"), Some("")].iter() {
+            let mut req = c.get("/");
+            if let Some(header) = *header {
+                req = req.header(Header::new(CORRELATION_ID_HEADER, header));
+            }
+            let resp = req.dispatch();
+            let id = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+            assert_eq!(resp.headers().get_one(CORRELATION_ID_HEADER), Some(&id[..]));
+            assert_eq!(resp.into_string(), Some(format!("{} {}", id, id)));
+        }
+    }
+
+    #[test]
+    fn trace_correlation_id() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_trace_correlation_id())
+            .mount("/", routes![correlated]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let inbound = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+        let resp = c.get("/")
+            .header(Header::new(TRACEPARENT_HEADER, inbound))
+            .dispatch();
+        let outbound = resp.headers().get_one(TRACEPARENT_HEADER).unwrap().to_string();
+        assert_eq!(&outbound[3..35], "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(
+            resp.headers().get_one(CORRELATION_ID_HEADER),
+            Some("0af7651916cd43dd8448eb211c80319c")
+        );
+
+        // Without a traceparent, the request starts a new trace, which correlates it.
+        let resp = c.get("/").dispatch();
+        let outbound = resp.headers().get_one(TRACEPARENT_HEADER).unwrap().to_string();
+        let id = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        assert_eq!(resp.headers().get_one(CORRELATION_ID_HEADER), Some(&outbound[3..35]));
+        assert_eq!(resp.into_string(), Some(format!("{} {}", id, &outbound[3..35])));
+
+        // An explicit correlation ID still takes precedence.
+        let resp = c.get("/")
+            .header(Header::new(TRACEPARENT_HEADER, inbound))
+            .header(Header::new(CORRELATION_ID_HEADER, "interaction-1"))
+            .dispatch();
+        assert_eq!(resp.headers().get_one(CORRELATION_ID_HEADER), Some("interaction-1"));
+    }
+
+    #[test]
+    fn no_correlation_id_by_default() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing)
+            .mount("/", routes![correlated]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/")
+            .header(Header::new(CORRELATION_ID_HEADER, "interaction-1"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::InternalServerError);
+        assert!(!resp.headers().contains(CORRELATION_ID_HEADER));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serializes_ids() {
+        use serde_test::{assert_de_tokens_error, assert_tokens, Token};
+
+        let id = RequestID {
+            id: 1234,
+            high: 0,
+            kind: IdKind::Number,
+            prefix: "",
+            namespace: PhantomData,
+        };
+        assert_tokens(&id, &[Token::U64(1234)]);
+        assert_de_tokens_error::<RequestID>(
+            &[Token::Bool(true)],
+            if cfg!(feature = "uuid") {
+                concat!(
+                    "invalid type: boolean `true`, ",
+                    "expected a u64, u128, UUID, ULID, KSUID or xid request id"
+                )
+            } else {
+                "invalid type: boolean `true`, expected a u64, u128, ULID, KSUID or xid request id"
+            },
+        );
+    }
+
+    #[cfg(all(feature = "serde", feature = "uuid"))]
+    #[test]
+    fn serializes_uuid_ids() {
+        use serde_test::{assert_de_tokens_error, assert_tokens, Token};
+
+        let id = RequestID {
+            id: 0x550e8400_e29b_41d4_a716_446655440000,
+            high: 0,
+            kind: IdKind::Uuid,
+            prefix: "",
+            namespace: PhantomData,
+        };
+        assert_tokens(&id, &[Token::Str("550e8400-e29b-41d4-a716-446655440000")]);
+        assert_de_tokens_error::<RequestID>(
+            &[Token::Str("1234")],
+            concat!(
+                "invalid value: string \"1234\", ",
+                "expected a u64, u128, UUID, ULID, KSUID or xid request id"
+            ),
+        );
+    }
+
+    #[test]
+    fn formats_ids() {
+        let decimal = RequestID {
+            id: 0xa1b2c3d4e,
+            high: 0,
+            kind: IdKind::Number,
+            prefix: "",
+            namespace: PhantomData,
+        };
+        assert_eq!(decimal.to_string(), "43405557070");
+        assert_eq!(decimal.to_hex(), "0000000a1b2c3d4e");
+
+        let hex: RequestID = RequestID {
+            id: 0xa1b2c3d4e,
+            high: 0,
+            kind: IdKind::HexNumber,
+            prefix: "",
+            namespace: PhantomData,
+        };
+        assert_eq!(hex.to_string(), "0000000a1b2c3d4e");
+        assert_eq!(IdKind::HexNumber.parse("0000000A1B2C3D4E"), Some((0, 0xa1b2c3d4e)));
+        assert_eq!(IdKind::HexNumber.parse("a1b2c3d4e"), None);
+        assert_eq!(IdKind::HexNumber.parse("+000000a1b2c3d4e"), None);
+
+        // Every kind of ID formats as hex the same way.
+        for id in [decimal, hex].iter() {
+            assert_eq!(format!("{:x}", id), "0000000a1b2c3d4e");
+            assert_eq!(format!("{:X}", id), "0000000A1B2C3D4E");
+            assert_eq!(format!("{:#x}", id), "0x0000000a1b2c3d4e");
+        }
+        let wide = RequestID::from_u128(0xa1b2c3d4e);
+        assert_eq!(format!("{:x}", wide), "00000000000000000000000a1b2c3d4e");
+        assert_eq!(format!("{:X}", wide), "00000000000000000000000A1B2C3D4E");
+    }
+
+    #[test]
+    fn uppercase_hex_ids() {
+        let rkt = rocket::build()
+            .attach(
+                RequestIDFairing::default()
+                    .with_format(IdFormat::Hex)
+                    .with_uppercase_hex(),
+            )
+            .mount("/", routes![req_id_display]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let header = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        assert_eq!(resp.into_string(), Some(header.clone()));
+        assert_eq!(header.len(), 16);
+        assert!(!header.contains(|c: char| c.is_ascii_lowercase()));
+
+        for inbound in ["0000000a1b2c3d4e", "0000000A1B2C3D4E"].iter() {
+            let resp = c.get("/")
+                .header(Header::new(REQUEST_ID_HEADER, *inbound))
+                .dispatch();
+            assert_eq!(resp.headers().get_one(REQUEST_ID_HEADER), Some("0000000A1B2C3D4E"));
+        }
+
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_128_bit_ids().with_uppercase_hex())
+            .mount("/", routes![req_id_display]);
+        let c = Client::tracked(rkt).unwrap();
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "80f198ee56343ba864fe8b2a57d3eff7"))
+            .dispatch();
+        assert_eq!(resp.into_string(), Some("80F198EE56343BA864FE8B2A57D3EFF7".to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid uppercase hex")]
+    fn rejects_uppercasing_non_hex_ids() {
+        RequestIDFairing::default().with_uppercase_hex();
+    }
+
+    #[test]
+    fn grouped_ids() {
+        let rkt = rocket::build()
+            .attach(
+                RequestIDFairing::default()
+                    .with_format(IdFormat::Hex)
+                    .with_grouping(4, '-', Some(1)),
+            )
+            .mount("/", routes![req_id])
+            .mount("/display", routes![req_id_display]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let header = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        let id: u64 = resp.into_string().unwrap().parse().unwrap();
+        assert_eq!(header.len(), 2 + 16 + 3);
+        assert_eq!(header.replace('-', ""), format!("1{:016x}", id));
+
+        for inbound in ["1-0000-0000-029a-eebe", "0000-0000-029A-EEBE", "00000000029aeebe"].iter() {
+            let resp = c.get("/")
+                .header(Header::new(REQUEST_ID_HEADER, *inbound))
+                .dispatch();
+            assert_eq!(resp.into_string(), Some("43708094".to_string()), "{}", inbound);
+            let resp = c.get("/display")
+                .header(Header::new(REQUEST_ID_HEADER, *inbound))
+                .dispatch();
+            assert_eq!(resp.into_string(), Some("1-0000-0000-029a-eebe".to_string()));
+        }
+        let resp = c.get("/display")
+            .header(Header::new(REQUEST_ID_HEADER, "00-00-0000-029a-eebe"))
+            .dispatch();
+        assert_ne!(resp.into_string(), Some("1-0000-0000-029a-eebe".to_string()));
+
+        let rkt = rocket::build()
+            .attach(
+                RequestIDFairing::default()
+                    .with_128_bit_ids()
+                    .with_grouping(8, '.', None),
+            )
+            .mount("/", routes![req_id_display]);
+        let c = Client::tracked(rkt).unwrap();
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "581cf771a006649127e371903a2de979"))
+            .dispatch();
+        assert_eq!(
+            resp.into_string(),
+            Some("581cf771.a0066491.27e37190.3a2de979".to_string())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid grouping: only hex ids can be grouped")]
+    fn rejects_grouping_non_hex_ids() {
+        RequestIDFairing::default().with_grouping(4, '-', None);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid grouping separator ':'")]
+    fn rejects_invalid_grouping_separators() {
+        RequestIDFairing::default()
+            .with_format(IdFormat::Hex)
+            .with_grouping(4, ':', None);
+    }
+
+    #[test]
+    fn short_ids() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_short_ids())
+            .mount("/", routes![req_id])
+            .mount("/display", routes![req_id_display]);
+        let c = Client::tracked(rkt).unwrap();
+
+        for _ in 0..20 {
+            let resp = c.get("/").dispatch();
+            let header = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+            assert_eq!(header.len(), 8);
+            let id: u64 = resp.into_string().unwrap().parse().unwrap();
+            assert!(id <= u32::MAX as u64);
+            assert_eq!(header, format!("{:08x}", id));
+        }
+
+        let resp = c.get("/display")
+            .header(Header::new(REQUEST_ID_HEADER, "3FA9C2E1"))
+            .dispatch();
+        assert_eq!(resp.into_string(), Some("3fa9c2e1".to_string()));
+        for inbound in ["3fa9c2e", "3fa9c2e1f", "00000000029aeebe"].iter() {
+            let resp = c.get("/display")
+                .header(Header::new(REQUEST_ID_HEADER, *inbound))
+                .dispatch();
+            assert_ne!(resp.into_string().as_ref().map(|id| &id[..]), Some(*inbound));
+        }
+    }
+
+    #[get("/")]
+    fn timestamps(id: RequestID) -> String {
+        match id.timestamp() {
+            Some(timestamp) => {
+                let since_epoch = timestamp.duration_since(UNIX_EPOCH).unwrap();
+                format!("{} {}", id, since_epoch.as_millis())
+            }
+            None => format!("{} none", id),
+        }
+    }
+
+    #[test]
+    fn timestamped_ids() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_timestamped_ids())
+            .mount("/", routes![timestamps]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let header = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        assert!(header.parse::<u64>().is_ok());
+        let body = resp.into_string().unwrap();
+        let (id, ms) = body.split_once(' ').unwrap();
+        assert_eq!(id, header);
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+        let ms: u128 = ms.parse().unwrap();
+        assert!(ms <= now && now - ms < 1000, "{} {}", ms, now);
+
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default())
+            .mount("/", routes![timestamps]);
+        let c = Client::tracked(rkt).unwrap();
+        assert!(c.get("/").dispatch().into_string().unwrap().ends_with(" none"));
+    }
+
+    #[test]
+    fn base62_ids() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_format(IdFormat::Base62))
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let header = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        let id: u64 = resp.into_string().unwrap().parse().unwrap();
+        assert!(header.len() <= 11);
+        assert_eq!(RequestID::from_base62(&header).map(u64::from), Some(id));
+
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "LygHa16AHYE"))
+            .dispatch();
+        assert_eq!(resp.into_string(), Some((u64::MAX - 1).to_string()));
+        // Past u64::MAX, so a new ID is generated.
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "LygHa16AHYG"))
+            .dispatch();
+        assert_ne!(resp.headers().get_one(REQUEST_ID_HEADER), Some("LygHa16AHYG"));
+
+        let decimal = |id: u64| RequestID {
+            id: id as u128,
+            high: 0,
+            kind: IdKind::Number,
+            prefix: "",
+            namespace: PhantomData,
+        };
+        for &id in [0, 1, 61, 62, 1234567890, u64::MAX].iter() {
+            let parsed = RequestID::from_base62(&decimal(id).to_base62()).unwrap();
+            assert_eq!(parsed.as_u64(), id);
+            assert_eq!(parsed.to_string(), parsed.to_base62());
+        }
+        assert_eq!(decimal(3843).to_base62(), "zz");
+
+        let wide = RequestID::from_u128(0x80f198ee56343ba864fe8b2a57d3eff7);
+        let parsed = RequestID::from_base62(&wide.to_base62()).unwrap();
+        assert_eq!(u128::from(parsed), u128::from(wide));
+        assert_eq!(parsed.to_string(), "80f198ee56343ba864fe8b2a57d3eff7");
+        assert_eq!(RequestID::from_base62("LygHa16AHYG").map(u128::from), Some(1 << 64));
+        assert_eq!(RequestID::from_base62("7n42DGM5Tflk9n8mt7Fhc8"), None);
+        assert_eq!(RequestID::from_base62(""), None);
+        assert_eq!(RequestID::from_base62("0zz"), None);
+    }
+
+    #[test]
+    fn crockford_ids() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_format(IdFormat::Crockford))
+            .mount("/", routes![req_id])
+            .mount("/display", routes![req_id_display]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let header = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        let id: u64 = resp.into_string().unwrap().parse().unwrap();
+        assert!(header.len() <= 13);
+        assert!(!header.contains(|c| "ILOU".contains(c)));
+        assert_eq!(RequestID::from_crockford(&header).map(u64::from), Some(id));
+
+        for inbound in ["19NVDY", "19nvdy", "19N-VDY", "I9NVDY"].iter() {
+            let resp = c.get("/display")
+                .header(Header::new(REQUEST_ID_HEADER, *inbound))
+                .dispatch();
+            assert_eq!(resp.into_string(), Some("19NVDY".to_string()), "{}", inbound);
+        }
+        let resp = c.get("/display")
+            .header(Header::new(REQUEST_ID_HEADER, "19UVDY"))
+            .dispatch();
+        assert_ne!(resp.into_string(), Some("19UVDY".to_string()));
+
+        let id = RequestID::from_crockford("19N-VDY").unwrap();
+        assert_eq!(id.as_u64(), 43707838);
+        assert_eq!(id.to_string(), "19NVDY");
+        assert_eq!(id.to_crockford(), "19NVDY");
+        assert_eq!(RequestID::from_crockford("FZZZZZZZZZZZZ").map(u64::from), Some(u64::MAX));
+        assert_eq!(RequestID::from_crockford("G000000000000"), None);
+    }
+
+    #[test]
+    fn checked_ids() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_format(IdFormat::Base62Checked))
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let header = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        let id: u64 = resp.into_string().unwrap().parse().unwrap();
+        assert!(header.len() <= 12);
+        assert_eq!(RequestID::parse_checked(&header).map(u64::from), Ok(id));
+
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "2xOP8Y"))
+            .dispatch();
+        assert_eq!(resp.into_string(), Some("43707838".to_string()));
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "2xOP8Z"))
+            .dispatch();
+        assert_ne!(resp.into_string(), Some("43707838".to_string()));
+
+        let id = RequestID::parse_checked("2xOP8Y").unwrap();
+        assert_eq!(id.as_u64(), 43707838);
+        assert_eq!(id.to_string(), "2xOP8Y");
+        assert_eq!(RequestID::parse_checked("LygHa16AHYFa").map(u64::from), Ok(u64::MAX));
+
+        // Any one character mistyped as any other is caught, check character included.
+        for valid in ["2xOP8Y", "LygHa16AHYFa", &header[..]].iter() {
+            for i in 0..valid.len() {
+                for &b in base62::ALPHABET.iter() {
+                    let mut typo = valid.as_bytes().to_vec();
+                    if typo[i] == b {
+                        continue;
+                    }
+                    typo[i] = b;
+                    let typo = String::from_utf8(typo).unwrap();
+                    assert_eq!(
+                        RequestID::parse_checked(&typo),
+                        Err(ParseCheckedIDError::ChecksumMismatch),
+                        "{}",
+                        typo
+                    );
+                }
+            }
+        }
+        for invalid in ["", "Y", "2xOP8-", "2x-P8Y", "2xOP8é", "02xOP8Y"].iter() {
+            assert_eq!(
+                RequestID::parse_checked(invalid),
+                Err(ParseCheckedIDError::Invalid),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn prefixes_ids() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_prefix("api-"))
+            .mount("/", routes![req_id_display])
+            .mount("/multiple", routes![multiple]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let header = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        assert!(header.starts_with("api-"));
+        assert!(header["api-".len()..].parse::<u64>().is_ok());
+        assert_eq!(resp.into_string(), Some(header));
+
+        let resp = c.get("/multiple").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert!(resp.headers().get_one(REQUEST_ID_HEADER).unwrap().starts_with("api-"));
+
+        for inbound in ["api-1234", "1234"].iter() {
+            let resp = c.get("/")
+                .header(Header::new(REQUEST_ID_HEADER, *inbound))
+                .dispatch();
+            assert_eq!(resp.headers().get_one(REQUEST_ID_HEADER), Some("api-1234"));
+            assert_eq!(resp.into_string(), Some("api-1234".to_string()));
+        }
+
+        let prefixed = RequestID {
+            id: 1234,
+            high: 0,
+            kind: IdKind::Number,
+            prefix: "api-",
+            namespace: PhantomData,
+        };
+        assert_eq!(prefixed, "1234".parse().unwrap());
+        assert_eq!(prefixed.to_string(), "api-1234");
+        assert_eq!(u64::from(prefixed), 1234);
+        assert_eq!(prefixed.as_u64(), 1234);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid request id prefix")]
+    fn rejects_invalid_prefixes() {
+        RequestIDFairing::default().with_prefix("api ");
+    }
+
+    #[test]
+    fn hex_ids() {
+        let rkt = rocket::build()
+            .attach(
+                RequestIDFairing::default()
+                    .with_format(IdFormat::Hex)
+                    .with_counter(),
+            )
+            .mount("/", routes![req_id_display]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let header = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        assert_eq!(header.len(), 16);
+        assert!(u64::from_str_radix(&header, 16).is_ok());
+        assert_eq!(resp.into_string(), Some(header));
+
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "0000000A1B2C3D4E"))
+            .dispatch();
+        assert_eq!(resp.headers().get_one(REQUEST_ID_HEADER), Some("0000000a1b2c3d4e"));
+        assert_eq!(resp.into_string(), Some("0000000a1b2c3d4e".to_string()));
+    }
+
+    #[test]
+    fn ulid_ids() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_ulids())
+            .mount("/", routes![req_id_display]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let first = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        assert_eq!(resp.into_string(), Some(first.clone()));
+        assert_eq!(first.len(), 26);
+        let second = c.get("/").dispatch().into_string().unwrap();
+        assert!(first < second);
+
+        let id: RequestID = first.parse().unwrap();
+        assert_eq!(id.to_ulid_string(), first);
+        let now = ulid::timestamp_ms(ulid::generate(&mut thread_rng()));
+        let age = now - id.timestamp_ms().unwrap();
+        assert!(age < 1000);
+
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "01arz3ndektsv4rrffq69g5fav"))
+            .dispatch();
+        assert_eq!(resp.into_string(), Some("01ARZ3NDEKTSV4RRFFQ69G5FAV".to_string()));
+
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "1234"))
+            .dispatch();
+        assert_ne!(resp.into_string(), Some("1234".to_string()));
+    }
+
+    #[test]
+    fn converts_ids_to_ulids() {
+        let id: RequestID = "1234".parse().unwrap();
+        assert_eq!(id.to_ulid_string(), "0000000000000000000000016J");
+        assert_eq!(id.timestamp_ms(), None);
+    }
+
+    #[get("/")]
+    fn snowflake_parts(id: RequestID) -> String {
+        format!("{} {:?} {:?}", id, id.worker_id(), id.sequence())
+    }
+
+    #[test]
+    fn snowflake_ids() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_snowflakes(40))
+            .mount("/", routes![snowflake_parts]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let first: u64 = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().parse().unwrap();
+        assert!(first <= i64::MAX as u64);
+        assert!(resp.into_string().unwrap().starts_with(&format!("{} Some(40) Some(", first)));
+        let second = c.get("/").dispatch();
+        let second: u64 = second.headers().get_one(REQUEST_ID_HEADER).unwrap().parse().unwrap();
+        assert!(first < second);
+
+        // Inherited IDs are taken apart, too.
+        let inbound = (1234u64 << 22 | 7 << 12 | 5).to_string();
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, inbound.clone()))
+            .dispatch();
+        assert_eq!(resp.into_string(), Some(format!("{} Some(7) Some(5)", inbound)));
+        let id = RequestID {
+            id: 1234 << 22 | 7 << 12 | 5,
+            high: 0,
+            kind: IdKind::Snowflake(40),
+            prefix: "",
+            namespace: PhantomData,
+        };
+        assert_eq!(id.timestamp_ms(), Some(1_288_834_974_657 + 1234));
+
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, u64::MAX.to_string()))
+            .dispatch();
+        assert_ne!(resp.headers().get_one(REQUEST_ID_HEADER), Some(&u64::MAX.to_string()[..]));
+
+        let id: RequestID = "1234".parse().unwrap();
+        assert_eq!(id.worker_id(), None);
+        assert_eq!(id.sequence(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid snowflake worker id")]
+    fn rejects_invalid_snowflake_worker_ids() {
+        RequestIDFairing::default().with_snowflakes(MAX_SNOWFLAKE_WORKER_ID + 1);
+    }
+
+    #[test]
+    fn xid_ids() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_xid_machine_id(0x12_3456))
+            .mount("/", routes![req_id_display]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let first = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        assert_eq!(resp.into_string(), Some(first.clone()));
+        assert_eq!(first.len(), 20);
+        let second = c.get("/").dispatch().into_string().unwrap();
+        assert_ne!(first, second);
+
+        let id: RequestID = first.parse().unwrap();
+        assert_eq!(id.to_string(), first);
+        assert_eq!(xid::machine(id.id), 0x12_3456);
+        let age = SystemTime::now().duration_since(id.timestamp().unwrap()).unwrap();
+        assert!(age < Duration::from_secs(2));
+
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "9m4e2mr0ui3e8a215n4g"))
+            .dispatch();
+        assert_eq!(resp.into_string(), Some("9m4e2mr0ui3e8a215n4g".to_string()));
+        let id: RequestID = "9m4e2mr0ui3e8a215n4g".parse().unwrap();
+        assert_eq!(id.to_hex(), "4d88e15b60f486e428412dc9");
+        assert_eq!(id.timestamp(), Some(UNIX_EPOCH + Duration::from_secs(1_300_816_219)));
+
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "1234"))
+            .dispatch();
+        assert_ne!(resp.into_string(), Some("1234".to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid xid machine id")]
+    fn rejects_invalid_xid_machine_ids() {
+        RequestIDFairing::default().with_xid_machine_id(MAX_XID_MACHINE_ID + 1);
+    }
+
+    #[test]
+    fn u128_ids() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_128_bit_ids())
+            .mount("/", routes![req_id_display]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let ids: Vec<String> = (0..20)
+            .map(|_| c.get("/").dispatch().into_string().unwrap())
+            .collect();
+        for id in &ids {
+            assert_eq!(id.len(), 32);
+            assert!(id.bytes().all(|b| b.is_ascii_hexdigit() && !b.is_ascii_uppercase()));
+        }
+        // 20 zero-extended u64s would all start with 16 zeros.
+        let highs: HashSet<&str> = ids.iter().map(|id| &id[..16]).collect();
+        assert!(highs.len() > 1);
+
+        let inbound = "80F198EE56343BA864FE8B2A57D3EFF7";
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, inbound))
+            .dispatch();
+        assert_eq!(resp.into_string(), Some(inbound.to_ascii_lowercase()));
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "64fe8b2a57d3eff7"))
+            .dispatch();
+        assert_ne!(resp.into_string(), Some("64fe8b2a57d3eff7".to_string()));
+
+        let id = RequestID {
+            id: 0x80f198ee56343ba864fe8b2a57d3eff7,
+            high: 0,
+            kind: IdKind::Hex128,
+            prefix: "",
+            namespace: PhantomData,
+        };
+        assert_eq!(u128::from(id), 0x80f198ee56343ba864fe8b2a57d3eff7);
+        assert_eq!(u64::from(id), 0x64fe8b2a57d3eff7);
+        assert_eq!(id.to_hex(), "80f198ee56343ba864fe8b2a57d3eff7");
+    }
+
+    #[test]
+    fn nanoid_ids() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_nanoids())
+            .mount("/", routes![req_id_display]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let first = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        assert_eq!(resp.into_string(), Some(first.clone()));
+        assert_eq!(first.len(), 21);
+        assert!(first.bytes().all(|b| DEFAULT_NANOID_ALPHABET.as_bytes().contains(&b)));
+        let second = c.get("/").dispatch().into_string().unwrap();
+        assert_ne!(first, second);
+
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "V1StGXR8_Z5jdHi6B-myT"))
+            .dispatch();
+        assert_eq!(resp.into_string(), Some("V1StGXR8_Z5jdHi6B-myT".to_string()));
+
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "1234"))
+            .dispatch();
+        assert_ne!(resp.into_string(), Some("1234".to_string()));
+    }
+
+    #[test]
+    fn nanoid_format() {
+        // No 0/O or 1/l/I, to read out over the phone.
+        const ALPHABET: &str = "23456789abcdefghijkmnpqrstuvwxyz";
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_nanoid_format(ALPHABET, 8))
+            .mount("/", routes![req_id_display]);
+        let c = Client::tracked(rkt).unwrap();
+
+        for _ in 0..20 {
+            let id = c.get("/").dispatch().into_string().unwrap();
+            assert_eq!(id.len(), 8);
+            assert!(id.bytes().all(|b| ALPHABET.as_bytes().contains(&b)), "{}", id);
+        }
+
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "abcd2345"))
+            .dispatch();
+        assert_eq!(resp.into_string(), Some("abcd2345".to_string()));
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "abcd1234"))
+            .dispatch();
+        assert_ne!(resp.into_string(), Some("abcd1234".to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid nanoid alphabet")]
+    fn rejects_repeated_nanoid_symbols() {
+        RequestIDFairing::default().with_nanoid_format("abca", 8);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid nanoid length")]
+    fn rejects_overlong_nanoids() {
+        RequestIDFairing::default().with_nanoid_format(DEFAULT_NANOID_ALPHABET, 22);
+    }
+
+    #[test]
+    fn ksuid_ids() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_ksuids())
+            .mount("/", routes![req_id_display]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let first = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        assert_eq!(resp.into_string(), Some(first.clone()));
+        assert_eq!(first.len(), 27);
+        let second = c.get("/").dispatch().into_string().unwrap();
+        assert_ne!(first, second);
+
+        let id: RequestID = first.parse().unwrap();
+        assert_eq!(id.to_string(), first);
+        assert_eq!(id.to_hex().len(), 40);
+        let age = SystemTime::now().duration_since(id.timestamp().unwrap()).unwrap();
+        assert!(age < Duration::from_secs(2));
+
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "0ujtsYcgvSTl8PAuAdqWYSMnLOv"))
+            .dispatch();
+        assert_eq!(resp.into_string(), Some("0ujtsYcgvSTl8PAuAdqWYSMnLOv".to_string()));
+        let id: RequestID = "0ujtsYcgvSTl8PAuAdqWYSMnLOv".parse().unwrap();
+        assert_eq!(id.to_hex(), "0669f7efb5a1cd34b5f99d1154fb6853345c9735");
+        assert_eq!(id.timestamp(), Some(UNIX_EPOCH + Duration::from_secs(1_507_608_047)));
+        assert_eq!(id.timestamp_ms(), Some(1_507_608_047_000));
+
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "1234"))
+            .dispatch();
+        assert_ne!(resp.into_string(), Some("1234".to_string()));
+    }
+
+    #[test]
+    fn parses_ids() {
+        let id: RequestID = "1234".parse().unwrap();
+        assert_eq!(u64::from(id), 1234);
+        assert_eq!(id.to_string(), "1234");
+
+        for malformed in ["", "12ab", "-1", "18446744073709551616"].iter() {
+            assert_eq!(malformed.parse::<RequestID>(), Err(ParseRequestIDError(())));
+        }
+        assert_eq!(ParseRequestIDError(()).to_string(), "invalid request id");
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn parses_uuid_ids() {
+        let id: RequestID = "550E8400E29B41D4A716446655440000".parse().unwrap();
+        assert_eq!(id.to_string(), "550e8400-e29b-41d4-a716-446655440000");
+        assert_eq!(id.to_string().parse(), Ok(id));
+    }
+
+    #[get("/")]
+    fn session_ids(id: RequestID, session: SessionCorrelationID) -> String {
+        format!("{} {}", id, session)
+    }
+
+    #[test]
+    fn session_cookie() {
+        let rkt = rocket::build()
+            .attach(
+                RequestIDFairing::default().with_session_cookie(
+                    SessionCookie::new()
+                        .with_max_age(Duration::from_secs(600))
+                        .with_secure(true),
+                ),
+            )
+            .mount("/", routes![session_ids]);
+        // An untracked client doesn't send cookies back by itself.
+        let c = Client::untracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let id = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        assert_eq!(
+            resp.headers().get_one("Set-Cookie"),
+            Some(&format!("request_id={}; Path=/; Max-Age=600; Secure; HttpOnly", id)[..])
+        );
+        assert_eq!(resp.into_string(), Some(format!("{} {}", id, id)));
+
+        let resp = c.get("/")
+            .cookie(Cookie::new("request_id", id.clone()))
+            .dispatch();
+        let second = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        assert_ne!(second, id);
+        assert_eq!(
+            resp.headers().get_one("Set-Cookie"),
+            Some(&format!("request_id={}; Path=/; Max-Age=600; Secure; HttpOnly", id)[..])
+        );
+        assert_eq!(resp.into_string(), Some(format!("{} {}", second, id)));
+
+        let resp = c.get("/")
+            .cookie(Cookie::new("request_id", "not valid"))
+            .dispatch();
+        let third = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        assert_eq!(resp.into_string(), Some(format!("{} {}", third, third)));
+
+        let resp = c.get("/").cookie(Cookie::new("request_id", "")).dispatch();
+        let fourth = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        assert_eq!(resp.into_string(), Some(format!("{} {}", fourth, fourth)));
+    }
+
+    // Recorder is a tracing subscriber which records the request_id of the span each event is in.
+    #[cfg(feature = "tracing")]
+    #[derive(Default)]
+    struct Recorder {
+        spans: Mutex<Vec<String>>,
+        entered: Mutex<Vec<u64>>,
+        events: Mutex<Vec<Option<String>>>,
+    }
+
+    #[cfg(feature = "tracing")]
+    struct RequestIDVisitor<'a>(&'a mut String);
+
+    #[cfg(feature = "tracing")]
+    impl<'a> tracing::field::Visit for RequestIDVisitor<'a> {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn fmt::Debug) {
+            if field.name() == "request_id" {
+                *self.0 = format!("{:?}", value);
+            }
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    impl tracing::Subscriber for Recorder {
+        fn enabled(&self, _: &tracing::Metadata) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &tracing::span::Attributes) -> tracing::span::Id {
+            let mut request_id = String::new();
+            span.record(&mut RequestIDVisitor(&mut request_id));
+            let mut spans = self.spans.lock().unwrap();
+            spans.push(request_id);
+            tracing::span::Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record) {}
+
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+        fn event(&self, _: &tracing::Event) {
+            let span = self.entered.lock().unwrap().last().cloned();
+            let request_id = span.map(|span| self.spans.lock().unwrap()[span as usize - 1].clone());
+            self.events.lock().unwrap().push(request_id);
+        }
+
+        fn enter(&self, span: &tracing::span::Id) {
+            self.entered.lock().unwrap().push(span.into_u64());
+        }
+
+        fn exit(&self, span: &tracing::span::Id) {
+            let mut entered = self.entered.lock().unwrap();
+            if entered.last() == Some(&span.into_u64()) {
+                entered.pop();
+            }
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[get("/")]
+    fn traced(id: RequestID, span: RequestSpan) -> String {
+        span.in_scope(|| tracing::info!("handling request"));
+        id.to_string()
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn enters_request_span() {
+        let dispatch = tracing::Dispatch::new(Recorder::default());
+        let id = tracing::dispatcher::with_default(&dispatch, || {
+            let rkt = rocket::build()
+                .attach(RequestIDFairing)
+                .mount("/", routes![traced]);
+            let c = Client::tracked(rkt).unwrap();
+
+            let id = c.get("/").dispatch().into_string().unwrap();
+            tracing::info!("after the request");
+            id
+        });
+
+        let recorder = dispatch.downcast_ref::<Recorder>().unwrap();
+        assert_eq!(*recorder.spans.lock().unwrap(), vec![id.clone()]);
+        assert_eq!(*recorder.events.lock().unwrap(), vec![Some(id), None]);
+        assert!(recorder.entered.lock().unwrap().is_empty());
+    }
+
+    // looked_up stands in for application code which only has the request.
+    fn looked_up(request: &Request) -> Option<RequestID> {
+        RequestID::of(request)
+    }
+
+    struct LookedUp(Option<RequestID>);
+
+    #[rocket::async_trait]
+    impl<'r> FromRequest<'r> for LookedUp {
+        type Error = ();
+
+        async fn from_request(request: &'r Request<'_>) -> ReqOutcome<Self, Self::Error> {
+            Outcome::Success(LookedUp(looked_up(request)))
+        }
+    }
+
+    #[get("/")]
+    fn look_up(id: Option<RequestID>, looked_up: LookedUp) -> String {
+        assert_eq!(id, looked_up.0);
+        format!("{:?}", looked_up.0.map(u64::from))
+    }
+
+    #[test]
+    fn looks_up_ids() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing)
+            .mount("/", routes![look_up]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let header = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        assert_eq!(resp.into_string(), Some(format!("Some({})", header)));
+
+        // Without the fairing, the guard gives the request its ID, which is then looked up.
+        let rkt = rocket::build().mount("/", routes![look_up]);
+        let c = Client::tracked(rkt).unwrap();
+        assert!(c.get("/").dispatch().into_string().unwrap().starts_with("Some("));
+    }
+
+    #[get("/")]
+    fn optional_id(id: Option<RequestID>) -> String {
+        match id {
+            Some(id) => format!("request {}", id),
+            None => "no request id".to_string(),
+        }
+    }
+
+    #[test]
+    fn optional_request_id() {
+        let rkt = rocket::build().mount("/", routes![optional_id]);
+        let c = Client::tracked(rkt).unwrap();
+        let resp = c.get("/").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert!(resp.into_string().unwrap().starts_with("request "));
+
+        // A request the fairing declined to give an ID is still handled.
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_inbound_policy(InboundPolicy::Require))
+            .mount("/", routes![optional_id]);
+        let c = Client::tracked(rkt).unwrap();
+        let resp = c.get("/").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.into_string(), Some("no request id".to_string()));
+    }
+
+    fn emits_on(fairing: &RequestIDFairing, paths: &[&str]) -> Vec<bool> {
+        paths.iter().map(|path| fairing.emits_header_on(path)).collect()
+    }
+
+    #[test]
+    fn filters_header_by_path() {
+        let paths = ["/", "/api", "/api/", "/api/users", "/apiv2", "/assets/logo.png"];
+
+        let fairing = RequestIDFairing::default().emit_header_on("/api");
+        assert_eq!(
+            emits_on(&fairing, &paths),
+            vec![false, true, true, true, false, false]
+        );
+
+        let fairing = RequestIDFairing::default().skip_header_on("/assets/");
+        assert_eq!(
+            emits_on(&fairing, &paths),
+            vec![true, true, true, true, true, false]
+        );
+
+        let fairing = RequestIDFairing::default()
+            .emit_header_on("/api")
+            .skip_header_on("/api/internal")
+            .skip_header_on("/assets")
+            .emit_header_on("/assets/docs");
+        assert_eq!(
+            emits_on(
+                &fairing,
+                &["/api/users", "/api/internal/stats", "/assets/logo.png", "/assets/docs/x", "/"]
+            ),
+            vec![true, false, false, true, false]
+        );
+
+        // Skipping wins a tie, and "/" covers everything.
+        let fairing = RequestIDFairing::default()
+            .emit_header_on("/")
+            .skip_header_on("/api")
+            .emit_header_on("/api");
+        assert_eq!(emits_on(&fairing, &["/", "/x", "/api/users"]), vec![true, true, false]);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid path prefix")]
+    fn rejects_relative_path_prefixes() {
+        RequestIDFairing::default().emit_header_on("api");
+    }
+
+    #[get("/api/id")]
+    fn api_id(id: RequestID) -> String {
+        id.to_string()
+    }
+
+    #[get("/assets/id")]
+    fn asset_id(id: RequestID) -> String {
+        id.to_string()
+    }
+
+    #[test]
+    fn only_emits_header_on_configured_paths() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().skip_header_on("/assets"))
+            .mount("/", routes![api_id, asset_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/api/id").dispatch();
+        let header = resp.headers().get_one(REQUEST_ID_HEADER).map(|h| h.to_string());
+        assert_eq!(header, resp.into_string());
+
+        let resp = c.get("/assets/id").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.headers().get_one(REQUEST_ID_HEADER), None);
+        assert!(resp.into_string().unwrap().parse::<u64>().is_ok());
+    }
+
+    #[get("/")]
+    fn id_source(source: RequestIDSource) -> String {
+        source.to_string()
+    }
+
+    #[test]
+    fn records_id_source() {
+        let dispatch = |parent: bool, inbound: Option<&str>| {
+            let policy = if parent {
+                InboundPolicy::Parent
+            } else {
+                InboundPolicy::Prefer
+            };
+            let rkt = rocket::build()
+                .attach(
+                    RequestIDFairing::default()
+                        .with_inbound_headers(vec!["X-Request-ID", "X-Correlation-ID"])
+                        .with_inbound_policy(policy),
+                )
+                .mount("/", routes![id_source]);
+            let c = Client::tracked(rkt).unwrap();
+            let mut req = c.get("/");
+            if let Some(inbound) = inbound {
+                req = req.header(Header::new(inbound.to_string(), "1234"));
+            }
+            req.dispatch().into_string().unwrap()
+        };
+
+        assert_eq!(dispatch(false, None), "generated");
+        assert_eq!(dispatch(false, Some("X-Request-ID")), "from X-Request-ID header");
+        assert_eq!(dispatch(false, Some("X-Correlation-ID")), "from X-Correlation-ID header");
+        assert_eq!(dispatch(true, Some("X-Request-ID")), "generated");
+    }
+
+    #[test]
+    fn query_params() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_query_param("rid"))
+            .mount("/", routes![req_id])
+            .mount("/source", routes![id_source]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/?rid=1234").dispatch();
+        assert_eq!(resp.headers().get_one(REQUEST_ID_HEADER), Some("1234"));
+        assert_eq!(resp.into_string(), Some("1234".to_string()));
+        let resp = c.get("/source?event=push&rid=1234").dispatch();
+        assert_eq!(resp.into_string(), Some("from rid query parameter".to_string()));
+        let resp = c.get("/?rid=1234")
+            .header(Header::new(REQUEST_ID_HEADER, "5678"))
+            .dispatch();
+        assert_eq!(resp.into_string(), Some("5678".to_string()));
+
+        // Without a usable parameter, requests get generated IDs.
+        for uri in ["/", "/?event=push", "/?rid=", "/?rid=not%20an%20id", "/?id=1234"].iter() {
+            let resp = c.get(*uri).dispatch();
+            let id = resp.into_string().unwrap();
+            assert_ne!(id, "1234", "{}", uri);
+        }
+        let resp = c.get("/source?rid=nope").dispatch();
+        assert_eq!(resp.into_string(), Some("generated".to_string()));
+    }
+
+    #[cfg(feature = "signing")]
+    #[test]
+    fn signed_ids() {
+        const KEY: &[u8] = b"correct horse battery staple";
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_signed_ids(KEY).with_query_param("rid"))
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let header = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        let body = resp.into_string().unwrap();
+        assert_eq!(header, format!("{}.{}", body, &header[body.len() + 1..]));
+        assert_eq!(RequestID::verify(&header, KEY).unwrap().as_str(), body);
+        assert_eq!(
+            RequestID::verify(&header, b"another key"),
+            Err(SignatureError::Mismatch)
+        );
+        assert_eq!(RequestID::verify(&body, KEY), Err(SignatureError::Unsigned));
+
+        // Signed inbound IDs are honored, and signed again on the way out.
+        let id: RequestID = "1234".parse().unwrap();
+        let signed = id.sign(KEY);
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, signed.clone()))
+            .dispatch();
+        assert_eq!(resp.headers().get_one(REQUEST_ID_HEADER), Some(&signed[..]));
+        assert_eq!(resp.into_string(), Some("1234".to_string()));
+        let resp = c.get(format!("/?rid={}", signed)).dispatch();
+        assert_eq!(resp.into_string(), Some("1234".to_string()));
+
+        // Tampered, unsigned, and otherwise signed IDs get generated IDs instead.
+        let mut tampered = signed.clone().into_bytes();
+        tampered[0] = b'5';
+        let tampered = String::from_utf8(tampered).unwrap();
+        let forged = id.sign(b"another key");
+        for inbound in [&tampered[..], "1234", &forged[..], &signed[..signed.len() - 1]].iter() {
+            let resp = c.get("/")
+                .header(Header::new(REQUEST_ID_HEADER, inbound.to_string()))
+                .dispatch();
+            let id = resp.into_string().unwrap();
+            assert!(id != "1234" && id != "5234", "{}", inbound);
+            let resp = c.get(format!("/?rid={}", inbound)).dispatch();
+            let id = resp.into_string().unwrap();
+            assert!(id != "1234" && id != "5234", "{}", inbound);
+        }
+    }
+
+    #[cfg(feature = "signing")]
+    #[test]
+    #[should_panic(expected = "invalid signing key")]
+    fn rejects_empty_signing_keys() {
+        RequestIDFairing::default().with_signed_ids(b"");
+    }
+
+    #[get("/")]
+    fn current_id(id: RequestID) -> String {
+        format!("{} {:?}", id, current_request_id().map(|current| current.to_string()))
+    }
+
+    #[test]
+    fn thread_local_id() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_thread_local_id())
+            .mount("/", routes![current_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let header = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        assert_eq!(resp.into_string(), Some(format!("{} Some({:?})", header, header)));
+        assert_eq!(current_request_id(), None);
+
+        let rkt = rocket::build()
+            .attach(RequestIDFairing)
+            .mount("/", routes![current_id]);
+        let c = Client::tracked(rkt).unwrap();
+        assert!(c.get("/").dispatch().into_string().unwrap().ends_with(" None"));
+    }
+
+    #[test]
+    fn logging() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_logging(true))
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let header = resp.headers().get_one(REQUEST_ID_HEADER).map(|h| h.to_string());
+        assert_eq!(header, resp.into_string());
+        assert_eq!(c.get("/missing").dispatch().status(), Status::NotFound);
+    }
+
+    #[get("/")]
+    fn timed() -> WithHeader {
+        WithHeader("", Header::new(SERVER_TIMING_HEADER, "db;dur=1.5"))
+    }
+
+    #[test]
+    fn server_timing() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_server_timing())
+            .mount("/", routes![req_id])
+            .mount("/timed", routes![timed]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let timing = resp.headers().get_one(SERVER_TIMING_HEADER).unwrap();
+        let dur: f64 = timing.strip_prefix("total;dur=").unwrap().parse().unwrap();
+        assert!(dur >= 0.0);
+
+        let resp = c.get("/timed").dispatch();
+        let timings: Vec<_> = resp.headers().get(SERVER_TIMING_HEADER).collect();
+        assert_eq!(timings.len(), 2);
+        assert_eq!(timings[0], "db;dur=1.5");
+        assert!(timings[1].starts_with("total;dur="));
+
+        let rkt = rocket::build()
+            .attach(RequestIDFairing)
+            .mount("/", routes![req_id]);
+        let c = Client::tracked(rkt).unwrap();
+        assert!(!c.get("/").dispatch().headers().contains(SERVER_TIMING_HEADER));
+    }
+
+    lazy_static! {
+        static ref HANDLED: Mutex<usize> = Mutex::new(0);
+    }
+
+    #[get("/")]
+    fn counted(id: RequestID) -> String {
+        *HANDLED.lock().unwrap() += 1;
+        id.to_string()
+    }
+
+    #[get("/unguarded")]
+    fn unguarded() -> &'static str {
+        "ok"
+    }
+
+    #[test]
+    fn rejects_invalid_inbound_ids() {
+        let rkt = rocket::build()
+            .attach(
+                RequestIDFairing::default()
+                    .with_header_name("X-Trace")
+                    .with_on_invalid(InvalidIdAction::Reject),
+            )
+            .mount("/", routes![counted, unguarded]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let before = *HANDLED.lock().unwrap();
+        for path in &["/", "/unguarded"] {
+            for inbound in &["not-a-number", "1\n2", &"1".repeat(200)[..]] {
+                let resp = c.get(*path)
+                    .header(Header::new("X-Trace", inbound.to_string()))
+                    .dispatch();
+                assert_eq!(resp.status(), Status::BadRequest);
+                let id = resp.headers().get_one("X-Trace").unwrap();
+                assert!(id.parse::<u64>().is_ok());
+                assert_ne!(id, *inbound);
+                assert_eq!(resp.into_string(), Some("invalid X-Trace header\n".to_string()));
+            }
+        }
+        assert_eq!(*HANDLED.lock().unwrap(), before);
+
+        let resp = c.get("/").header(Header::new("X-Trace", "1234")).dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.into_string(), Some("1234".to_string()));
+
+        let resp = c.get("/").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+    }
+
+    #[get("/id")]
+    fn id_or_error(id: Result<RequestID, RequestIDError>) -> String {
+        match id {
+            Ok(id) => id.to_string(),
+            Err(e) => format!("{:?}", e),
+        }
+    }
+
+    #[get("/source")]
+    fn source_or_error(source: Result<RequestIDSource, RequestIDError>) -> String {
+        match source {
+            Ok(source) => source.to_string(),
+            Err(e) => format!("{:?}", e),
+        }
+    }
+
+    #[get("/trace")]
+    fn trace_or_error(trace: Result<TraceParent, RequestIDError>) -> String {
+        match trace {
+            Ok(trace) => trace.to_string(),
+            Err(e) => format!("{:?}", e),
+        }
+    }
+
+    struct NestedSource(String);
+
+    #[rocket::async_trait]
+    impl<'r> FromRequest<'r> for NestedSource {
+        type Error = ();
+
+        async fn from_request(request: &'r Request<'_>) -> ReqOutcome<Self, ()> {
+            Outcome::Success(NestedSource(match request.guard::<RequestIDSource>().await {
+                Outcome::Success(source) => source.to_string(),
+                Outcome::Error((_, e)) => format!("{:?}", e),
+                Outcome::Forward(_) => "Forward".to_string(),
+            }))
+        }
+    }
+
+    #[get("/nested")]
+    fn nested_source(source: NestedSource) -> String {
+        source.0
+    }
+
+    #[test]
+    fn typed_guard_errors() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default().with_inbound_policy(InboundPolicy::Require))
+            .mount("/", routes![id_or_error, source_or_error, trace_or_error]);
+        let c = Client::tracked(rkt).unwrap();
+        // The fairing is attached, but didn't give these requests IDs.
+        for path in ["/id", "/source", "/trace"].iter() {
+            let resp = c.get(*path).dispatch();
+            assert_eq!(resp.into_string(), Some("NoRequestID".to_string()));
+        }
+        let resp = c.get("/trace")
+            .header(Header::new(REQUEST_ID_HEADER, "1234"))
+            .dispatch();
+        assert_eq!(resp.into_string(), Some("NotTracked".to_string()));
+
+        // Routes can't name the guard without the fairing, but guards of other crates can run it.
+        let rkt = rocket::build().mount("/", routes![nested_source]);
+        let c = Client::tracked(rkt).unwrap();
+        let resp = c.get("/nested").dispatch();
+        assert_eq!(resp.into_string(), Some("FairingNotAttached".to_string()));
+
+        assert_eq!(
+            RequestIDError::InvalidInboundID(Cow::Borrowed("X-Trace")).to_string(),
+            "invalid X-Trace header"
+        );
+    }
+
+    #[get("/")]
+    fn optional(
+        id: Option<RequestID>,
+        source: Optional<RequestIDSource>,
+        trace: Optional<TraceParent>,
+    ) -> String {
+        format!("{} {:?} {:?}", id.is_some(), source.0, trace.0)
+    }
+
+    #[test]
+    fn optional_guards_without_fairing() {
+        let rkt = rocket::build().mount("/", routes![optional]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.into_string(), Some("true Some(Generated) None".to_string()));
+    }
+
+    #[get("/")]
+    fn parent(parent: Option<ParentRequestID>) -> String {
+        format!("{:?}", parent)
+    }
+
+    #[test]
+    fn launch_aborts_without_fairing() {
+        enum Other {}
+        impl Namespace for Other {}
+
+        // Wrapping the guard in an Option doesn't opt out, and nor does another namespace's
+        // fairing stand in for the default one.
+        let rockets = vec![
+            rocket::build(),
+            rocket::build().attach(RequestIDFairing::default().in_namespace::<Other>()),
+        ];
+        for rkt in rockets {
+            match Client::tracked(rkt.mount("/", routes![parent])) {
+                Ok(_) => panic!("launched without the fairing"),
+                Err(e) => assert!(matches!(e.kind(), rocket::error::ErrorKind::SentinelAborts(_))),
+            }
+        }
+
+        // Attaching it twice marks it once.
+        let rkt = rocket::build()
+            .attach(RequestIDFairing::default())
+            .attach(RequestIDFairing::default().with_header_name("X-Other-ID"))
+            .mount("/", routes![parent]);
+        let c = Client::tracked(rkt).unwrap();
+        assert_eq!(c.get("/").dispatch().into_string(), Some("None".to_string()));
+    }
+
+    #[get("/suppressed")]
+    fn suppressed(id: RequestID, _suppress: SuppressRequestIDHeader) -> String {
+        id.to_string()
+    }
+
+    #[get("/emitted")]
+    fn emitted(id: RequestID) -> String {
+        id.to_string()
+    }
+
+    #[test]
+    fn suppresses_headers() {
+        let rkt = rocket::build()
+            .attach(
+                RequestIDFairing::default()
+                    .with_trace_context()
+                    .with_session_cookie(SessionCookie::new()),
+            )
+            .mount("/", routes![suppressed, emitted]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/suppressed").dispatch();
+        assert_eq!(resp.status(), Status::Ok);
+        assert_eq!(resp.headers().get_one(REQUEST_ID_HEADER), None);
+        assert_eq!(resp.headers().get_one(TRACEPARENT_HEADER), None);
+        assert_eq!(resp.headers().get_one("Set-Cookie"), None);
+        assert!(resp.into_string().unwrap().parse::<u64>().is_ok());
+
+        let resp = c.get("/emitted").dispatch();
+        let header = resp.headers().get_one(REQUEST_ID_HEADER).map(|h| h.to_string());
+        assert!(resp.headers().get_one(TRACEPARENT_HEADER).is_some());
+        assert_eq!(header, resp.into_string());
+    }
+
+    #[get("/")]
+    fn copied(id: RequestID) -> String {
+        let copy = id;
+        let mut seen = HashSet::new();
+        seen.insert(id);
+        assert!(seen.contains(&copy));
+        assert!(!seen.insert(copy));
+        u64::from(copy).to_string()
+    }
+
+    #[test]
+    fn ids_are_copy_and_hash() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing)
+            .mount("/", routes![copied]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/").dispatch();
+        let header = resp.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        assert_eq!(resp.into_string(), Some(header));
+    }
+
+    #[get("/")]
+    fn req_id_u64(id: RequestID) -> String {
+        assert_eq!(id.as_u64(), u64::from(id));
+        id.as_u64().to_string()
+    }
+
+    #[test]
+    fn converts_ids_to_u64s() {
+        let rkt = rocket::build()
+            .attach(RequestIDFairing)
+            .mount("/", routes![req_id_u64]);
+        let c = Client::tracked(rkt).unwrap();
+
+        let resp = c.get("/")
+            .header(Header::new(REQUEST_ID_HEADER, "1234"))
+            .dispatch();
+        assert_eq!(resp.into_string(), Some("1234".to_string()));
+
+        let id: RequestID = "80f198ee56343ba864fe8b2a57d3eff7".parse().unwrap();
+        assert_eq!(id.as_u64(), 0x64fe8b2a57d3eff7);
+        assert_eq!(i64::from(id), 0x64fe8b2a57d3eff7);
+        assert_eq!(u128::from(id), 0x80f198ee56343ba864fe8b2a57d3eff7);
+
+        let id: RequestID = "18446744073709551614".parse().unwrap();
+        assert_eq!(i64::from(id), -2);
+        assert_eq!(i64::from(id) as u64, u64::MAX - 1);
+        assert_eq!(u128::from(id), (u64::MAX - 1) as u128);
+        let id: RequestID = "9223372036854775808".parse().unwrap();
+        assert_eq!(i64::from(id), i64::MIN);
+    }
+
+    // Traced is the sort of struct an application might keep a request's ID in.
+    struct Traced {
+        id: RequestID,
+    }
+
+    #[test]
+    fn converts_borrowed_ids() {
+        let traced = Traced {
+            id: "1234".parse().unwrap(),
+        };
+        let borrowed = &traced;
+        assert_eq!(borrowed.id.as_u64(), 1234);
+        assert_eq!(traced.id.to_string(), "1234");
+    }
+
+    #[test]
+    fn ids_key_maps() {
+        let first: RequestID = "1234".parse().unwrap();
+        let second: RequestID = "5678".parse().unwrap();
+        let mut data = HashMap::new();
+        data.insert(first, "first");
+        data.insert(second, "second");
 
-        assert_eq!(REQUEST_IDS.lock().unwrap().len(), 0);
+        let prefixed = RequestID {
+            prefix: "api-",
+            ..first
+        };
+        assert_eq!(data.get(&prefixed), Some(&"first"));
+        assert_eq!(data.get(&second), Some(&"second"));
+        assert_eq!(data.get(&"1234".parse::<RequestID>().unwrap()), Some(&"first"));
+        assert_eq!(data.get(&"4321".parse::<RequestID>().unwrap()), None);
     }
 
     #[get("/")]
@@ -193,12 +6968,13 @@ mod tests {
         id: RequestID,
     }
 
-    impl<'a, 'r> FromRequest<'a, 'r> for TestGuard {
+    #[rocket::async_trait]
+    impl<'r> FromRequest<'r> for TestGuard {
         type Error = ();
 
-        fn from_request(request: &'a Request<'r>) -> ReqOutcome<Self, Self::Error> {
+        async fn from_request(request: &'r Request<'_>) -> ReqOutcome<Self, Self::Error> {
             Outcome::Success(TestGuard {
-                id: request.guard().unwrap(),
+                id: request.guard().await.unwrap(),
             })
         }
     }
@@ -211,10 +6987,10 @@ mod tests {
 
     #[test]
     fn same_in_same_request() {
-        let rkt = rocket::ignite()
+        let rkt = rocket::build()
             .attach(RequestIDFairing)
             .mount("/", routes![multiple_with_guard]);
-        let c = Client::new(rkt).unwrap();
+        let c = Client::tracked(rkt).unwrap();
 
         assert_eq!(c.get("/").dispatch().status(), Status::Ok);
     }
@@ -0,0 +1,96 @@
+use rand::Rng;
+
+/// The alphabet of nanoid's URL-safe IDs, which `RequestIDFairing::with_nanoids` uses.
+pub const DEFAULT_NANOID_ALPHABET: &str =
+    "useandom-26T198340PX75pxJACKVERYMINDBUSHWOLF_GQZbfghjklqvwyzrict";
+
+/// The length of nanoid's IDs, which `RequestIDFairing::with_nanoids` uses.
+pub const DEFAULT_NANOID_LEN: u8 = 21;
+
+// fits reports whether every ID of len symbols from an alphabet of size symbols fits in a u128.
+pub fn fits(size: usize, len: u8) -> bool {
+    (size as u128).checked_pow(len as u32).is_some()
+}
+
+/// Returns a new ID of len symbols from alphabet, as the number they spell in base
+/// `alphabet.len()`, least significant symbol last. Each symbol is equally likely: random bytes
+/// are masked down to the next power of two above the alphabet's size, and those which are still
+/// too big are thrown away, rather than mapped onto the alphabet with a modulo, which would
+/// favour its first symbols.
+pub fn generate<R: Rng>(alphabet: &str, len: u8, rng: &mut R) -> u128 {
+    let size = alphabet.len() as u128;
+    let mask = (alphabet.len().next_power_of_two() - 1) as u8;
+    (0..len).fold(0, |id, _| loop {
+        let symbol = rng.gen::<u8>() & mask;
+        if (symbol as u128) < size {
+            break id * size + symbol as u128;
+        }
+    })
+}
+
+/// Encodes an ID generated by `generate` as len symbols of alphabet.
+pub fn encode(alphabet: &str, len: u8, mut id: u128) -> String {
+    let alphabet = alphabet.as_bytes();
+    let size = alphabet.len() as u128;
+    let mut encoded = vec![alphabet[0]; len as usize];
+    for symbol in encoded.iter_mut().rev() {
+        *symbol = alphabet[(id % size) as usize];
+        id /= size;
+    }
+    encoded.iter().map(|&c| c as char).collect()
+}
+
+/// Decodes an ID encoded by `encode` with the same alphabet and length.
+pub fn decode(alphabet: &str, len: u8, s: &str) -> Option<u128> {
+    if s.len() != len as usize {
+        return None;
+    }
+    let size = alphabet.len() as u128;
+    s.bytes().try_fold(0u128, |id, b| {
+        let symbol = alphabet.bytes().position(|c| c == b)?;
+        Some(id * size + symbol as u128)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+
+    #[test]
+    fn round_trips() {
+        let id = decode(DEFAULT_NANOID_ALPHABET, 21, "V1StGXR8_Z5jdHi6B-myT").unwrap();
+        assert_eq!(encode(DEFAULT_NANOID_ALPHABET, 21, id), "V1StGXR8_Z5jdHi6B-myT");
+        assert_eq!(encode("01", 8, 5), "00000101");
+        assert_eq!(decode("01", 8, "00000101"), Some(5));
+
+        for _ in 0..100 {
+            let id = generate(DEFAULT_NANOID_ALPHABET, 21, &mut thread_rng());
+            let encoded = encode(DEFAULT_NANOID_ALPHABET, 21, id);
+            assert_eq!(encoded.len(), 21);
+            assert!(encoded.bytes().all(|b| DEFAULT_NANOID_ALPHABET.as_bytes().contains(&b)));
+            assert_eq!(decode(DEFAULT_NANOID_ALPHABET, 21, &encoded), Some(id));
+        }
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        assert_eq!(decode(DEFAULT_NANOID_ALPHABET, 21, "V1StGXR8_Z5jdHi6B-my"), None);
+        assert_eq!(decode(DEFAULT_NANOID_ALPHABET, 21, "V1StGXR8_Z5jdHi6B-myTT"), None);
+        assert_eq!(decode(DEFAULT_NANOID_ALPHABET, 21, "V1StGXR8_Z5jdHi6B-my."), None);
+        assert_eq!(decode("01", 8, "00000102"), None);
+    }
+
+    #[test]
+    fn unbiased() {
+        let mut counts = [0; 3];
+        for _ in 0..3000 {
+            counts[generate("abc", 1, &mut thread_rng()) as usize] += 1;
+        }
+        for &count in counts.iter() {
+            assert!(count > 850 && count < 1150, "{:?}", counts);
+        }
+        assert!(fits(64, 21));
+        assert!(!fits(64, 22));
+    }
+}
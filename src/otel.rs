@@ -0,0 +1,67 @@
+use opentelemetry::global::{self, BoxedSpan};
+use opentelemetry::trace::{
+    self, Span, SpanContext, SpanId, SpanKind, TraceContextExt, TraceFlags, TraceId, TraceState,
+    Tracer,
+};
+use opentelemetry::{Context, KeyValue};
+use rocket::http::Status;
+use rocket::request::Request;
+use std::str::FromStr;
+
+use super::{RequestID, TraceParent};
+
+// The name the fairing's tracer is registered with.
+const TRACER_NAME: &str = "rocket-request_id";
+
+/// Starts a server span for a request with the global tracer provider, with the request's ID as
+/// its `request_id` attribute. If the fairing tracks the request's W3C trace context, the span
+/// takes its trace and span IDs from it, so it's the span the response's `traceparent` names, and
+/// a child of the upstream's span, if there was one, carrying its `tracestate`.
+pub fn start(request: &Request, id: RequestID, trace: Option<&TraceParent>) -> BoxedSpan {
+    let tracer = global::tracer(TRACER_NAME);
+    let mut builder = tracer
+        .span_builder(request.method().as_str())
+        .with_kind(SpanKind::Server)
+        .with_attributes(vec![
+            KeyValue::new("request_id", id.to_string()),
+            KeyValue::new("http.request.method", request.method().as_str()),
+            KeyValue::new("url.path", request.uri().path().to_string()),
+        ]);
+    if let Some(trace) = trace {
+        builder = builder
+            .with_trace_id(TraceId::from_bytes(trace.trace_id().to_be_bytes()))
+            .with_span_id(SpanId::from_bytes(trace.span_id().to_be_bytes()));
+    }
+    let parent = match trace.and_then(|trace| Some((trace, trace.parent_id()?))) {
+        Some((trace, parent_id)) => {
+            let flags = if trace.sampled() {
+                TraceFlags::SAMPLED
+            } else {
+                TraceFlags::default()
+            };
+            Context::new().with_remote_span_context(SpanContext::new(
+                TraceId::from_bytes(trace.trace_id().to_be_bytes()),
+                SpanId::from_bytes(parent_id.to_be_bytes()),
+                flags,
+                true,
+                // The fairing already dropped malformed members, so this shouldn't fail.
+                trace
+                    .tracestate()
+                    .and_then(|state| TraceState::from_str(state).ok())
+                    .unwrap_or_default(),
+            ))
+        }
+        None => Context::new(),
+    };
+    builder.start_with_context(&tracer, &parent)
+}
+
+/// Ends a request's span, recording the status of its response.
+pub fn end(mut span: BoxedSpan, status: Status) {
+    span.set_attribute(KeyValue::new("http.response.status_code", status.code as i64));
+    // Per OpenTelemetry's HTTP conventions, client errors aren't the server's errors.
+    if status.code >= 500 {
+        span.set_status(trace::Status::error(status.to_string()));
+    }
+    span.end();
+}
@@ -0,0 +1,180 @@
+use rand::{thread_rng, Rng};
+use rocket::request::Request;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use super::IdGenerator;
+
+// An empty slot. 0 is never a random ID the pool hands out: it's reserved by default anyway.
+const EMPTY: u64 = 0;
+
+/// Random IDs generated ahead of time by a background thread, for
+/// `RequestIDFairing::with_id_pool`. Requests take IDs from slots without locking anything, and
+/// the thread refills the slots they empty once fewer than `refill_at` IDs are left.
+pub struct IdPool {
+    shared: Arc<Shared>,
+    refiller: Option<JoinHandle<()>>,
+}
+
+struct Shared {
+    slots: Box<[AtomicU64]>,
+    // Where the next request starts looking for an ID.
+    cursor: AtomicUsize,
+    // The number of full slots.
+    available: AtomicUsize,
+    refill_at: usize,
+    stop: AtomicBool,
+}
+
+impl IdPool {
+    pub fn new(size: usize, refill_at: usize) -> IdPool {
+        let shared = Arc::new(Shared {
+            slots: (0..size).map(|_| AtomicU64::new(EMPTY)).collect(),
+            cursor: AtomicUsize::new(0),
+            available: AtomicUsize::new(0),
+            refill_at,
+            stop: AtomicBool::new(false),
+        });
+        let refiller = {
+            let shared = shared.clone();
+            thread::Builder::new()
+                .name("request id pool".to_string())
+                .spawn(move || refill(&shared))
+                .expect("unable to start the request id pool's thread")
+        };
+        IdPool {
+            shared,
+            refiller: Some(refiller),
+        }
+    }
+
+    /// Takes an ID from the pool, if there are any left. This never blocks.
+    pub fn take(&self) -> Option<u64> {
+        let shared = &*self.shared;
+        if shared.available.load(Ordering::Acquire) == 0 {
+            return None;
+        }
+        for _ in 0..shared.slots.len() {
+            let i = shared.cursor.fetch_add(1, Ordering::Relaxed) % shared.slots.len();
+            let id = shared.slots[i].swap(EMPTY, Ordering::AcqRel);
+            if id != EMPTY {
+                if shared.available.fetch_sub(1, Ordering::AcqRel) == shared.refill_at {
+                    self.wake_refiller();
+                }
+                return Some(id);
+            }
+        }
+        None
+    }
+
+    fn wake_refiller(&self) {
+        if let Some(ref refiller) = self.refiller {
+            refiller.thread().unpark();
+        }
+    }
+}
+
+// refill is the pool's thread, which fills the empty slots whenever it's woken, until the pool is
+// dropped.
+fn refill(shared: &Shared) {
+    let mut rng = thread_rng();
+    while !shared.stop.load(Ordering::Acquire) {
+        for slot in shared.slots.iter() {
+            if slot.load(Ordering::Acquire) != EMPTY {
+                continue;
+            }
+            let id = loop {
+                let id = rng.gen::<u64>();
+                if id != EMPTY {
+                    break id;
+                }
+            };
+            // Counted first, so that a request which takes the ID at once doesn't take the count
+            // below zero. Requests only ever empty slots, so nothing else can have filled it.
+            shared.available.fetch_add(1, Ordering::AcqRel);
+            slot.store(id, Ordering::Release);
+        }
+        // A wakeup which came while the slots were being filled makes this return at once.
+        thread::park();
+    }
+}
+
+impl IdGenerator for IdPool {
+    fn generate(&self, _: &Request) -> u64 {
+        self.take().unwrap_or_else(|| thread_rng().gen())
+    }
+}
+
+impl Drop for IdPool {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Release);
+        if let Some(refiller) = self.refiller.take() {
+            refiller.thread().unpark();
+            // The thread can only have panicked if the RNG did, which the fairing would have too.
+            let _ = refiller.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::time::{Duration, Instant};
+
+    // wait_for_refill waits until the pool has at least n IDs, or panics after a second.
+    fn wait_for_refill(pool: &IdPool, n: usize) {
+        let started = Instant::now();
+        while pool.shared.available.load(Ordering::Acquire) < n {
+            assert!(started.elapsed() < Duration::from_secs(1), "the pool wasn't refilled");
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn refills() {
+        let pool = IdPool::new(8, 4);
+        wait_for_refill(&pool, 8);
+        let taken: Vec<_> = (0..8).map(|_| pool.take().unwrap()).collect();
+        assert!(taken.iter().all(|&id| id != EMPTY));
+        wait_for_refill(&pool, 8);
+        assert!(pool.take().is_some());
+    }
+
+    #[test]
+    fn survives_exhaustion() {
+        let pool = Arc::new(IdPool::new(16, 8));
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let pool = pool.clone();
+                thread::spawn(move || {
+                    let (mut ids, mut dry) = (Vec::new(), 0);
+                    for _ in 0..10_000 {
+                        match pool.take() {
+                            Some(id) => ids.push(id),
+                            None => dry += 1,
+                        }
+                    }
+                    (ids, dry)
+                })
+            })
+            .collect();
+
+        let mut ids = HashSet::new();
+        let mut taken = 0;
+        for thread in threads {
+            let (thread_ids, dry) = thread.join().unwrap();
+            assert_eq!(thread_ids.len() + dry, 10_000);
+            taken += thread_ids.len();
+            ids.extend(thread_ids);
+        }
+        // No ID was handed out twice.
+        assert_eq!(ids.len(), taken);
+        assert!(!ids.contains(&EMPTY));
+
+        // Dropping the pool stops its thread, rather than waiting on it forever.
+        let pool = Arc::try_unwrap(pool).ok().unwrap();
+        drop(pool);
+    }
+}
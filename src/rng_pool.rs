@@ -0,0 +1,92 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+// The number of IDs a stream generates before it's reseeded from the operating system.
+const RESEED_INTERVAL: u64 = 1 << 16;
+
+// A stream is one generator in a pool, and how many IDs it has generated since it was seeded.
+struct Stream {
+    rng: StdRng,
+    drawn: u64,
+}
+
+impl Stream {
+    fn seeded() -> Stream {
+        Stream {
+            rng: StdRng::from_entropy(),
+            drawn: 0,
+        }
+    }
+}
+
+/// A pool of random number generators, one per core, which requests take turns with.
+///
+/// Each stream is seeded from the operating system on its own, so IDs from different streams are
+/// no more related than IDs from different processes, and every stream is reseeded after
+/// `RESEED_INTERVAL` IDs, so that one whose state leaked doesn't give away the IDs after it.
+pub struct RngPool {
+    streams: Vec<Mutex<Stream>>,
+    next: AtomicUsize,
+}
+
+impl RngPool {
+    pub fn new() -> RngPool {
+        let size = thread::available_parallelism().map_or(4, |n| n.get());
+        RngPool {
+            streams: (0..size).map(|_| Mutex::new(Stream::seeded())).collect(),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Runs f with a stream from the pool: the first idle one, starting from a different stream
+    /// for each call, or if they're all busy, the one it started from, once it's free.
+    pub fn with<T, F: FnOnce(&mut StdRng) -> T>(&self, f: F) -> T {
+        let len = self.streams.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let mut stream = (0..len)
+            .find_map(|i| self.streams[(start + i) % len].try_lock().ok())
+            .unwrap_or_else(|| self.streams[start % len].lock().unwrap());
+        if stream.drawn == RESEED_INTERVAL {
+            *stream = Stream::seeded();
+        }
+        stream.drawn += 1;
+        f(&mut stream.rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+    use std::collections::HashSet;
+
+    #[test]
+    fn distinct_across_threads() {
+        let pool = RngPool::new();
+        let drawn: Vec<Vec<u64>> = thread::scope(|scope| {
+            let threads: Vec<_> = (0..8)
+                .map(|_| scope.spawn(|| (0..10_000).map(|_| pool.with(|rng| rng.gen())).collect()))
+                .collect();
+            threads.into_iter().map(|thread| thread.join().unwrap()).collect()
+        });
+
+        let ids: HashSet<u64> = drawn.iter().flatten().cloned().collect();
+        assert_eq!(ids.len(), 80_000);
+    }
+
+    #[test]
+    fn reseeds() {
+        let pool = RngPool {
+            streams: vec![Mutex::new(Stream::seeded())],
+            next: AtomicUsize::new(0),
+        };
+        pool.streams[0].lock().unwrap().drawn = RESEED_INTERVAL;
+        let before = pool.streams[0].lock().unwrap().rng.clone().gen::<u64>();
+        let after = pool.with(|rng| rng.gen::<u64>());
+        assert_ne!(before, after);
+        assert_eq!(pool.streams[0].lock().unwrap().drawn, 1);
+    }
+}
@@ -0,0 +1,208 @@
+use rocket::http::Header;
+use rocket::request::{FromRequest, Outcome as ReqOutcome, Request};
+use rocket::{Ignite, Rocket, Sentinel};
+use std::borrow::Cow;
+use std::fmt;
+use std::time::Duration;
+
+use super::{fairing_missing, tracked_guard, RequestIDError};
+
+/// The cookie a `SessionCookie` uses, unless configured otherwise.
+pub const DEFAULT_SESSION_COOKIE: &str = "request_id";
+
+/// How a `RequestIDFairing` correlates the requests a browser makes, by keeping a cookie; see
+/// `RequestIDFairing::with_session_cookie`.
+///
+/// By default, the cookie is named `request_id`, has the path `/`, is `HttpOnly` but not `Secure`,
+/// and lasts for the browser's session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionCookie {
+    name: Cow<'static, str>,
+    path: Cow<'static, str>,
+    max_age: Option<Duration>,
+    secure: bool,
+    http_only: bool,
+}
+
+impl SessionCookie {
+    /// Returns the default session cookie configuration.
+    pub fn new() -> SessionCookie {
+        SessionCookie {
+            name: Cow::Borrowed(DEFAULT_SESSION_COOKIE),
+            path: Cow::Borrowed("/"),
+            max_age: None,
+            secure: false,
+            http_only: true,
+        }
+    }
+
+    /// Use the cookie named `name`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` isn't a valid cookie name.
+    pub fn with_name<S: Into<Cow<'static, str>>>(mut self, name: S) -> SessionCookie {
+        let name = name.into();
+        if !super::is_valid_header_name(&name) {
+            panic!("invalid session cookie name {:?}", name);
+        }
+        self.name = name;
+        self
+    }
+
+    /// Set the cookie's `Path` attribute.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `path` contains characters which would break up the cookie.
+    pub fn with_path<S: Into<Cow<'static, str>>>(mut self, path: S) -> SessionCookie {
+        let path = path.into();
+        if !path.bytes().all(|b| (0x20..0x7f).contains(&b) && b != b';') {
+            panic!("invalid session cookie path {:?}", path);
+        }
+        self.path = path;
+        self
+    }
+
+    /// Set the cookie's `Max-Age` attribute, so that it outlasts the browser's session. Each
+    /// response refreshes it.
+    pub fn with_max_age(mut self, max_age: Duration) -> SessionCookie {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Set whether the cookie has the `Secure` attribute, which keeps it to HTTPS.
+    pub fn with_secure(mut self, secure: bool) -> SessionCookie {
+        self.secure = secure;
+        self
+    }
+
+    /// Set whether the cookie has the `HttpOnly` attribute, which hides it from scripts.
+    pub fn with_http_only(mut self, http_only: bool) -> SessionCookie {
+        self.http_only = http_only;
+        self
+    }
+
+    /// Returns the cookie's name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Default for SessionCookie {
+    fn default() -> SessionCookie {
+        SessionCookie::new()
+    }
+}
+
+// set_cookie returns the header which sets cookie to value.
+pub fn set_cookie(cookie: &SessionCookie, value: &str) -> Header<'static> {
+    let mut header = format!("{}={}; Path={}", cookie.name, value, cookie.path);
+    if let Some(max_age) = cookie.max_age {
+        header.push_str(&format!("; Max-Age={}", max_age.as_secs()));
+    }
+    if cookie.secure {
+        header.push_str("; Secure");
+    }
+    if cookie.http_only {
+        header.push_str("; HttpOnly");
+    }
+    Header::new("Set-Cookie", header)
+}
+
+// is_cookie_value checks that value can be sent back in a cookie unquoted.
+pub fn is_cookie_value(value: &str) -> bool {
+    value.bytes().all(|b| {
+        b > 0x20 && b < 0x7f && b != b'"' && b != b',' && b != b';' && b != b'\\'
+    })
+}
+
+///
+/// The ID which correlates the requests a browser makes, kept in a cookie.
+///
+/// This is only available if the fairing was configured with
+/// `RequestIDFairing::with_session_cookie`. In that case, a request which presents a valid
+/// session cookie keeps its value, and any other request's own ID becomes the value; either way,
+/// the response sets the cookie again, so that the browser's next request presents it too.
+///
+/// Unlike its `RequestID`, this is the same for every request in the chain, so a user reporting a
+/// bug need only tell you this to find every request they made.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionCorrelationID {
+    id: String,
+}
+
+// correlation_id returns a SessionCorrelationID for the chain of requests identified by id.
+pub fn correlation_id(id: String) -> SessionCorrelationID {
+    SessionCorrelationID { id }
+}
+
+impl SessionCorrelationID {
+    /// Returns the correlation ID.
+    pub fn as_str(&self) -> &str {
+        &self.id
+    }
+}
+
+impl From<SessionCorrelationID> for String {
+    fn from(s: SessionCorrelationID) -> String {
+        s.id
+    }
+}
+
+impl fmt::Display for SessionCorrelationID {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.id)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SessionCorrelationID {
+    type Error = RequestIDError;
+
+    async fn from_request(request: &'r Request<'_>) -> ReqOutcome<Self, Self::Error> {
+        tracked_guard(request, "session correlation id", |tracked| tracked.session.clone())
+    }
+}
+
+impl Sentinel for SessionCorrelationID {
+    fn abort(rocket: &Rocket<Ignite>) -> bool {
+        fairing_missing(rocket, "SessionCorrelationID")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_cookies() {
+        let header = set_cookie(&SessionCookie::new(), "1234");
+        assert_eq!(header.value(), "request_id=1234; Path=/; HttpOnly");
+
+        let cookie = SessionCookie::new()
+            .with_name("rid")
+            .with_path("/app")
+            .with_max_age(Duration::from_secs(3600))
+            .with_secure(true)
+            .with_http_only(false);
+        assert_eq!(
+            set_cookie(&cookie, "1234").value(),
+            "rid=1234; Path=/app; Max-Age=3600; Secure"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid session cookie name")]
+    fn rejects_invalid_names() {
+        SessionCookie::new().with_name("request id");
+    }
+
+    #[test]
+    fn checks_cookie_values() {
+        assert!(is_cookie_value("550e8400-e29b-41d4-a716-446655440000"));
+        assert!(!is_cookie_value("a;b"));
+        assert!(!is_cookie_value("a b"));
+        assert!(!is_cookie_value("\"a\""));
+    }
+}
@@ -0,0 +1,131 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::error::Error;
+use std::fmt;
+
+// The number of bytes of an ID's HMAC its tag keeps, rendered as twice as many hex digits. 64 bits
+// are plenty when the only way to check a guess is to send it to the service.
+const TAG_LEN: usize = 8;
+
+// The character between an ID and its tag. IDs may contain it too, so it's the last one.
+const SEPARATOR: char = '.';
+
+// mac returns the HMAC-SHA256 of id with key.
+fn mac(id: &str, key: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(id.as_bytes());
+    mac
+}
+
+/// Returns id followed by its tag, e.g. `1234.3f8a0c9b1d2e4f60`.
+pub fn sign(id: &str, key: &[u8]) -> String {
+    let tag = mac(id, key).finalize().into_bytes();
+    let mut signed = String::with_capacity(id.len() + 1 + TAG_LEN * 2);
+    signed.push_str(id);
+    signed.push(SEPARATOR);
+    for b in &tag[..TAG_LEN] {
+        signed.push_str(&format!("{:02x}", b));
+    }
+    signed
+}
+
+/// Checks the tag of an ID signed by `sign` with key, comparing it in constant time, and returns
+/// the ID without it.
+pub fn verify<'s>(signed: &'s str, key: &[u8]) -> Result<&'s str, SignatureError> {
+    let (id, tag) = signed
+        .rsplit_once(SEPARATOR)
+        .ok_or(SignatureError::Unsigned)?;
+    if id.is_empty() || tag.len() != TAG_LEN * 2 || !tag.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(SignatureError::Unsigned);
+    }
+    let tag = u64::from_str_radix(tag, 16)
+        .map_err(|_| SignatureError::Unsigned)?
+        .to_be_bytes();
+    mac(id, key)
+        .verify_truncated_left(&tag)
+        .map_err(|_| SignatureError::Mismatch)?;
+    Ok(id)
+}
+
+/// A request ID whose signature `RequestID::verify` has checked, so it was issued by a fairing
+/// with the same key (see `RequestIDFairing::with_signed_ids`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedId(pub(crate) String);
+
+impl VerifiedId {
+    /// Returns the ID, without its tag, as the fairing rendered it, e.g. to search logs for.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for VerifiedId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The error returned when `RequestID::verify` can't verify an ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureError {
+    /// The string isn't a signed ID at all: it has no tag, or its tag is malformed.
+    Unsigned,
+    /// The ID's tag doesn't match it, so it was altered, made up, or signed with another key.
+    Mismatch,
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SignatureError::Unsigned => f.write_str("request id isn't signed"),
+            SignatureError::Mismatch => f.write_str("request id signature doesn't match"),
+        }
+    }
+}
+
+impl Error for SignatureError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = b"correct horse battery staple";
+
+    #[test]
+    fn round_trips() {
+        let signed = sign("api-1234", KEY);
+        assert!(signed.starts_with("api-1234."));
+        assert_eq!(signed.len(), "api-1234.".len() + 16);
+        assert_eq!(verify(&signed, KEY), Ok("api-1234"));
+        // IDs may contain the separator themselves.
+        let signed = sign("1.2", KEY);
+        assert_eq!(verify(&signed, KEY), Ok("1.2"));
+    }
+
+    #[test]
+    fn known_vector() {
+        // RFC 4231's second test case: HMAC-SHA256("Jefe", "what do ya want for nothing?").
+        let signed = sign("what do ya want for nothing?", b"Jefe");
+        assert_eq!(signed, "what do ya want for nothing?.5bdcc146bf60754e");
+    }
+
+    #[test]
+    fn rejects_tampering() {
+        let signed = sign("1234", KEY);
+        assert_eq!(verify(&signed, b"another key"), Err(SignatureError::Mismatch));
+        for i in 0..signed.len() {
+            if i == 4 {
+                continue;
+            }
+            let mut tampered = signed.clone().into_bytes();
+            tampered[i] = if tampered[i] == b'0' { b'1' } else { b'0' };
+            let tampered = String::from_utf8(tampered).unwrap();
+            assert_eq!(verify(&tampered, KEY), Err(SignatureError::Mismatch), "{}", tampered);
+        }
+        let tag = &signed[5..];
+        for unsigned in ["1234", ".", "1234.", &format!(".{}", tag), &signed[..20], "1234.+"].iter()
+        {
+            assert_eq!(verify(unsigned, KEY), Err(SignatureError::Unsigned), "{}", unsigned);
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use std::sync::Mutex;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The largest worker ID a Snowflake ID can hold; see `RequestIDFairing::with_snowflakes`.
+pub const MAX_SNOWFLAKE_WORKER_ID: u16 = (1 << WORKER_BITS) - 1;
+
+// Snowflake timestamps count milliseconds from this Unix time, as Twitter's do. Their 41 bits run
+// out in 2080.
+const EPOCH_MS: u64 = 1_288_834_974_657;
+
+// The layout of an ID below its timestamp: the worker ID, then a sequence number which tells apart
+// the IDs a worker generates within a millisecond. The top bit is always 0, so IDs fit in an i64.
+const WORKER_BITS: u32 = 10;
+const SEQUENCE_BITS: u32 = 12;
+const MAX_SEQUENCE: u64 = (1 << SEQUENCE_BITS) - 1;
+
+/// The largest valid Snowflake ID.
+pub const MAX_ID: u64 = i64::MAX as u64;
+
+// State is the timestamp, in milliseconds since the epoch, and sequence number of the last ID
+// generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct State {
+    timestamp: u64,
+    sequence: u64,
+}
+
+impl State {
+    // advance moves on to the next ID, given the clock's time now. It returns false if the
+    // sequence numbers for the millisecond have run out, and the clock should be read again.
+    //
+    // The timestamp never goes backwards: if the clock does, the last timestamp is reused until it
+    // catches up, or is advanced if that one's sequence numbers run out.
+    fn advance(&mut self, now: u64) -> bool {
+        if now > self.timestamp {
+            self.timestamp = now;
+            self.sequence = 0;
+        } else if self.sequence < MAX_SEQUENCE {
+            self.sequence += 1;
+        } else if now < self.timestamp {
+            // Waiting for the clock could take a while, so borrow the next millisecond instead.
+            self.timestamp += 1;
+            self.sequence = 0;
+        } else {
+            return false;
+        }
+        true
+    }
+}
+
+lazy_static!{
+    // The last ID this process generated. Every worker ID shares the sequence; processes are
+    // meant to be told apart by their worker ID, not IDs within one.
+    static ref LAST: Mutex<State> = Mutex::new(State {
+        timestamp: 0,
+        sequence: 0,
+    });
+}
+
+/// Returns a new Snowflake ID for the given worker: a 41-bit timestamp in milliseconds since
+/// Twitter's epoch, the 10-bit worker ID, and a 12-bit sequence number. If the sequence numbers
+/// for the current millisecond have run out, this waits for the next one.
+pub fn generate(worker_id: u16) -> u64 {
+    let mut last = LAST.lock().unwrap();
+    while !last.advance(now_ms()) {
+        thread::yield_now();
+    }
+    compose(last.timestamp, worker_id, last.sequence)
+}
+
+// compose lays out the parts of an ID.
+fn compose(timestamp: u64, worker_id: u16, sequence: u64) -> u64 {
+    let timestamp = timestamp << (WORKER_BITS + SEQUENCE_BITS);
+    (timestamp | (worker_id as u64) << SEQUENCE_BITS | sequence) & MAX_ID
+}
+
+/// Returns the Unix timestamp, in milliseconds, that a Snowflake ID was generated at.
+pub fn timestamp_ms(id: u64) -> u64 {
+    EPOCH_MS + (id >> (WORKER_BITS + SEQUENCE_BITS))
+}
+
+/// Returns the ID of the worker which generated a Snowflake ID.
+pub fn worker_id(id: u64) -> u16 {
+    (id >> SEQUENCE_BITS) as u16 & MAX_SNOWFLAKE_WORKER_ID
+}
+
+/// Returns the sequence number of a Snowflake ID within its millisecond.
+pub fn sequence(id: u64) -> u16 {
+    (id & MAX_SEQUENCE) as u16
+}
+
+fn now_ms() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock is before 1970");
+    let now = now.as_secs() * 1000 + now.subsec_millis() as u64;
+    now.saturating_sub(EPOCH_MS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decomposes() {
+        // Generated at 2020-01-01T00:00:33.042Z.
+        let id = 1_212_161_650_631_950_336;
+        assert_eq!(timestamp_ms(id), 1_577_836_833_042);
+        assert_eq!(worker_id(id), 76);
+        assert_eq!(sequence(id), 0);
+        assert_eq!(compose(timestamp_ms(id) - EPOCH_MS, 76, 0), id);
+
+        let id = compose(1234, MAX_SNOWFLAKE_WORKER_ID, MAX_SEQUENCE);
+        assert_eq!(timestamp_ms(id), EPOCH_MS + 1234);
+        assert_eq!(worker_id(id), MAX_SNOWFLAKE_WORKER_ID);
+        assert_eq!(sequence(id) as u64, MAX_SEQUENCE);
+
+        for worker in [0, 1, 40, MAX_SNOWFLAKE_WORKER_ID].iter() {
+            let id = generate(*worker);
+            assert!(id <= MAX_ID);
+            assert_eq!(worker_id(id), *worker);
+            let age = EPOCH_MS + now_ms() - timestamp_ms(id);
+            assert!(age < 1000);
+        }
+    }
+
+    #[test]
+    fn rolls_over_sequence() {
+        let mut state = State {
+            timestamp: 1000,
+            sequence: 0,
+        };
+        for sequence in 1..=MAX_SEQUENCE {
+            assert!(state.advance(1000));
+            assert_eq!(state, State {
+                timestamp: 1000,
+                sequence,
+            });
+        }
+        // The millisecond is used up, so the clock has to move on.
+        assert!(!state.advance(1000));
+        assert!(state.advance(1001));
+        assert_eq!(state, State {
+            timestamp: 1001,
+            sequence: 0,
+        });
+    }
+
+    #[test]
+    fn never_goes_backwards() {
+        let mut state = State {
+            timestamp: 1000,
+            sequence: MAX_SEQUENCE - 1,
+        };
+        assert!(state.advance(900));
+        assert_eq!(state, State {
+            timestamp: 1000,
+            sequence: MAX_SEQUENCE,
+        });
+        assert!(state.advance(900));
+        assert_eq!(state, State {
+            timestamp: 1001,
+            sequence: 0,
+        });
+        assert!(state.advance(1001));
+        assert_eq!(state, State {
+            timestamp: 1001,
+            sequence: 1,
+        });
+    }
+
+    #[test]
+    fn increasing() {
+        let mut last = generate(7);
+        for _ in 0..10000 {
+            let next = generate(7);
+            assert!(next > last);
+            last = next;
+        }
+    }
+}
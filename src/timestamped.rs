@@ -0,0 +1,46 @@
+use rand::{thread_rng, Rng};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// The number of random bits below the timestamp. That leaves 42 bits for the timestamp, which
+// run out in 2109.
+const RANDOM_BITS: u32 = 22;
+
+/// Returns a new ID of the current Unix time in milliseconds, above 22 random bits.
+pub fn generate() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock is before 1970");
+    let now = now.as_secs() * 1000 + now.subsec_millis() as u64;
+    compose(now, thread_rng().gen())
+}
+
+// compose lays out the parts of an ID.
+fn compose(timestamp_ms: u64, random: u64) -> u64 {
+    timestamp_ms << RANDOM_BITS | random & ((1 << RANDOM_BITS) - 1)
+}
+
+/// Returns the Unix timestamp, in milliseconds, that an ID was generated at.
+pub fn timestamp_ms(id: u64) -> u64 {
+    id >> RANDOM_BITS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decomposes() {
+        let id = compose(1_577_836_833_042, u64::MAX);
+        assert_eq!(id, 0x5bd7_99da_44bf_ffff);
+        assert_eq!(timestamp_ms(id), 1_577_836_833_042);
+        assert_eq!(compose(1234, 1 << RANDOM_BITS), compose(1234, 0));
+    }
+
+    #[test]
+    fn generates() {
+        let first = generate();
+        let second = generate();
+        assert_ne!(first, second);
+        assert!(timestamp_ms(second) - timestamp_ms(first) < 1000);
+    }
+}
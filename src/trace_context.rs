@@ -0,0 +1,400 @@
+use rand::{thread_rng, Rng};
+use rocket::http::Header;
+use rocket::request::{FromRequest, Outcome as ReqOutcome, Request};
+use rocket::{Ignite, Rocket, Sentinel};
+use std::fmt;
+
+use super::{fairing_missing, hex, random_nonzero_u64, tracked_guard, RequestIDError};
+
+/// The header W3C Trace Context propagates a request's trace in.
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// The header W3C Trace Context propagates vendor-specific trace data in.
+pub const TRACESTATE_HEADER: &str = "tracestate";
+
+// Limits on what tracestate we propagate: the spec allows at most 32 members, and asks that we
+// propagate at least 512 characters of them, shedding members over 128 characters first.
+const MAX_TRACESTATE_MEMBERS: usize = 32;
+const MAX_TRACESTATE_LEN: usize = 512;
+const LONG_TRACESTATE_MEMBER: usize = 128;
+
+// The sampled flag is the only trace flag defined so far; the rest must not be propagated.
+const SAMPLED_FLAG: u8 = 0x01;
+
+///
+/// The [W3C Trace Context](https://www.w3.org/TR/trace-context/) of a rocket request.
+///
+/// This is only available if the fairing was configured with
+/// `RequestIDFairing::with_trace_context`. In that case, a request which arrives with a valid
+/// `traceparent` header continues that trace, and any other request starts a new one; either
+/// way, the request gets a newly generated span ID of its own.
+///
+/// A continued trace also keeps the upstream's `tracestate` header, which the fairing passes on
+/// unchanged (apart from dropping members that break the spec's limits) on the response.
+///
+/// Its `Display` implementation renders the `traceparent` header to send with any requests made
+/// on this request's behalf, which is also the header the fairing sets on the response; `headers`
+/// returns that along with the `tracestate` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceParent {
+    trace_id: u128,
+    parent_id: Option<u64>,
+    span_id: u64,
+    flags: u8,
+    tracestate: Option<String>,
+}
+
+impl TraceParent {
+    /// Returns the ID of the trace this request is part of.
+    pub fn trace_id(&self) -> u128 {
+        self.trace_id
+    }
+
+    /// Returns the span ID the upstream sent in its `traceparent` header, or `None` if this
+    /// request started a new trace.
+    pub fn parent_id(&self) -> Option<u64> {
+        self.parent_id
+    }
+
+    /// Returns this request's own span ID.
+    pub fn span_id(&self) -> u64 {
+        self.span_id
+    }
+
+    /// Returns whether the upstream recorded its part of the trace.
+    pub fn sampled(&self) -> bool {
+        self.flags & SAMPLED_FLAG != 0
+    }
+
+    /// Returns the trace's `tracestate`, if the upstream sent one.
+    pub fn tracestate(&self) -> Option<&str> {
+        self.tracestate.as_ref().map(|state| &state[..])
+    }
+
+    /// Returns the headers to send with requests made on this request's behalf to propagate its
+    /// trace.
+    pub fn headers(&self) -> Vec<Header<'static>> {
+        let mut headers = vec![Header::new(TRACEPARENT_HEADER, self.to_string())];
+        if let Some(ref state) = self.tracestate {
+            headers.push(Header::new(TRACESTATE_HEADER, state.clone()));
+        }
+        headers
+    }
+
+    /// Returns a new trace context for a request with the given `traceparent` header (or none),
+    /// and `tracestate` headers. If the `traceparent` header is malformed, per the spec, a new
+    /// trace is started and the `tracestate` is discarded.
+    pub fn for_request<'h, I>(header: Option<&str>, tracestate: I) -> TraceParent
+    where
+        I: IntoIterator<Item = &'h str>,
+    {
+        match header.map(|header| (header, parse(header))) {
+            Some((_, Some(parent))) => TraceParent {
+                trace_id: parent.trace_id,
+                parent_id: Some(parent.span_id),
+                span_id: random_nonzero_u64(),
+                flags: parent.flags & SAMPLED_FLAG,
+                tracestate: normalize_tracestate(tracestate),
+            },
+            Some((header, None)) => {
+                debug!("ignoring malformed traceparent {:?}", header);
+                TraceParent::start()
+            }
+            None => TraceParent::start(),
+        }
+    }
+
+    fn start() -> TraceParent {
+        let mut trace_id = 0;
+        while trace_id == 0 {
+            trace_id = (thread_rng().gen::<u64>() as u128) << 64
+                | thread_rng().gen::<u64>() as u128;
+        }
+        TraceParent {
+            trace_id,
+            parent_id: None,
+            span_id: random_nonzero_u64(),
+            flags: 0,
+            tracestate: None,
+        }
+    }
+}
+
+impl fmt::Display for TraceParent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "00-{:032x}-{:016x}-{:02x}", self.trace_id, self.span_id, self.flags)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for TraceParent {
+    type Error = RequestIDError;
+
+    async fn from_request(request: &'r Request<'_>) -> ReqOutcome<Self, Self::Error> {
+        tracked_guard(request, "trace context", |tracked| tracked.trace.clone())
+    }
+}
+
+impl Sentinel for TraceParent {
+    fn abort(rocket: &Rocket<Ignite>) -> bool {
+        fairing_missing(rocket, "TraceParent")
+    }
+}
+
+// Parent is what a valid traceparent header tells us about the upstream's span.
+#[derive(Debug, PartialEq, Eq)]
+struct Parent {
+    trace_id: u128,
+    span_id: u64,
+    flags: u8,
+}
+
+// parse parses a traceparent header following the spec's rules for parsing the fields it knows
+// about: versions it doesn't know must still start with those fields, and may add more after them.
+fn parse(header: &str) -> Option<Parent> {
+    let bytes = header.as_bytes();
+    if !header.is_ascii() || bytes.len() < 55 {
+        return None;
+    }
+    if bytes[2] != b'-' || bytes[35] != b'-' || bytes[52] != b'-' {
+        return None;
+    }
+
+    let version = hex(&header[0..2])?;
+    if version == 0xff || (version == 0 && bytes.len() != 55) {
+        return None;
+    }
+    if bytes.len() > 55 && bytes[55] != b'-' {
+        return None;
+    }
+
+    let trace_id = hex(&header[3..35])?;
+    let span_id = hex(&header[36..52])? as u64;
+    let flags = hex(&header[53..55])? as u8;
+    if trace_id == 0 || span_id == 0 {
+        return None;
+    }
+
+    Some(Parent {
+        trace_id,
+        span_id,
+        flags,
+    })
+}
+
+// normalize_tracestate combines tracestate headers into one, dropping members which are malformed,
+// duplicated, or beyond the spec's limits, rather than the whole header. It returns None if there
+// are no members left.
+fn normalize_tracestate<'h, I: IntoIterator<Item = &'h str>>(headers: I) -> Option<String> {
+    let mut members: Vec<&str> = Vec::new();
+    let mut keys: Vec<&str> = Vec::new();
+    for member in headers.into_iter().flat_map(|header| header.split(',')) {
+        let member = member.trim_matches(|c| c == ' ' || c == '\t');
+        if member.is_empty() {
+            continue;
+        }
+        let key = match tracestate_key(member) {
+            Some(key) => key,
+            None => {
+                debug!("dropping malformed tracestate member {:?}", member);
+                continue;
+            }
+        };
+        if keys.contains(&key) || members.len() == MAX_TRACESTATE_MEMBERS {
+            debug!("dropping tracestate member {:?}", member);
+            continue;
+        }
+        keys.push(key);
+        members.push(member);
+    }
+
+    // Each member after the first costs a comma, too.
+    let len = |members: &[&str]| {
+        members
+            .iter()
+            .map(|m| m.len() + 1)
+            .sum::<usize>()
+            .saturating_sub(1)
+    };
+    while len(&members) > MAX_TRACESTATE_LEN {
+        match members.iter().rposition(|m| m.len() > LONG_TRACESTATE_MEMBER) {
+            Some(i) => members.remove(i),
+            None => members.pop().unwrap(),
+        };
+    }
+
+    if members.is_empty() {
+        None
+    } else {
+        Some(members.join(","))
+    }
+}
+
+// tracestate_key returns the key of a valid tracestate list member.
+fn tracestate_key(member: &str) -> Option<&str> {
+    let mut kv = member.splitn(2, '=');
+    let key = kv.next().unwrap();
+    let value = kv.next()?;
+    if key.is_empty() {
+        return None;
+    }
+
+    let key_char = |c: u8| c.is_ascii_lowercase() || c.is_ascii_digit() || b"_-*/".contains(&c);
+    let valid_key = match key.find('@') {
+        None => {
+            key.len() <= 256 && key.as_bytes()[0].is_ascii_lowercase()
+                && key.bytes().all(key_char)
+        }
+        Some(at) => {
+            let (tenant, system) = (&key[..at], &key[at + 1..]);
+            !tenant.is_empty() && tenant.len() <= 241 && tenant.bytes().all(key_char)
+                && !system.is_empty() && system.len() <= 14
+                && system.as_bytes()[0].is_ascii_lowercase() && system.bytes().all(key_char)
+        }
+    };
+    let valid_value = !value.is_empty() && value.len() <= 256 && !value.ends_with(' ')
+        && value.bytes().all(|c| (0x20..=0x7e).contains(&c) && c != b',' && c != b'=');
+
+    if valid_key && valid_value {
+        Some(key)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_spec_examples() {
+        assert_eq!(
+            parse("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"),
+            Some(Parent {
+                trace_id: 0x0af7651916cd43dd8448eb211c80319c,
+                span_id: 0xb7ad6b7169203331,
+                flags: 0x01,
+            })
+        );
+        assert_eq!(
+            parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00"),
+            Some(Parent {
+                trace_id: 0x4bf92f3577b34da6a3ce929d0e0e4736,
+                span_id: 0x00f067aa0ba902b7,
+                flags: 0x00,
+            })
+        );
+    }
+
+    #[test]
+    fn parses_future_versions() {
+        let parent = parse("cc-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01-future");
+        assert_eq!(parent.unwrap().trace_id, 0x0af7651916cd43dd8448eb211c80319c);
+        assert!(parse("cc-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01x").is_none());
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        let malformed = [
+            "",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01-extra",
+            "ff-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            "00-00000000000000000000000000000000-b7ad6b7169203331-01",
+            "00-0af7651916cd43dd8448eb211c80319c-0000000000000000-01",
+            "00-0AF7651916CD43DD8448EB211C80319C-B7AD6B7169203331-01",
+            "00-0af7651916cd43dd8448eb211c80319-cb7ad6b7169203331-01",
+            "00-0af7651916cd43dd8448eb211c80319g-b7ad6b7169203331-01",
+            "00_0af7651916cd43dd8448eb211c80319c_b7ad6b7169203331_01",
+            "00-0af7651916cd43dd8448eb211c8031é-b7ad6b7169203331-01",
+        ];
+        for header in malformed.iter() {
+            assert_eq!(parse(header), None, "{:?} should be rejected", header);
+        }
+    }
+
+    #[test]
+    fn continues_trace() {
+        let header = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+        let trace = TraceParent::for_request(Some(header), vec!["congo=t61rcWkgMzE"]);
+
+        assert_eq!(trace.trace_id(), 0x0af7651916cd43dd8448eb211c80319c);
+        assert_eq!(trace.parent_id(), Some(0xb7ad6b7169203331));
+        assert_ne!(trace.span_id(), 0xb7ad6b7169203331);
+        assert!(trace.sampled());
+
+        let outgoing = trace.to_string();
+        assert_eq!(&outgoing[..36], &header[..36]);
+        assert_eq!(&outgoing[52..], "-01");
+        assert_eq!(parse(&outgoing).unwrap().span_id, trace.span_id());
+        assert_eq!(trace.tracestate(), Some("congo=t61rcWkgMzE"));
+        assert_eq!(trace.headers().len(), 2);
+    }
+
+    #[test]
+    fn starts_new_trace() {
+        let malformed = "00-00000000000000000000000000000000-b7ad6b7169203331-01";
+        for header in [None, Some(malformed)].iter() {
+            let trace = TraceParent::for_request(*header, vec!["congo=t61rcWkgMzE"]);
+
+            assert_ne!(trace.trace_id(), 0);
+            assert_eq!(trace.parent_id(), None);
+            assert_eq!(trace.tracestate(), None);
+            assert!(!trace.sampled());
+            assert!(parse(&trace.to_string()).is_some());
+        }
+    }
+
+    #[test]
+    fn combines_tracestate_headers() {
+        assert_eq!(
+            normalize_tracestate(vec!["rojo=00f067aa0ba902b7, ,congo=t61rcWkgMzE", "a@b=c"]),
+            Some("rojo=00f067aa0ba902b7,congo=t61rcWkgMzE,a@b=c".to_string())
+        );
+        assert_eq!(normalize_tracestate(vec![" , "]), None);
+        assert_eq!(normalize_tracestate(Vec::new()), None);
+    }
+
+    #[test]
+    fn drops_malformed_tracestate_members() {
+        let members = vec![
+            "Upper=1",
+            "1st=key",
+            "no-value=",
+            "no-equals",
+            "bad=va=lue",
+            "control=\u{7}",
+            "@system=x",
+            "tenant@=x",
+            "ok=1",
+            "ok=duplicate",
+        ];
+        assert_eq!(normalize_tracestate(members), Some("ok=1".to_string()));
+    }
+
+    #[test]
+    fn limits_tracestate_members() {
+        let members: Vec<String> = (0..40).map(|i| format!("k{}=v", i)).collect();
+        let state = normalize_tracestate(members.iter().map(|m| &m[..])).unwrap();
+
+        assert_eq!(state.split(',').count(), MAX_TRACESTATE_MEMBERS);
+        assert!(state.starts_with("k0=v,"));
+        assert!(state.ends_with(",k31=v"));
+    }
+
+    #[test]
+    fn limits_tracestate_len() {
+        let long = format!("long={}", "x".repeat(200));
+        let medium: Vec<String> = (0..6).map(|i| format!("m{}={}", i, "y".repeat(90))).collect();
+
+        let mut members = vec![&long[..]];
+        members.extend(medium.iter().map(|m| &m[..]));
+        let state = normalize_tracestate(members).unwrap();
+
+        // The long member goes first, and then members are dropped from the end.
+        assert!(!state.contains("long="));
+        assert!(state.len() <= MAX_TRACESTATE_LEN);
+        assert_eq!(state.split(',').count(), 5);
+        assert!(state.starts_with("m0="));
+    }
+}
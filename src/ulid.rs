@@ -0,0 +1,127 @@
+use rand::Rng;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::crockford::ALPHABET;
+
+// The number of characters in an encoded ULID.
+const LEN: usize = 26;
+
+// The number of random bits after the timestamp.
+const RANDOM_BITS: u32 = 80;
+const RANDOM_MAX: u128 = (1 << RANDOM_BITS) - 1;
+
+lazy_static!{
+    // The last ULID this process generated.
+    static ref LAST: Mutex<u128> = Mutex::new(0);
+}
+
+/// Returns a new ULID, as laid out in the [spec](https://github.com/ulid/spec): a 48-bit Unix
+/// timestamp in milliseconds, then 80 random bits.
+///
+/// Every ULID is greater than the previous one generated by this process: within a millisecond,
+/// the random bits of the previous ULID are incremented instead, as the spec asks. If they run
+/// out, or the clock goes backwards, the timestamp of the previous ULID is advanced or reused.
+pub fn generate<R: Rng>(rng: &mut R) -> u128 {
+    let mut last = LAST.lock().unwrap();
+    let now = now_ms();
+    *last = if now > timestamp_ms(*last) {
+        (now as u128) << RANDOM_BITS | rng.gen::<u128>() & RANDOM_MAX
+    } else if *last & RANDOM_MAX < RANDOM_MAX {
+        *last + 1
+    } else {
+        // The timestamp's 48 bits will outlast us.
+        ((timestamp_ms(*last) + 1) as u128) << RANDOM_BITS
+    };
+    *last
+}
+
+/// Returns the Unix timestamp, in milliseconds, that a ULID was generated at.
+pub fn timestamp_ms(ulid: u128) -> u64 {
+    (ulid >> RANDOM_BITS) as u64
+}
+
+/// Encodes a ULID as 26 characters of Crockford base32.
+pub fn encode(ulid: u128) -> String {
+    (0..LEN)
+        .rev()
+        .map(|i| ALPHABET[(ulid >> (i * 5) & 0x1f) as usize] as char)
+        .collect()
+}
+
+/// Decodes a ULID encoded by `encode`, in either case.
+pub fn decode(s: &str) -> Option<u128> {
+    // 26 characters hold 130 bits, so the first may only hold the top 3.
+    if s.len() != LEN || s.as_bytes()[0] > b'7' {
+        return None;
+    }
+    s.bytes().try_fold(0u128, |ulid, b| {
+        let digit = ALPHABET
+            .iter()
+            .position(|&c| c == b.to_ascii_uppercase())?;
+        Some(ulid << 5 | digit as u128)
+    })
+}
+
+fn now_ms() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock is before 1970");
+    now.as_secs() * 1000 + now.subsec_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+
+    #[test]
+    fn known_vectors() {
+        // From the spec, and the timestamp encoding tests of its reference implementation.
+        let ulid = decode("01ARZ3NDEKTSV4RRFFQ69G5FAV").unwrap();
+        assert_eq!(ulid, 0x0156_3e3a_b5d3_d676_4c61_efb9_9302_bd5b);
+        assert_eq!(encode(ulid), "01ARZ3NDEKTSV4RRFFQ69G5FAV");
+
+        let ulid = decode("01ARYZ6S410000000000000000").unwrap();
+        assert_eq!(timestamp_ms(ulid), 1_469_918_176_385);
+        assert_eq!(encode(ulid), "01ARYZ6S410000000000000000");
+
+        assert_eq!(decode("7ZZZZZZZZZZZZZZZZZZZZZZZZZ"), Some(u128::MAX));
+        assert_eq!(encode(0), "00000000000000000000000000");
+    }
+
+    #[test]
+    fn decodes_either_case() {
+        assert_eq!(
+            decode("01arz3ndektsv4rrffq69g5fav"),
+            decode("01ARZ3NDEKTSV4RRFFQ69G5FAV")
+        );
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        for malformed in [
+            "",
+            "01ARZ3NDEKTSV4RRFFQ69G5FA",
+            "01ARZ3NDEKTSV4RRFFQ69G5FAVV",
+            "81ARZ3NDEKTSV4RRFFQ69G5FAV",
+            "01ARZ3NDEKTSV4RRFFQ69G5FAU",
+            "01ARZ3NDEKTSV4RRFFQ69G5FA-",
+        ]
+        .iter()
+        {
+            assert_eq!(decode(malformed), None, "{}", malformed);
+        }
+    }
+
+    #[test]
+    fn increasing() {
+        let mut last = generate(&mut thread_rng());
+        assert!((now_ms() as i64 - timestamp_ms(last) as i64).abs() < 1000);
+        for _ in 0..10000 {
+            let next = generate(&mut thread_rng());
+            assert!(next > last);
+            last = next;
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use rand::Rng;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// The number of bits after the timestamp which are used to order UUIDs generated in the same
+// millisecond.
+const COUNTER_BITS: u32 = 12;
+const COUNTER_MAX: u16 = (1 << COUNTER_BITS) - 1;
+
+lazy_static!{
+    // The timestamp and counter of the last UUID this process generated.
+    static ref LAST: Mutex<(u64, u16)> = Mutex::new((0, 0));
+}
+
+/// Returns a new version 7 UUID, as laid out in RFC 9562: a 48-bit Unix timestamp in
+/// milliseconds, then the version, a 12-bit counter, the variant, and 62 random bits.
+///
+/// Every UUID is greater than the previous one generated by this process. Within a millisecond,
+/// the counter is incremented from a random starting point; if it runs out, or the clock goes
+/// backwards, the timestamp of the previous UUID is advanced or reused instead.
+pub fn generate<R: Rng>(rng: &mut R) -> u128 {
+    let (ms, counter) = {
+        let mut last = LAST.lock().unwrap();
+        let now = now_ms();
+        *last = if now > last.0 {
+            // Start in the lower half of the counter's range, so there's room to count up.
+            (now, rng.gen_range(0..COUNTER_MAX / 2))
+        } else if last.1 < COUNTER_MAX {
+            (last.0, last.1 + 1)
+        } else {
+            (last.0 + 1, 0)
+        };
+        *last
+    };
+    let random = rng.gen::<u64>() & ((1 << 62) - 1);
+
+    (ms as u128) << 80 | 0x7u128 << 76 | (counter as u128) << 64 | 0x2u128 << 62 | random as u128
+}
+
+/// Returns the Unix timestamp, in milliseconds, that a version 7 UUID was generated at.
+pub fn timestamp_ms(uuid: u128) -> u64 {
+    (uuid >> 80) as u64
+}
+
+fn now_ms() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock is before 1970");
+    now.as_secs() * 1000 + now.subsec_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+
+    #[test]
+    fn layout() {
+        let uuid = generate(&mut thread_rng());
+        assert_eq!((uuid >> 76) & 0xf, 7);
+        assert_eq!((uuid >> 62) & 0x3, 0x2);
+        assert!((now_ms() as i64 - timestamp_ms(uuid) as i64).abs() < 1000);
+    }
+
+    #[test]
+    fn increasing() {
+        let mut last = generate(&mut thread_rng());
+        for _ in 0..10000 {
+            let next = generate(&mut thread_rng());
+            assert!(next > last);
+            last = next;
+        }
+    }
+}
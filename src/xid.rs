@@ -0,0 +1,159 @@
+use rand::{thread_rng, Rng};
+use std::process;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::{fnv1a, hostname};
+
+/// The largest machine ID an xid can hold; see `RequestIDFairing::with_xid_machine_id`.
+pub const MAX_XID_MACHINE_ID: u32 = 0xff_ffff;
+
+// The base32hex alphabet xids are encoded in, lowercase, as the reference implementation does.
+const ALPHABET: &[u8; 32] = b"0123456789abcdefghijklmnopqrstuv";
+
+// The number of characters in an encoded xid.
+const LEN: usize = 20;
+
+// The layout of an xid, from the top: a 32-bit Unix timestamp in seconds, a 24-bit machine ID, the
+// low 16 bits of the process ID, and a 24-bit counter.
+const TIMESTAMP_SHIFT: u32 = 64;
+const MACHINE_SHIFT: u32 = 40;
+const PID_SHIFT: u32 = 24;
+const COUNTER_MAX: u32 = 0xff_ffff;
+
+lazy_static!{
+    // The counter for the next xid this process generates, which starts at a random value, as in
+    // the reference implementation.
+    static ref COUNTER: AtomicU32 = AtomicU32::new(thread_rng().gen());
+    // This machine's ID, derived from its hostname.
+    static ref MACHINE_ID: u32 = derive_machine_id();
+}
+
+/// Returns this machine's xid machine ID: a hash of its hostname, or, if that can't be found, a
+/// random value chosen once per process.
+pub fn machine_id() -> u32 {
+    *MACHINE_ID
+}
+
+fn derive_machine_id() -> u32 {
+    match hostname() {
+        Some(hostname) => fnv1a(&hostname) as u32 & MAX_XID_MACHINE_ID,
+        None => {
+            warn!("unable to find this machine's hostname; using a random xid machine id");
+            thread_rng().gen::<u32>() & MAX_XID_MACHINE_ID
+        }
+    }
+}
+
+/// Returns a new [xid](https://github.com/rs/xid) for the given machine, as 96 bits.
+pub fn generate(machine_id: u32) -> u128 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock is before 1970");
+    let counter = COUNTER.fetch_add(1, Ordering::Relaxed) & COUNTER_MAX;
+    compose(now.as_secs() as u32, machine_id, process::id() as u16, counter)
+}
+
+// compose lays out the parts of an xid.
+fn compose(timestamp: u32, machine_id: u32, pid: u16, counter: u32) -> u128 {
+    (timestamp as u128) << TIMESTAMP_SHIFT
+        | ((machine_id & MAX_XID_MACHINE_ID) as u128) << MACHINE_SHIFT
+        | (pid as u128) << PID_SHIFT
+        | (counter & COUNTER_MAX) as u128
+}
+
+/// Returns the time an xid was generated at.
+pub fn timestamp(xid: u128) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs((xid >> TIMESTAMP_SHIFT) as u32 as u64)
+}
+
+/// Returns the ID of the machine which generated an xid.
+pub fn machine(xid: u128) -> u32 {
+    (xid >> MACHINE_SHIFT) as u32 & MAX_XID_MACHINE_ID
+}
+
+/// Returns the low 16 bits of the ID of the process which generated an xid.
+#[cfg(test)]
+pub fn pid(xid: u128) -> u16 {
+    (xid >> PID_SHIFT) as u16
+}
+
+/// Returns an xid's counter.
+#[cfg(test)]
+pub fn counter(xid: u128) -> u32 {
+    xid as u32 & COUNTER_MAX
+}
+
+/// Encodes an xid as 20 characters of base32hex. The 96 bits don't fill the last character, so
+/// they're followed by 4 zero bits.
+pub fn encode(xid: u128) -> String {
+    let padded = xid << 4;
+    (0..LEN)
+        .rev()
+        .map(|i| ALPHABET[(padded >> (i * 5) & 0x1f) as usize] as char)
+        .collect()
+}
+
+/// Decodes an xid encoded by `encode`.
+pub fn decode(s: &str) -> Option<u128> {
+    if s.len() != LEN {
+        return None;
+    }
+    let padded = s.bytes().try_fold(0u128, |padded, b| {
+        let digit = ALPHABET.iter().position(|&c| c == b)?;
+        Some(padded << 5 | digit as u128)
+    })?;
+    // Anything in the padding means the xid was encoded by something else, or mangled.
+    if padded & 0xf != 0 {
+        return None;
+    }
+    Some(padded >> 4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_vectors() {
+        // From the reference implementation's tests.
+        let xid = decode("9m4e2mr0ui3e8a215n4g").unwrap();
+        assert_eq!(xid, 0x4d88_e15b_60f4_86e4_2841_2dc9);
+        assert_eq!(timestamp(xid), UNIX_EPOCH + Duration::from_secs(1_300_816_219));
+        assert_eq!(machine(xid), 0x60_f486);
+        assert_eq!(pid(xid), 0xe428);
+        assert_eq!(counter(xid), 4_271_561);
+        assert_eq!(compose(1_300_816_219, 0x60_f486, 0xe428, 4_271_561), xid);
+        assert_eq!(encode(xid), "9m4e2mr0ui3e8a215n4g");
+
+        assert_eq!(encode(0), "00000000000000000000");
+        assert_eq!(decode("vvvvvvvvvvvvvvvvvvvg"), Some((1 << 96) - 1));
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        for malformed in [
+            "",
+            "9m4e2mr0ui3e8a215n4",
+            "9m4e2mr0ui3e8a215n4gg",
+            "9M4E2MR0UI3E8A215N4G",
+            "9m4e2mr0ui3e8a215n4w",
+            "9m4e2mr0ui3e8a215n4h",
+        ]
+        .iter()
+        {
+            assert_eq!(decode(malformed), None, "{}", malformed);
+        }
+    }
+
+    #[test]
+    fn generates() {
+        let first = generate(0x12_3456);
+        let second = generate(0x12_3456);
+        assert_ne!(first, second);
+        assert_eq!(decode(&encode(first)), Some(first));
+        assert_eq!(machine(first), 0x12_3456);
+        assert_eq!(pid(first), process::id() as u16);
+        assert!(machine_id() <= MAX_XID_MACHINE_ID);
+    }
+}